@@ -0,0 +1,253 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::meta::ParseNestedMeta;
+use syn::{Expr, GenericArgument, ItemFn, LitInt, LitStr, PathArguments, ReturnType, Type};
+
+// 与redisutils::key::MAX_DB_INDEX一致
+const MAX_DB_INDEX: u8 = 15;
+
+/// 缓存宏参数
+#[derive(Default)]
+pub struct CacheArgs {
+    /// key模板，{}中为参数表达式，如"user:{id}"
+    key: Option<LitStr>,
+    /// 过期时间(秒)
+    ttl: Option<u64>,
+    /// key所在的db，未指定时与字符串key的规则相同
+    db: Option<u8>,
+}
+
+impl CacheArgs {
+    /// 解析单个参数，用于syn::meta::parser
+    pub fn parse(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("key") {
+            self.key = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("ttl") {
+            self.ttl = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+        } else if meta.path.is_ident("db") {
+            let lit: LitInt = meta.value()?.parse()?;
+            let db: u8 = lit.base10_parse()?;
+            if db > MAX_DB_INDEX {
+                return Err(syn::Error::new(lit.span(), "db must be between 0 and 15"));
+            }
+            self.db = Some(db);
+        } else {
+            return Err(meta.error("unsupported argument, expected `key`, `ttl` or `db`"));
+        }
+        Ok(())
+    }
+
+    // 生成RedisKey表达式
+    fn key_expr(&self) -> syn::Result<TokenStream> {
+        let key = self
+            .key
+            .as_ref()
+            .ok_or_else(|| syn::Error::new(Span::call_site(), "missing `key` argument"))?;
+        let template = key.value();
+        if template.is_empty() {
+            return Err(syn::Error::new(key.span(), "key must not be empty"));
+        }
+
+        let (format, args) = parse_template(&template, key.span())?;
+        let format = LitStr::new(&format, key.span());
+        let key = quote! { ::std::format!(#format #(, #args)*) };
+        Ok(match self.db {
+            Some(db) => quote! { crate::common::redisutils::key::RedisKey::new(#db, #key) },
+            None => quote! { crate::common::redisutils::key::RedisKey::from(#key) },
+        })
+    }
+}
+
+// 将key模板转换为format!格式串及参数表达式
+// {expr}替换为{}，{{和}}为转义的花括号
+fn parse_template(template: &str, span: Span) -> syn::Result<(String, Vec<Expr>)> {
+    let mut format = String::new();
+    let mut args = Vec::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                format.push_str("{{");
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                format.push_str("}}");
+            }
+            '{' => {
+                let mut expr = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => expr.push(c),
+                        None => return Err(syn::Error::new(span, "unclosed `{` in key")),
+                    }
+                }
+                let expr = expr.trim();
+                if expr.is_empty() {
+                    return Err(syn::Error::new(span, "empty `{}` in key"));
+                }
+                let expr = syn::parse_str::<Expr>(expr).map_err(|e| {
+                    syn::Error::new(span, format!("invalid expression `{}` in key: {}", expr, e))
+                })?;
+                format.push_str("{}");
+                args.push(expr);
+            }
+            '}' => return Err(syn::Error::new(span, "unmatched `}` in key")),
+            c => format.push(c),
+        }
+    }
+    Ok((format, args))
+}
+
+// 返回值为Result(含RedisResult等别名)时返回Ok的类型
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else {
+        return None;
+    };
+    let segment = type_path.path.segments.last()?;
+    if !segment.ident.to_string().ends_with("Result") {
+        return None;
+    }
+    let PathArguments::AngleBracketed(generics) = &segment.arguments else {
+        return None;
+    };
+    match generics.args.first()? {
+        GenericArgument::Type(ok_ty) => Some(ok_ty),
+        _ => None,
+    }
+}
+
+// 校验函数签名，返回值类型
+fn check_fn<'a>(func: &'a ItemFn, macro_name: &str) -> syn::Result<Option<&'a Type>> {
+    if func.sig.asyncness.is_none() {
+        return Err(syn::Error::new_spanned(
+            func.sig.fn_token,
+            format!("#[{}] can only be applied to async fn", macro_name),
+        ));
+    }
+    match &func.sig.output {
+        ReturnType::Default => Ok(None),
+        ReturnType::Type(_, ty) => {
+            if let Type::ImplTrait(_) = ty.as_ref() {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    format!("#[{}] does not support impl Trait return types", macro_name),
+                ));
+            }
+            Ok(Some(ty))
+        }
+    }
+}
+
+/// 生成#[cached]的展开代码
+/// 先按key读取缓存，命中时直接返回，未命中时执行原函数并写入缓存
+/// 返回值为Result时只缓存Ok的值
+pub fn expand_cached(args: CacheArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let ret_ty = check_fn(&func, "cached")?
+        .ok_or_else(|| syn::Error::new_spanned(&func.sig, "#[cached] requires a return value"))?;
+    let ttl = match args.ttl {
+        Some(0) | None => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "missing or zero `ttl` argument",
+            ));
+        }
+        Some(ttl) => ttl,
+    };
+    let key_expr = args.key_expr()?;
+
+    let (hit, store) = match result_ok_type(ret_ty) {
+        Some(ok_ty) => (
+            quote! {
+                if let ::std::option::Option::Some(__cached) =
+                    crate::common::redisutils::cache::get::<#ok_ty>(&__cache_key).await
+                {
+                    return ::std::result::Result::Ok(__cached);
+                }
+            },
+            quote! {
+                if let ::std::result::Result::Ok(__value) = &__result {
+                    crate::common::redisutils::cache::set(&__cache_key, __value, __ttl).await;
+                }
+            },
+        ),
+        None => (
+            quote! {
+                if let ::std::option::Option::Some(__cached) =
+                    crate::common::redisutils::cache::get::<#ret_ty>(&__cache_key).await
+                {
+                    return __cached;
+                }
+            },
+            quote! {
+                crate::common::redisutils::cache::set(&__cache_key, &__result, __ttl).await;
+            },
+        ),
+    };
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = &func;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __cache_key = #key_expr;
+            let __ttl = ::std::time::Duration::from_secs(#ttl);
+            // key不存在或反序列化失败时视为未命中
+            #hit
+            let __result: #ret_ty = async move #block.await;
+            #store
+            __result
+        }
+    })
+}
+
+/// 生成#[cache_evict]的展开代码
+/// 执行原函数后删除key，返回值为Result时只在Ok时删除
+pub fn expand_cache_evict(args: CacheArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let ret_ty = check_fn(&func, "cache_evict")?;
+    if args.ttl.is_some() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "#[cache_evict] does not accept `ttl`",
+        ));
+    }
+    let key_expr = args.key_expr()?;
+
+    let evict = quote! {
+        crate::common::redisutils::cache::del(&__cache_key).await;
+    };
+    let evict = match ret_ty.and_then(result_ok_type) {
+        Some(_) => quote! {
+            if __result.is_ok() {
+                #evict
+            }
+        },
+        None => evict,
+    };
+    let ret_ty = match ret_ty {
+        Some(ty) => quote! { #ty },
+        None => quote! { () },
+    };
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = &func;
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            // 参数可能在函数体中被移动，先生成key
+            let __cache_key = #key_expr;
+            let __result: #ret_ty = async move #block.await;
+            #evict
+            __result
+        }
+    })
+}
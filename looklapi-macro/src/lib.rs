@@ -1,7 +1,8 @@
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{Data, DeriveInput, Fields, Path, parse_macro_input};
+use syn::{Data, DeriveInput, Fields, ItemFn, Path, parse_macro_input};
 
+mod cache_macro;
 mod proxy_micro;
 
 // 定义派生宏入口，用法：#[proxy(TraitName)]
@@ -50,3 +51,32 @@ pub fn proxy(attr: TokenStream, item: TokenStream) -> TokenStream {
 
     TokenStream::from(expanded)
 }
+
+// 缓存宏入口，用法：#[cached(key = "user:{id}", ttl = 300)]
+// 只能用于async fn，返回值序列化为json后写入当前的缓存存储(默认为redis)，后续调用直接读取缓存
+// key中的{}为参数表达式，指定db = N时使用对应的db，否则与字符串key的db规则相同
+#[proc_macro_attribute]
+pub fn cached(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = cache_macro::CacheArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+    let func = parse_macro_input!(item as ItemFn);
+
+    cache_macro::expand_cached(args, func)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// 缓存清除宏入口，用法：#[cache_evict(key = "user:{id}")]
+// 用于修改数据的async fn，执行成功后删除对应的缓存
+#[proc_macro_attribute]
+pub fn cache_evict(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = cache_macro::CacheArgs::default();
+    let parser = syn::meta::parser(|meta| args.parse(meta));
+    parse_macro_input!(attr with parser);
+    let func = parse_macro_input!(item as ItemFn);
+
+    cache_macro::expand_cache_evict(args, func)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -28,18 +28,158 @@ pub struct Mongodb {
     pub uri: String,
 }
 
+/// redis部署模式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisMode {
+    /// 单机，使用host和port
+    #[default]
+    Standalone,
+    /// 哨兵，使用master_name和sentinels，主节点切换后自动连接新的主节点
+    Sentinel,
+    /// 集群，使用nodes，只能使用db 0
+    Cluster,
+}
+
 #[derive(Clone, Deserialize)]
 pub struct Redis {
+    /// 部署模式，默认单机
+    #[serde(default)]
+    pub mode: RedisMode,
+    #[serde(default)]
     pub host: String,
+    #[serde(default)]
     pub port: i32,
+    /// ACL用户名，为空时使用default用户
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
     pub password: String,
-    /// 超时时间(毫秒)
+    /// 超时时间(毫秒)，未单独配置连接或命令超时时间时使用，为0时不限制
+    #[serde(default)]
     pub timeout: i32,
+    /// 建立连接超时时间(毫秒)
+    pub connect_timeout: Option<i32>,
+    /// 等待命令响应超时时间(毫秒)
+    pub command_timeout: Option<i32>,
+    /// 哨兵模式下的主节点名称
+    #[serde(default)]
+    pub master_name: String,
+    /// 哨兵节点地址，格式为host:port
+    #[serde(default)]
+    pub sentinels: Vec<String>,
+    /// 哨兵节点的ACL用户名
+    #[serde(default)]
+    pub sentinel_username: String,
+    /// 哨兵节点的密码，为空时不认证
+    #[serde(default)]
+    pub sentinel_password: String,
+    /// 集群模式下的初始节点地址，格式为host:port
+    #[serde(default)]
+    pub nodes: Vec<String>,
+    /// TLS配置，配置后所有节点(包括哨兵)均使用TLS连接
+    pub tls: Option<RedisTls>,
+    /// 未指定db的key是否按旧的"N_"前缀选择db，默认使用db 0
+    #[serde(default)]
+    pub legacy_db_prefix: bool,
+    /// 是否允许读穿缓存使用进程内的本地缓存，开启后缓存失效时通知所有实例，默认关闭
+    #[serde(default)]
+    pub local_cache: bool,
+}
+
+impl Redis {
+    /// 建立连接超时时间(毫秒)
+    pub fn connect_timeout_ms(&self) -> i32 {
+        self.connect_timeout.unwrap_or(self.timeout)
+    }
+
+    /// 等待命令响应超时时间(毫秒)
+    pub fn command_timeout_ms(&self) -> i32 {
+        self.command_timeout.unwrap_or(self.timeout)
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RedisTls {
+    /// 自定义CA证书文件(PEM)，未配置时使用系统根证书
+    pub ca_cert: Option<String>,
+    /// 客户端证书文件(PEM)，与client_key一起用于双向认证
+    pub client_cert: Option<String>,
+    /// 客户端私钥文件(PEM)
+    pub client_key: Option<String>,
 }
 
 #[derive(Clone, Deserialize)]
 pub struct RabbitMQ {
     pub address: String,
+    /// 集群备用节点地址，与address一起轮询连接
+    #[serde(default)]
+    pub fallback_addresses: Vec<String>,
+    /// 发布连接数量限制
+    #[serde(default = "RabbitMQ::default_conn_limit")]
+    pub conn_limit: i32,
+    /// 每个连接的channel数量限制
+    #[serde(default = "RabbitMQ::default_ch_limit_for_conn")]
+    pub ch_limit_for_conn: i32,
+    /// channel空闲超时时间(分钟)
+    #[serde(default = "RabbitMQ::default_ch_idle_timeout_min")]
+    pub ch_idle_timeout_min: i32,
+    /// 连接空闲超时时间(分钟)
+    #[serde(default = "RabbitMQ::default_conn_idle_timeout_min")]
+    pub conn_idle_timeout_min: i32,
+    /// 心跳间隔(秒)，未设置时与服务端协商
+    pub heartbeat: Option<u16>,
+    /// 连接名称，显示在管理界面中
+    pub connection_name: Option<String>,
+    /// TLS配置，地址需使用amqps协议
+    pub tls: Option<RabbitMQTls>,
+    /// gzip消息解压后的最大字节数，超出时按解码失败处理
+    #[serde(default = "RabbitMQ::default_max_decompressed_size")]
+    pub max_decompressed_size: usize,
+}
+
+impl RabbitMQ {
+    fn default_conn_limit() -> i32 {
+        100
+    }
+
+    fn default_ch_limit_for_conn() -> i32 {
+        100
+    }
+
+    fn default_ch_idle_timeout_min() -> i32 {
+        5
+    }
+
+    fn default_conn_idle_timeout_min() -> i32 {
+        10
+    }
+
+    fn default_max_decompressed_size() -> usize {
+        crate::common::mqutils::codec::DEFAULT_MAX_DECOMPRESSED_SIZE
+    }
+
+    /// 所有节点地址，address在前
+    pub fn addresses(&self) -> Vec<String> {
+        std::iter::once(self.address.clone())
+            .chain(self.fallback_addresses.iter().cloned())
+            .filter(|address| !address.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Clone, Deserialize)]
+pub struct RabbitMQTls {
+    /// 自定义CA证书链文件(PEM)
+    pub ca_cert: Option<String>,
+    /// 客户端证书文件(PEM)，与client_key一起用于双向认证
+    pub client_cert: Option<String>,
+    /// 客户端私钥文件(PKCS#8 PEM)
+    pub client_key: Option<String>,
+    /// 客户端PKCS#12证书文件，与client_cert/client_key二选一
+    pub pkcs12: Option<String>,
+    /// PKCS#12证书密码
+    pub pkcs12_password: Option<String>,
 }
 
 #[derive(Clone, Deserialize)]
@@ -5,4 +5,7 @@ pub struct AppEventInitCompleted;
 pub struct AppEventBeanInjected;
 
 // 应用配置加载完成
-pub struct AppEventConfigInitialized;
\ No newline at end of file
+pub struct AppEventConfigInitialized;
+
+// MQ连接池初始化完成
+pub struct AppEventMqReady;
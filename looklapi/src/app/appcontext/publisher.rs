@@ -79,7 +79,8 @@ impl AppEventPublisher {
 
         if let Some(obs) = observers.get(&event_type_id) {
             println!("Found {} observers for event type", obs.len());
-            let rt_handle = self.rt_handle.clone();
+            // 优先使用当前运行时，发布器创建时所在的运行时可能已结束
+            let rt_handle = Handle::try_current().unwrap_or_else(|_| self.rt_handle.clone());
 
             // 使用Arc包装事件，确保所有观察者都能访问到事件
             let event_arc = std::sync::Arc::new(event);
@@ -1,3 +1,6 @@
 pub mod loggers;
 pub mod mqutils;
-pub mod redisutils;
\ No newline at end of file
+pub mod overridable;
+pub mod redisutils;
+#[cfg(test)]
+pub mod testutil;
@@ -0,0 +1,220 @@
+use crate::common::mqutils::codec::{self, CodecError, Envelope};
+use crate::common::mqutils::context::MessageContext;
+use crate::common::mqutils::publisher::{self, PublishError};
+use crate::common::mqutils::retry::{DLQ_SUFFIX, HEADER_DEAD_REASON, HEADER_ORIGIN_QUEUE};
+use crate::common::mqutils::transport::{self, Delivery, QueueBrowser, TransportError};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// 单次浏览死信队列的最大消息数
+/// RabbitMQ不支持按偏移读取，浏览时需逐条拉取并在结束后重新入队
+pub const MAX_BROWSE: usize = 1000;
+/// 每页最大消息数
+pub const MAX_PAGE_SIZE: usize = 100;
+
+/// 死信管理错误
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+    /// 参数无效
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// 死信队列中没有该消息
+    #[error("message not found: {0}")]
+    NotFound(String),
+    /// 消息编解码失败
+    #[error("codec error: {0}")]
+    Codec(#[from] CodecError),
+    /// 传输层错误
+    #[error("transport error: {0}")]
+    Transport(#[from] TransportError),
+    /// 重新发布失败
+    #[error("publish error: {0}")]
+    Publish(#[from] PublishError),
+}
+
+/// 死信消息
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    /// 消息id，消息无法解码时为空
+    pub guid: String,
+    /// 原始队列
+    pub origin_queue: Option<String>,
+    /// 进入死信的原因
+    pub reason: Option<String>,
+    /// 进入死信前的重试次数
+    pub current_retry: i32,
+    /// 首次发布时间
+    pub timespan: Option<DateTime<Utc>>,
+    /// 编码格式
+    pub content_type: Option<String>,
+    /// 消息内容，无法解码时为None
+    pub content: Option<serde_json::Value>,
+}
+
+impl DeadLetter {
+    // 从投递的死信消息中提取
+    fn from_delivery(delivery: &Delivery) -> Self {
+        let envelope = codec::decode_delivery(delivery).ok();
+        DeadLetter {
+            guid: envelope
+                .as_ref()
+                .map(|envelope| envelope.guid.clone())
+                .unwrap_or_default(),
+            origin_queue: header(delivery, HEADER_ORIGIN_QUEUE),
+            reason: header(delivery, HEADER_DEAD_REASON),
+            current_retry: envelope
+                .as_ref()
+                .map(|envelope| envelope.current_retry)
+                .unwrap_or_default(),
+            timespan: envelope.as_ref().map(|envelope| envelope.timespan),
+            content_type: delivery
+                .properties
+                .content_type()
+                .as_ref()
+                .map(|v| v.to_string()),
+            content: envelope.and_then(|envelope| envelope.decode_content().ok()),
+        }
+    }
+}
+
+/// 分页浏览死信队列，消息浏览后按原顺序留在队列中
+pub async fn list_dead_letters(
+    dlq: &str,
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<DeadLetter>, AdminError> {
+    check_dlq(dlq)?;
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        return Err(AdminError::InvalidArgument(format!(
+            "limit must between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    if offset + limit > MAX_BROWSE {
+        return Err(AdminError::InvalidArgument(format!(
+            "offset + limit must not greater than {}",
+            MAX_BROWSE
+        )));
+    }
+
+    let mut browser = transport::current().browse(dlq).await?;
+    let result = async {
+        let mut dead_letters = Vec::with_capacity(limit);
+        for idx in 0..offset + limit {
+            let Some(delivery) = browser.next().await? else {
+                break;
+            };
+            if idx >= offset {
+                dead_letters.push(DeadLetter::from_delivery(&delivery));
+            }
+        }
+        Ok(dead_letters)
+    }
+    .await;
+    browser.close().await;
+    result
+}
+
+/// 将死信消息重新发布到原始队列并从死信队列中删除，重试次数清零
+/// content不为空时以其替换消息内容后发布
+pub async fn replay_dead_letter(
+    dlq: &str,
+    guid: &str,
+    content: Option<serde_json::Value>,
+) -> Result<(), AdminError> {
+    check_dlq(dlq)?;
+
+    let mut browser = transport::current().browse(dlq).await?;
+    let result = async {
+        let (delivery, mut envelope) = find(browser.as_mut(), guid).await?;
+        let origin_queue = header(&delivery, HEADER_ORIGIN_QUEUE).ok_or_else(|| {
+            AdminError::InvalidArgument(format!("origin queue unknown: {}", guid))
+        })?;
+
+        if let Some(content) = content {
+            envelope.content = envelope.codec.encode(&content)?;
+        }
+        envelope.current_retry = 0;
+        let payload = envelope.to_bytes()?;
+
+        // 沿用原消息的属性，去掉死信相关的消息头
+        let ctx = MessageContext::from_delivery(&delivery, &envelope, dlq);
+        let mut options = ctx.forward_options();
+        options.headers.remove(HEADER_ORIGIN_QUEUE);
+        options.headers.remove(HEADER_DEAD_REASON);
+
+        publisher::pub_existing_queue_msg_internal(&origin_queue, &payload, &options).await?;
+        delivery.ack().await?;
+        tracing::info!("死信消息已重新发布: {} - {} -> {}", guid, dlq, origin_queue);
+        Ok(())
+    }
+    .await;
+    browser.close().await;
+    result
+}
+
+/// 删除死信消息，guid为空时清空死信队列，返回删除的消息数
+pub async fn purge_dead_letters(dlq: &str, guid: Option<&str>) -> Result<u32, AdminError> {
+    check_dlq(dlq)?;
+
+    let Some(guid) = guid else {
+        let count = transport::current().purge_queue(dlq).await?;
+        tracing::info!("死信队列已清空: {} - {}条", dlq, count);
+        return Ok(count);
+    };
+
+    let mut browser = transport::current().browse(dlq).await?;
+    let result = async {
+        let (delivery, _) = find(browser.as_mut(), guid).await?;
+        delivery.ack().await?;
+        tracing::info!("死信消息已删除: {} - {}", guid, dlq);
+        Ok(1)
+    }
+    .await;
+    browser.close().await;
+    result
+}
+
+// 只允许管理死信队列
+fn check_dlq(dlq: &str) -> Result<(), AdminError> {
+    if dlq.ends_with(DLQ_SUFFIX) && dlq.len() > DLQ_SUFFIX.len() {
+        Ok(())
+    } else {
+        Err(AdminError::InvalidArgument(format!(
+            "not a dead letter queue: {}",
+            dlq
+        )))
+    }
+}
+
+// 在死信队列中查找消息，最多浏览MAX_BROWSE条
+async fn find(
+    browser: &mut dyn QueueBrowser,
+    guid: &str,
+) -> Result<(Delivery, Envelope), AdminError> {
+    if guid.is_empty() {
+        return Err(AdminError::InvalidArgument("empty guid".to_string()));
+    }
+
+    for _ in 0..MAX_BROWSE {
+        let Some(delivery) = browser.next().await? else {
+            break;
+        };
+        if let Ok(envelope) = codec::decode_delivery(&delivery)
+            && envelope.guid == guid
+        {
+            return Ok((delivery, envelope));
+        }
+    }
+    Err(AdminError::NotFound(guid.to_string()))
+}
+
+// 读取字符串类型的消息头
+fn header(delivery: &Delivery, key: &str) -> Option<String> {
+    let headers = delivery.properties.headers().as_ref()?;
+    match headers.inner().get(key)? {
+        lapin::types::AMQPValue::LongString(v) => Some(v.to_string()),
+        lapin::types::AMQPValue::ShortString(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,339 @@
+use crate::common::mqutils::models::MqMessage;
+use crate::common::mqutils::transport::Delivery;
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// content-type: json
+pub const CONTENT_TYPE_JSON: &str = "application/json";
+/// content-type: MessagePack
+pub const CONTENT_TYPE_MSGPACK: &str = "application/msgpack";
+/// content-type: CBOR
+pub const CONTENT_TYPE_CBOR: &str = "application/cbor";
+/// content-type: 旧版本发布的消息，按json处理
+pub const CONTENT_TYPE_LEGACY: &str = "application/octet-stream";
+/// content-encoding: gzip
+pub const CONTENT_ENCODING_GZIP: &str = "gzip";
+/// 默认解压后的最大字节数 64MB
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+// 解压后的最大字节数，防止压缩炸弹耗尽内存
+static MAX_DECOMPRESSED_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_DECOMPRESSED_SIZE);
+
+/// 设置解压后的最大字节数
+pub fn set_max_decompressed_size(limit: usize) {
+    MAX_DECOMPRESSED_SIZE.store(limit, Ordering::Relaxed);
+}
+
+/// 解压后的最大字节数
+pub fn max_decompressed_size() -> usize {
+    MAX_DECOMPRESSED_SIZE.load(Ordering::Relaxed)
+}
+
+/// 编解码错误
+#[derive(Debug, thiserror::Error)]
+pub enum CodecError {
+    /// 不支持的content-type
+    #[error("unsupported content type: {0}")]
+    UnsupportedContentType(String),
+    /// 不支持的content-encoding
+    #[error("unsupported content encoding: {0}")]
+    UnsupportedContentEncoding(String),
+    /// 编码失败
+    #[error("encode failed: {0}")]
+    Encode(String),
+    /// 解码失败
+    #[error("decode failed: {0}")]
+    Decode(String),
+    /// 解压后超过最大字节数
+    #[error("decompressed size exceeds limit of {0} bytes")]
+    TooLarge(usize),
+}
+
+/// 消息编解码器
+pub trait MessageCodec {
+    /// 编码后的content-type
+    fn content_type(&self) -> &'static str;
+
+    /// 编码
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// 解码
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError>;
+}
+
+/// json编解码器
+pub struct JsonCodec;
+
+impl MessageCodec for JsonCodec {
+    fn content_type(&self) -> &'static str {
+        CONTENT_TYPE_JSON
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(|err| CodecError::Encode(err.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(data).map_err(|err| CodecError::Decode(err.to_string()))
+    }
+}
+
+/// MessagePack编解码器，结构体按字段名编码以兼容字段增减
+pub struct MsgPackCodec;
+
+impl MessageCodec for MsgPackCodec {
+    fn content_type(&self) -> &'static str {
+        CONTENT_TYPE_MSGPACK
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        rmp_serde::to_vec_named(value).map_err(|err| CodecError::Encode(err.to_string()))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        rmp_serde::from_slice(data).map_err(|err| CodecError::Decode(err.to_string()))
+    }
+}
+
+/// CBOR编解码器
+pub struct CborCodec;
+
+impl MessageCodec for CborCodec {
+    fn content_type(&self) -> &'static str {
+        CONTENT_TYPE_CBOR
+    }
+
+    fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        let mut buf = Vec::new();
+        ciborium::into_writer(value, &mut buf)
+            .map_err(|err| CodecError::Encode(err.to_string()))?;
+        Ok(buf)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        ciborium::from_reader(data).map_err(|err| CodecError::Decode(err.to_string()))
+    }
+}
+
+/// 消息编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Codec {
+    /// json，与旧版本消息格式兼容
+    #[default]
+    Json,
+    /// MessagePack
+    MsgPack,
+    /// CBOR
+    Cbor,
+}
+
+impl Codec {
+    /// 编码后的content-type
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Codec::Json => JsonCodec.content_type(),
+            Codec::MsgPack => MsgPackCodec.content_type(),
+            Codec::Cbor => CborCodec.content_type(),
+        }
+    }
+
+    /// 根据消息的content-type选择编解码器
+    /// 未设置content-type或为旧版本的octet-stream时按json处理
+    pub fn from_content_type(content_type: Option<&str>) -> Result<Self, CodecError> {
+        // 忽略charset等参数
+        let content_type = content_type
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim())
+            .unwrap_or_default();
+        match content_type {
+            "" | CONTENT_TYPE_JSON | CONTENT_TYPE_LEGACY => Ok(Codec::Json),
+            CONTENT_TYPE_MSGPACK | "application/x-msgpack" => Ok(Codec::MsgPack),
+            CONTENT_TYPE_CBOR => Ok(Codec::Cbor),
+            other => Err(CodecError::UnsupportedContentType(other.to_string())),
+        }
+    }
+
+    /// 编码
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        match self {
+            Codec::Json => JsonCodec.encode(value),
+            Codec::MsgPack => MsgPackCodec.encode(value),
+            Codec::Cbor => CborCodec.encode(value),
+        }
+    }
+
+    /// 解码
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T, CodecError> {
+        match self {
+            Codec::Json => JsonCodec.decode(data),
+            Codec::MsgPack => MsgPackCodec.decode(data),
+            Codec::Cbor => CborCodec.decode(data),
+        }
+    }
+}
+
+/// MQ消息
+/// json格式下按MqMessage编码(消息内容为json字符串)，与旧版本兼容
+/// 二进制格式下消息内容直接以同一格式编码为字节，避免二次序列化
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// 消息id
+    pub guid: String,
+    /// 首次发布时间
+    pub timespan: DateTime<Utc>,
+    /// 当前重试次数
+    pub current_retry: i32,
+    /// 编码格式
+    pub codec: Codec,
+    /// 编码后的消息内容
+    pub content: Vec<u8>,
+}
+
+// 二进制格式的消息结构
+#[derive(Serialize, Deserialize)]
+struct BinaryEnvelope {
+    guid: String,
+    timespan: DateTime<Utc>,
+    current_retry: i32,
+    #[serde(with = "serde_bytes")]
+    content: Vec<u8>,
+}
+
+impl Envelope {
+    /// 以指定格式编码消息内容
+    #[allow(dead_code, reason = "供业务代码预先编码消息")]
+    pub fn new<T: Serialize + ?Sized>(codec: Codec, msg: &T) -> Result<Self, CodecError> {
+        Ok(Envelope {
+            guid: uuid::Uuid::new_v4().to_string().replace('-', ""),
+            timespan: Utc::now(),
+            current_retry: 0,
+            codec,
+            content: codec.encode(msg)?,
+        })
+    }
+
+    /// 解码消息内容
+    pub fn decode_content<T: DeserializeOwned>(&self) -> Result<T, CodecError> {
+        self.codec.decode(&self.content)
+    }
+
+    /// 编码为消息体
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        match self.codec {
+            Codec::Json => {
+                let meta_msg = MqMessage {
+                    guid: self.guid.clone(),
+                    timespan: self.timespan,
+                    current_retry: self.current_retry,
+                    json_content: String::from_utf8(self.content.clone())
+                        .map_err(|err| CodecError::Encode(err.to_string()))?,
+                };
+                self.codec.encode(&meta_msg)
+            }
+            codec => codec.encode(&BinaryEnvelope {
+                guid: self.guid.clone(),
+                timespan: self.timespan,
+                current_retry: self.current_retry,
+                content: self.content.clone(),
+            }),
+        }
+    }
+
+    /// 从消息体解码
+    pub fn from_bytes(codec: Codec, data: &[u8]) -> Result<Self, CodecError> {
+        match codec {
+            Codec::Json => {
+                let meta_msg: MqMessage = codec.decode(data)?;
+                Ok(meta_msg.into())
+            }
+            codec => {
+                let envelope: BinaryEnvelope = codec.decode(data)?;
+                Ok(Envelope {
+                    guid: envelope.guid,
+                    timespan: envelope.timespan,
+                    current_retry: envelope.current_retry,
+                    codec,
+                    content: envelope.content,
+                })
+            }
+        }
+    }
+}
+
+impl From<MqMessage> for Envelope {
+    fn from(meta_msg: MqMessage) -> Self {
+        Envelope {
+            guid: meta_msg.guid,
+            timespan: meta_msg.timespan,
+            current_retry: meta_msg.current_retry,
+            codec: Codec::Json,
+            content: meta_msg.json_content.into_bytes(),
+        }
+    }
+}
+
+/// 消息体超过阈值时gzip压缩，返回消息体与content-encoding
+pub fn compress(
+    data: Vec<u8>,
+    threshold: Option<usize>,
+) -> Result<(Vec<u8>, Option<&'static str>), CodecError> {
+    match threshold {
+        Some(threshold) if data.len() > threshold => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder
+                .write_all(&data)
+                .map_err(|err| CodecError::Encode(err.to_string()))?;
+            let compressed = encoder
+                .finish()
+                .map_err(|err| CodecError::Encode(err.to_string()))?;
+            Ok((compressed, Some(CONTENT_ENCODING_GZIP)))
+        }
+        _ => Ok((data, None)),
+    }
+}
+
+/// 按content-encoding解压消息体，解压后超过max_decompressed_size时返回TooLarge
+pub fn decompress(data: &[u8], content_encoding: Option<&str>) -> Result<Vec<u8>, CodecError> {
+    decompress_with_limit(data, content_encoding, max_decompressed_size())
+}
+
+// 按content-encoding解压消息体，解压后最多limit字节
+pub(crate) fn decompress_with_limit(
+    data: &[u8],
+    content_encoding: Option<&str>,
+    limit: usize,
+) -> Result<Vec<u8>, CodecError> {
+    match content_encoding.map(|v| v.trim()).unwrap_or_default() {
+        "" | "identity" => Ok(data.to_vec()),
+        CONTENT_ENCODING_GZIP => {
+            let mut buf = Vec::new();
+            // 多读一个字节用于判断是否超出限制
+            GzDecoder::new(data)
+                .take(limit as u64 + 1)
+                .read_to_end(&mut buf)
+                .map_err(|err| CodecError::Decode(err.to_string()))?;
+            if buf.len() > limit {
+                return Err(CodecError::TooLarge(limit));
+            }
+            Ok(buf)
+        }
+        other => Err(CodecError::UnsupportedContentEncoding(other.to_string())),
+    }
+}
+
+// 按消息属性中的content-type与content-encoding解码投递的消息
+pub(crate) fn decode_delivery(delivery: &Delivery) -> Result<Envelope, CodecError> {
+    let properties = &delivery.properties;
+    let codec = Codec::from_content_type(properties.content_type().as_ref().map(|v| v.as_str()))?;
+    let data = decompress(
+        &delivery.data,
+        properties.content_encoding().as_ref().map(|v| v.as_str()),
+    )?;
+    Envelope::from_bytes(codec, &data)
+}
@@ -1,36 +1,129 @@
+use crate::app::appcontext::events::AppEventMqReady;
+use crate::app::appcontext::observer::AppObserver;
+use crate::common::mqutils::codec::{self, Codec, Envelope};
 use crate::common::mqutils::consts::ConsumerType;
-use crate::common::mqutils::models::MqMessage;
-use crate::common::mqutils::rabbitmq_pool::RabbitmqConnPool;
+use crate::common::mqutils::context::{self, MessageContext};
+use crate::common::mqutils::dedupe::{self, Admission, DedupeOptions};
+use crate::common::mqutils::handler::{self, ConsumeError, ConsumeHandler};
+use crate::common::mqutils::partition;
+use crate::common::mqutils::registry;
+use crate::common::mqutils::retry::{self, RetryBackoff};
+use crate::common::mqutils::topology;
+use crate::common::mqutils::transport::{
+    self, ConsumeSpec, Delivery, ExchangeSpec, QueueSpec, Subscription,
+};
+use crate::register_observer_for;
+use crate::request_context;
 use futures::StreamExt;
 use lapin;
 use lazy_static::lazy_static;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use serde_json;
-use std::sync::Arc;
+use std::future::Future;
 use std::sync::Mutex;
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc;
-use std::thread;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::time::Duration;
 use tokio;
+use tokio::runtime::{Runtime, RuntimeFlavor};
+use tokio::sync::{mpsc, watch};
 use tracing;
+use tracing::Instrument;
 
 lazy_static! {
     static ref CONSUMER_CONTAINER: Arc<Mutex<Vec<Arc<Consumer>>>> =
         Arc::new(Mutex::new(Vec::new()));
     static ref HAS_CONSUMER_BIND: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    // 是否已绑定消费者容器中的消费者，之后创建的消费者需单独绑定
+    static ref CONTAINER_BOUND: Mutex<bool> = Mutex::new(false);
+}
+
+// 在tokio运行时外同步处理消息时使用的运行时
+fn blocking_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build consumer runtime failed")
+    })
 }
 
 // 消费者
 pub struct Consumer {
     pub r#type: ConsumerType,
     pub max_retry: u32,
-    pub consume: Arc<dyn Fn(serde_json::Value) -> bool + Send + Sync>,
+    /// 消息处理器
+    handler: ConsumeHandler,
     pub exchange: String,
     pub route_key: String,
     pub concurrency: u32,
     pub prefetch_count: u32,
     pub parallel: bool,
     pub topic_pattern: String,
+    /// 是否为分区消费者，route_key为分区队列名称
+    /// 分区消费者顺序处理消息，失败时原地重试以保证同一分区内的消息顺序
+    pub partitioned: bool,
+    /// 重试退避策略
+    retry_backoff: Mutex<RetryBackoff>,
+    /// 已绑定的消费通道数
+    bound: AtomicU32,
+    /// 是否等待重连
+    reconnecting: AtomicBool,
+    /// 连续绑定失败次数
+    failed_attempts: AtomicU32,
+    /// 最近一次绑定失败原因
+    last_error: Mutex<Option<String>>,
+    /// 处理中的消息数
+    in_flight: AtomicU32,
+    /// 消息去重配置，None表示不去重
+    dedupe: Mutex<Option<DedupeOptions>>,
+    /// 消费者实例标识，用于命名广播消费者的实例队列
+    instance_id: String,
+}
+
+/// 消费者运行状态快照
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsumerState {
+    /// 消费者类型
+    pub r#type: ConsumerType,
+    /// 消费者名称(队列/交换器)
+    pub name: String,
+    /// 期望的消费通道数
+    pub expected: u32,
+    /// 已绑定的消费通道数
+    pub bound: u32,
+    /// 是否等待重连
+    pub reconnecting: bool,
+    /// 连续绑定失败次数
+    pub failed_attempts: u32,
+    /// 最近一次绑定失败原因
+    pub last_error: Option<String>,
+    /// 处理中的消息数
+    pub in_flight: u32,
+    /// 死信队列
+    pub dead_letter_queue: String,
+}
+
+/// 创建按需使用的消费者
+/// 消费者初始化前创建的随其他消费者一起绑定，初始化后创建的立即绑定
+pub fn add_consumer(create: impl FnOnce() -> Arc<Consumer>) -> Arc<Consumer> {
+    let bound = CONTAINER_BOUND.lock().unwrap();
+    let consumer = create();
+    if *bound {
+        let consumer = consumer.clone();
+        tokio::spawn(async move {
+            ConsumerBinder::get_instance().bind_consumer(consumer).await;
+        });
+    }
+    consumer
+}
+
+/// 获取所有已注册消费者的运行状态
+pub fn consumer_states() -> Vec<ConsumerState> {
+    let container = CONSUMER_CONTAINER.lock().unwrap();
+    container.iter().map(|consumer| consumer.state()).collect()
 }
 
 impl Consumer {
@@ -42,6 +135,120 @@ impl Consumer {
         parallel: bool,
         max_retry: u32,
         consume: impl Fn(serde_json::Value) -> bool + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Self::create_work_queue(
+            route_key,
+            concurrency,
+            prefetch_count,
+            parallel,
+            max_retry,
+            handler::legacy_handler(consume),
+        )
+    }
+
+    /// 新建类型化的工作队列消费者
+    /// 消息内容反序列化为T后交由异步处理器处理，处理器返回的错误决定消息的去向
+    #[allow(dead_code, reason = "类型化消费者的创建入口，由业务代码调用")]
+    pub fn work_queue<T, F, Fut, E>(
+        route_key: &str,
+        concurrency: u32,
+        prefetch_count: u32,
+        parallel: bool,
+        max_retry: u32,
+        handler: F,
+    ) -> Arc<Self>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<ConsumeError>,
+    {
+        Self::create_work_queue(
+            route_key,
+            concurrency,
+            prefetch_count,
+            parallel,
+            max_retry,
+            handler::typed_handler(handler),
+        )
+    }
+
+    /// 新建RPC消费者
+    /// 基于工作队列，处理器的返回值通过请求的reply_to回复给rpc::call的调用方
+    /// 处理失败时回复错误而不重试，调用方收到RpcError::Remote
+    #[allow(dead_code, reason = "RPC服务端的创建入口，由业务代码调用")]
+    pub fn rpc<Req, Resp, F, Fut, E>(
+        route_key: &str,
+        concurrency: u32,
+        prefetch_count: u32,
+        parallel: bool,
+        handler: F,
+    ) -> Arc<Self>
+    where
+        Req: DeserializeOwned + Send + 'static,
+        Resp: Serialize + Send + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, E>> + Send + 'static,
+        E: std::fmt::Display,
+    {
+        Self::create_work_queue(
+            route_key,
+            concurrency,
+            prefetch_count,
+            parallel,
+            1,
+            handler::rpc_handler(handler),
+        )
+    }
+
+    /// 新建分区消费者，每个分区队列对应一个消费者
+    /// 分区队列为单活消费者队列，多个实例同时消费时每个分区只有一个实例在处理，
+    /// 同一分区键的消息按发布顺序逐条处理，不同分区之间并行处理
+    /// 处理失败时按重试退避策略原地重试，超过最大重试次数后转入死信队列
+    #[allow(dead_code, reason = "分区消费者的创建入口，由业务代码调用")]
+    pub fn partitioned<T, F, Fut, E>(
+        route_key: &str,
+        partitions: u32,
+        max_retry: u32,
+        handler: F,
+    ) -> Vec<Arc<Self>>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<ConsumeError>,
+    {
+        if route_key.is_empty() {
+            panic!("invalid routekey");
+        }
+
+        if partitions < 1 {
+            panic!("partitioned consumer partitions must greater than 0");
+        }
+
+        if max_retry < 1 {
+            panic!("partitioned consumer maxRetry must greater than 0");
+        }
+
+        let handler = handler::typed_handler(handler);
+        (0..partitions)
+            .map(|index| {
+                Self::register(Self {
+                    route_key: partition::partition_queue(route_key, index),
+                    partitioned: true,
+                    ..Self::base(ConsumerType::WorkQueue, max_retry, handler.clone())
+                })
+            })
+            .collect()
+    }
+
+    fn create_work_queue(
+        route_key: &str,
+        concurrency: u32,
+        prefetch_count: u32,
+        parallel: bool,
+        max_retry: u32,
+        handler: ConsumeHandler,
     ) -> Arc<Self> {
         if route_key.is_empty() {
             panic!("invalid routekey");
@@ -59,22 +266,13 @@ impl Consumer {
             panic!("workqueue consumer maxRetry must greater than 0");
         }
 
-        let consumer = Arc::new(Self {
-            r#type: ConsumerType::WorkQueue,
-            max_retry,
-            consume: Arc::new(consume),
-            exchange: String::new(),
+        Self::register(Self {
             route_key: route_key.to_string(),
             concurrency,
             prefetch_count,
             parallel,
-            topic_pattern: String::new(),
-        });
-
-        let mut container = CONSUMER_CONTAINER.lock().unwrap();
-        container.push(consumer.clone());
-
-        consumer
+            ..Self::base(ConsumerType::WorkQueue, max_retry, handler)
+        })
     }
 
     // 新建广播消费者
@@ -83,6 +281,22 @@ impl Consumer {
         max_retry: u32,
         consume: impl Fn(serde_json::Value) -> bool + Send + Sync + 'static,
     ) -> Arc<Self> {
+        Self::create_broadcast(exchange, max_retry, handler::legacy_handler(consume))
+    }
+
+    /// 新建类型化的广播消费者
+    #[allow(dead_code, reason = "类型化消费者的创建入口，由业务代码调用")]
+    pub fn broadcast<T, F, Fut, E>(exchange: &str, max_retry: u32, handler: F) -> Arc<Self>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<ConsumeError>,
+    {
+        Self::create_broadcast(exchange, max_retry, handler::typed_handler(handler))
+    }
+
+    fn create_broadcast(exchange: &str, max_retry: u32, handler: ConsumeHandler) -> Arc<Self> {
         if exchange.is_empty() {
             panic!("invalid exchange");
         }
@@ -91,22 +305,10 @@ impl Consumer {
             panic!("broadcast consumer maxRetry must greater than 0");
         }
 
-        let consumer = Arc::new(Self {
-            r#type: ConsumerType::Broadcast,
-            max_retry,
-            consume: Arc::new(consume),
+        Self::register(Self {
             exchange: exchange.to_string(),
-            route_key: String::new(),
-            concurrency: 1,
-            prefetch_count: 1,
-            parallel: false,
-            topic_pattern: String::new(),
-        });
-
-        let mut container = CONSUMER_CONTAINER.lock().unwrap();
-        container.push(consumer.clone());
-
-        consumer
+            ..Self::base(ConsumerType::Broadcast, max_retry, handler)
+        })
     }
 
     // 新建topic消费者
@@ -115,6 +317,42 @@ impl Consumer {
         topic_pattern: &str,
         max_retry: u32,
         consume: impl Fn(serde_json::Value) -> bool + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Self::create_topic(
+            exchange,
+            topic_pattern,
+            max_retry,
+            handler::legacy_handler(consume),
+        )
+    }
+
+    /// 新建类型化的topic消费者
+    #[allow(dead_code, reason = "类型化消费者的创建入口，由业务代码调用")]
+    pub fn topic<T, F, Fut, E>(
+        exchange: &str,
+        topic_pattern: &str,
+        max_retry: u32,
+        handler: F,
+    ) -> Arc<Self>
+    where
+        T: DeserializeOwned + Send + 'static,
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send + 'static,
+        E: Into<ConsumeError>,
+    {
+        Self::create_topic(
+            exchange,
+            topic_pattern,
+            max_retry,
+            handler::typed_handler(handler),
+        )
+    }
+
+    fn create_topic(
+        exchange: &str,
+        topic_pattern: &str,
+        max_retry: u32,
+        handler: ConsumeHandler,
     ) -> Arc<Self> {
         if exchange.is_empty() {
             panic!("invalid exchange");
@@ -128,31 +366,71 @@ impl Consumer {
             panic!("topic consumer maxRetry must greater than 0");
         }
 
-        let consumer = Arc::new(Self {
-            r#type: ConsumerType::Topic,
-            max_retry,
-            consume: Arc::new(consume),
+        Self::register(Self {
             exchange: exchange.to_string(),
+            topic_pattern: topic_pattern.to_string(),
+            ..Self::base(ConsumerType::Topic, max_retry, handler)
+        })
+    }
+
+    // 各类消费者的公共字段，单通道、逐条处理，未绑定
+    fn base(r#type: ConsumerType, max_retry: u32, handler: ConsumeHandler) -> Self {
+        Self {
+            r#type,
+            max_retry,
+            handler,
+            exchange: String::new(),
             route_key: String::new(),
             concurrency: 1,
             prefetch_count: 1,
             parallel: false,
-            topic_pattern: topic_pattern.to_string(),
-        });
+            topic_pattern: String::new(),
+            partitioned: false,
+            retry_backoff: Mutex::new(RetryBackoff::default()),
+            bound: AtomicU32::new(0),
+            reconnecting: AtomicBool::new(false),
+            failed_attempts: AtomicU32::new(0),
+            last_error: Mutex::new(None),
+            in_flight: AtomicU32::new(0),
+            dedupe: Mutex::new(None),
+            instance_id: uuid::Uuid::new_v4().simple().to_string(),
+        }
+    }
 
+    // 放入消费者容器
+    fn register(consumer: Self) -> Arc<Self> {
+        let consumer = Arc::new(consumer);
         let mut container = CONSUMER_CONTAINER.lock().unwrap();
         container.push(consumer.clone());
 
         consumer
     }
 
-    // 接收到消息
+    // 接收到消息，同步等待处理完成
+    // 不在tokio运行时内调用时使用共享的运行时处理
+    // 单线程运行时无法在等待期间继续处理其他任务，此时不处理消息并返回false，
+    // 异步代码中应使用on_received_async
+    #[allow(dead_code, reason = "保留给同步调用的旧代码")]
     pub fn on_received(&self, msg: &str) -> bool {
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::CurrentThread => {
+                tracing::error!("单线程tokio运行时内不能同步处理消息，请使用on_received_async");
+                false
+            }
+            Ok(handle) => {
+                tokio::task::block_in_place(|| handle.block_on(self.on_received_async(msg)))
+            }
+            Err(_) => blocking_runtime().block_on(self.on_received_async(msg)),
+        }
+    }
+
+    // 接收到消息，异步处理
+    pub async fn on_received_async(&self, msg: &str) -> bool {
         if msg.is_empty() {
             return true;
         }
 
-        let meta_msg: MqMessage = match serde_json::from_str(msg) {
+        let meta_msg = match Envelope::from_bytes(Codec::Json, msg.as_bytes()) {
             Ok(msg) => msg,
             Err(err) => {
                 tracing::error!("消息反序列化失败: {:?}", err);
@@ -160,31 +438,353 @@ impl Consumer {
             }
         };
 
-        if meta_msg.json_content.is_empty() {
-            return true;
+        self.consume_message(&meta_msg).await.is_ok()
+    }
+
+    // 处理消息体
+    async fn consume_message(&self, meta_msg: &Envelope) -> Result<(), ConsumeError> {
+        if meta_msg.content.is_empty() {
+            return Ok(());
         }
 
-        let msg_value: serde_json::Value = match serde_json::from_str(&meta_msg.json_content) {
-            Ok(value) => value,
-            Err(err) => {
-                tracing::error!("消息内容反序列化失败: {:?}", err);
-                return false;
+        (self.handler)(meta_msg.clone()).await
+    }
+
+    // 处理消息体，分区消费者失败时原地重试
+    // 重试期间分区内后续消息等待，超过最大重试次数后返回最后一次的错误
+    async fn consume_in_place(&self, meta_msg: &mut Envelope) -> Result<(), ConsumeError> {
+        loop {
+            match self.consume_message(meta_msg).await {
+                Err(ConsumeError::Retry(reason))
+                    if self.partitioned && meta_msg.current_retry < self.max_retry as i32 =>
+                {
+                    meta_msg.current_retry += 1;
+                    let delay_ms = self
+                        .get_retry_backoff()
+                        .delay_ms(meta_msg.current_retry as u32);
+                    tracing::warn!(
+                        "分区消息处理失败，{}毫秒后原地重试: {} - {}",
+                        delay_ms,
+                        meta_msg.guid,
+                        reason
+                    );
+                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+                }
+                result => return result,
             }
-        };
+        }
+    }
 
-        (self.consume)(msg_value)
+    /// 设置重试退避策略，需在消费者绑定前设置
+    #[allow(dead_code, reason = "由业务代码在创建消费者后按需设置")]
+    pub fn set_retry_backoff(&self, backoff: RetryBackoff) {
+        if let Ok(mut guard) = self.retry_backoff.lock() {
+            *guard = backoff;
+        }
     }
+
+    /// 获取重试退避策略
+    pub fn get_retry_backoff(&self) -> RetryBackoff {
+        if let Ok(guard) = self.retry_backoff.lock() {
+            guard.clone()
+        } else {
+            RetryBackoff::default()
+        }
+    }
+
+    /// 开启消息去重，需在消费者绑定前设置
+    /// 已处理过的消息直接确认，正在被其他消费者处理的消息稍后重新入队
+    #[allow(dead_code, reason = "由业务代码在创建消费者后按需开启")]
+    pub fn set_dedupe(&self, options: DedupeOptions) {
+        if let Ok(mut guard) = self.dedupe.lock() {
+            *guard = Some(options);
+        }
+    }
+
+    /// 获取消息去重配置
+    pub fn get_dedupe(&self) -> Option<DedupeOptions> {
+        self.dedupe.lock().ok().and_then(|guard| guard.clone())
+    }
+
+    /// 获取死信队列名称
+    pub fn dead_letter_queue(&self) -> String {
+        match self.r#type {
+            ConsumerType::Broadcast => format!("{}{}", self.exchange, retry::DLQ_SUFFIX),
+            ConsumerType::Topic => format!("{}{}", self.topic_queue_name(), retry::DLQ_SUFFIX),
+            _ => format!("{}{}", self.route_key, retry::DLQ_SUFFIX),
+        }
+    }
+
+    // 未设置去重作用域时的默认作用域
+    // 广播消息需由每个实例各自处理，使用实例队列区分
+    fn dedupe_scope(&self) -> String {
+        match self.r#type {
+            ConsumerType::Broadcast => self.broadcast_queue_name(),
+            _ => self.name(),
+        }
+    }
+
+    // 广播消费者的实例队列名称，重连后不变
+    // 不使用服务端生成的amq.gen-名称: amq.前缀的队列不允许客户端声明，
+    // 且每次重连名称都会变化，延迟重试的消息无法投递回来
+    pub(crate) fn broadcast_queue_name(&self) -> String {
+        format!("broadcast_{}_{}", self.exchange, self.instance_id)
+    }
+
+    /// 消费者名称，用于日志与状态展示
+    pub fn name(&self) -> String {
+        match self.r#type {
+            ConsumerType::WorkQueue => self.route_key.clone(),
+            ConsumerType::Broadcast => self.exchange.clone(),
+            ConsumerType::Topic => format!("{} - {}", self.exchange, self.topic_pattern),
+            _ => String::new(),
+        }
+    }
+
+    /// 获取运行状态快照
+    pub fn state(&self) -> ConsumerState {
+        ConsumerState {
+            r#type: self.r#type,
+            name: self.name(),
+            expected: self.concurrency,
+            bound: self.bound.load(Ordering::Relaxed),
+            reconnecting: self.reconnecting.load(Ordering::Relaxed),
+            failed_attempts: self.failed_attempts.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().ok().and_then(|err| err.clone()),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            dead_letter_queue: self.dead_letter_queue(),
+        }
+    }
+
+    // 绑定成功
+    fn on_bound(&self) {
+        self.bound.fetch_add(1, Ordering::Relaxed);
+        self.reconnecting.store(false, Ordering::Relaxed);
+        self.failed_attempts.store(0, Ordering::Relaxed);
+    }
+
+    // 绑定失败
+    fn on_bind_failed(&self, err: &str) {
+        self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(err.to_string());
+        }
+    }
+
+    // 消费循环结束
+    fn on_unbound(&self) {
+        let _ = self
+            .bound
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+
+    // topic消费者的队列名称
+    fn topic_queue_name(&self) -> String {
+        format!("topic_{}_{}", self.exchange, self.topic_pattern)
+    }
+}
+
+// 处理一条投递的消息，根据处理结果确认、重试或转入死信队列
+// 处理过程在消息上下文与trace span中执行，消息的trace id同时作为请求id向下游传递
+async fn handle_delivery(
+    consumer: &Consumer,
+    delivery: Delivery,
+    origin_queue: &str,
+) {
+    let meta_msg = match codec::decode_delivery(&delivery) {
+        Ok(msg) => msg,
+        Err(err) => {
+            // 无法解析的消息重试无意义，按原样转入死信队列
+            tracing::error!("消息反序列化失败，转入死信队列: {:?}", err);
+            let reason = format!("decode failed: {}", err);
+            if retry::dead_letter_undecodable(&delivery, consumer, origin_queue, &reason).await {
+                ack(&delivery).await;
+            } else {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let _ = delivery.nack(true).await;
+            }
+            return;
+        }
+    };
+
+    let ctx = MessageContext::from_delivery(&delivery, &meta_msg, origin_queue);
+    let trace_id = ctx.trace_id.clone().unwrap_or_default();
+    let span = tracing::info_span!(
+        "mq_message",
+        trace_id = %trace_id,
+        guid = %meta_msg.guid,
+        queue = %origin_queue
+    );
+
+    let process = context::scope(
+        ctx.clone(),
+        process_delivery(consumer, &delivery, meta_msg, origin_queue, &ctx),
+    )
+    .instrument(span);
+    if trace_id.is_empty() {
+        process.await;
+    } else {
+        request_context::scope_request_id(trace_id, process).await;
+    }
+}
+
+// 处理消息并确认、重试或转入死信队列
+async fn process_delivery(
+    consumer: &Consumer,
+    delivery: &Delivery,
+    mut meta_msg: Envelope,
+    origin_queue: &str,
+    ctx: &MessageContext,
+) {
+    // 开启去重时先获取处理租约
+    let lease = match consumer.get_dedupe() {
+        Some(options) => {
+            let scope = consumer.dedupe_scope();
+            match dedupe::admit(&options, &scope, &meta_msg.guid).await {
+                Admission::Proceed(lease) => lease,
+                Admission::Duplicate => {
+                    tracing::info!("重复消息已跳过: {}", meta_msg.guid);
+                    ack(delivery).await;
+                    return;
+                }
+                Admission::InFlight => {
+                    tracing::info!("消息正在被其他消费者处理，稍后重新入队: {}", meta_msg.guid);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    let _ = delivery.nack(true).await;
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
+    let err = match consumer.consume_in_place(&mut meta_msg).await {
+        Ok(()) => {
+            if meta_msg.current_retry > 0 {
+                retry::retry_success(&meta_msg, consumer.r#type);
+            }
+            // 先记录已处理再确认，确认失败后重新投递的消息会被跳过
+            if let Some(lease) = lease {
+                lease.complete().await;
+            }
+            ack(delivery).await;
+            return;
+        }
+        Err(err) => err,
+    };
+
+    // 处理失败，释放租约以便重试或重新入队后再次处理
+    if let Some(lease) = lease {
+        lease.release().await;
+    }
+
+    let handled = match err {
+        ConsumeError::Retry(reason) => {
+            tracing::warn!("消息处理失败: {} - {}", meta_msg.guid, reason);
+            retry::retry(meta_msg, consumer, origin_queue, ctx).await
+        }
+        ConsumeError::DeadLetter(reason) => {
+            tracing::error!("消息转入死信队列: {} - {}", meta_msg.guid, reason);
+            retry::dead_letter(&meta_msg, consumer, origin_queue, &reason, ctx).await
+        }
+        ConsumeError::Requeue(reason) => {
+            tracing::warn!("消息重新入队: {} - {}", meta_msg.guid, reason);
+            false
+        }
+        ConsumeError::Discard(reason) => {
+            tracing::warn!("消息已丢弃: {} - {}", meta_msg.guid, reason);
+            true
+        }
+    };
+
+    if handled {
+        ack(delivery).await;
+    } else {
+        // 稍后重新入队，避免热循环
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let _ = delivery.nack(true).await;
+    }
+}
+
+// 确认消息
+async fn ack(delivery: &Delivery) {
+    let _ = delivery.ack().await;
+}
+
+// 处理消息并维护处理中的消息数
+async fn handle_delivery_tracked(
+    consumer: &Consumer,
+    delivery: Delivery,
+    origin_queue: &str,
+) {
+    consumer.in_flight.fetch_add(1, Ordering::Relaxed);
+    handle_delivery(consumer, delivery, origin_queue).await;
+    consumer.in_flight.fetch_sub(1, Ordering::Relaxed);
+}
+
+// 启动消息消费循环
+// 开启并行消费时每条消息在独立任务中处理，并发上限由prefetch_count决定
+// 消费流因通道或连接断开而终止时，释放订阅并交由重连处理器重新绑定
+// 收到关闭信号时取消订阅，不再接收新消息，已接收的消息继续处理完成
+fn spawn_delivery_loop(consumer: Arc<Consumer>, subscription: Subscription) {
+    let Subscription {
+        queue: origin_queue,
+        mut deliveries,
+        control,
+    } = subscription;
+    let mut shutdown_rx = ConsumerBinder::get_instance().shutdown_tx.subscribe();
+    tokio::spawn(async move {
+        loop {
+            let delivery = tokio::select! {
+                delivery = deliveries.next() => delivery,
+                _ = async { let _ = shutdown_rx.wait_for(|shutdown| *shutdown).await; } => {
+                    control.cancel().await;
+                    consumer.on_unbound();
+                    return;
+                }
+            };
+
+            match delivery {
+                Some(Ok(delivery)) => {
+                    if consumer.parallel {
+                        let consumer = consumer.clone();
+                        let origin_queue = origin_queue.clone();
+                        tokio::spawn(async move {
+                            handle_delivery_tracked(&consumer, delivery, &origin_queue).await;
+                        });
+                    } else {
+                        handle_delivery_tracked(&consumer, delivery, &origin_queue).await;
+                    }
+                }
+                Some(Err(err)) => {
+                    tracing::error!("消费消息失败: {} - {:?}", consumer.name(), err);
+                    break;
+                }
+                None => break,
+            }
+        }
+
+        if ConsumerBinder::get_instance().is_shutdown() {
+            consumer.on_unbound();
+            return;
+        }
+
+        tracing::warn!("消费流已终止，准备重新绑定: {}", consumer.name());
+        consumer.on_unbound();
+        control.close().await;
+        ConsumerBinder::get_instance().request_reconnect(consumer);
+    });
 }
 
 // 处理工作队列重连
 async fn handle_workqueue_reconnect(
-    rx: mpsc::Receiver<Arc<Consumer>>,
-    tx: Option<mpsc::Sender<Arc<Consumer>>>,
+    mut rx: mpsc::UnboundedReceiver<Arc<Consumer>>,
+    tx: Option<mpsc::UnboundedSender<Arc<Consumer>>>,
 ) {
     let mut last_reconnect_ok = true;
     let mut continue_err = 0;
 
-    for consumer in rx {
+    while let Some(consumer) = rx.recv().await {
         if !last_reconnect_ok {
             if continue_err > 10 {
                 continue_err = 10;
@@ -198,7 +798,7 @@ async fn handle_workqueue_reconnect(
             tokio::time::sleep(Duration::from_secs(wait_secs as u64)).await;
         }
 
-        let binder = ConsumerBinder::new();
+        let binder = ConsumerBinder::get_instance();
         last_reconnect_ok = binder.bind_work_queue_consumer(consumer.clone()).await;
         if last_reconnect_ok {
             continue_err = 0;
@@ -217,13 +817,13 @@ async fn handle_workqueue_reconnect(
 
 // 处理广播重连
 async fn handle_broadcast_reconnect(
-    rx: mpsc::Receiver<Arc<Consumer>>,
-    tx: Option<mpsc::Sender<Arc<Consumer>>>,
+    mut rx: mpsc::UnboundedReceiver<Arc<Consumer>>,
+    tx: Option<mpsc::UnboundedSender<Arc<Consumer>>>,
 ) {
     let mut last_reconnect_ok = true;
     let mut continue_err = 0;
 
-    for consumer in rx {
+    while let Some(consumer) = rx.recv().await {
         if !last_reconnect_ok {
             if continue_err > 10 {
                 continue_err = 10;
@@ -237,7 +837,7 @@ async fn handle_broadcast_reconnect(
             tokio::time::sleep(Duration::from_secs(wait_secs as u64)).await;
         }
 
-        let binder = ConsumerBinder::new();
+        let binder = ConsumerBinder::get_instance();
         last_reconnect_ok = binder.bind_broadcast_consumer(consumer.clone()).await;
         if last_reconnect_ok {
             continue_err = 0;
@@ -256,13 +856,13 @@ async fn handle_broadcast_reconnect(
 
 // 处理topic重连
 async fn handle_topic_reconnect(
-    rx: mpsc::Receiver<Arc<Consumer>>,
-    tx: Option<mpsc::Sender<Arc<Consumer>>>,
+    mut rx: mpsc::UnboundedReceiver<Arc<Consumer>>,
+    tx: Option<mpsc::UnboundedSender<Arc<Consumer>>>,
 ) {
     let mut last_reconnect_ok = true;
     let mut continue_err = 0;
 
-    for consumer in rx {
+    while let Some(consumer) = rx.recv().await {
         if !last_reconnect_ok {
             if continue_err > 10 {
                 continue_err = 10;
@@ -276,7 +876,7 @@ async fn handle_topic_reconnect(
             tokio::time::sleep(Duration::from_secs(wait_secs as u64)).await;
         }
 
-        let binder = ConsumerBinder::new();
+        let binder = ConsumerBinder::get_instance();
         last_reconnect_ok = binder.bind_topic_consumer(consumer.clone()).await;
         if last_reconnect_ok {
             continue_err = 0;
@@ -294,46 +894,96 @@ async fn handle_topic_reconnect(
     }
 }
 
-// 消费者绑定器
-struct ConsumerBinder {
-    workqueue_reconnect_ch: Option<mpsc::Sender<Arc<Consumer>>>,
-    broadcast_reconnect_ch: Option<mpsc::Sender<Arc<Consumer>>>,
-    topic_reconnect_ch: Option<mpsc::Sender<Arc<Consumer>>>,
+impl AppObserver for ConsumerBinder {
+    fn on_application_event(&self, event: &dyn std::any::Any) {
+        if event.downcast_ref::<AppEventMqReady>().is_some() {
+            // MQ就绪后绑定所有消费者
+            tokio::spawn(async {
+                ConsumerBinder::get_instance().init_consumers().await;
+            });
+        }
+    }
+}
+
+// 注册ConsumerBinder作为应用事件观察者，订阅AppEventMqReady事件
+register_observer_for!(ConsumerBinder, AppEventMqReady);
+
+// 全局消费者绑定器实例
+static GLOBAL_BINDER: OnceLock<Arc<ConsumerBinder>> = OnceLock::new();
+
+/// 消费者绑定器
+pub struct ConsumerBinder {
+    workqueue_reconnect_ch: OnceLock<mpsc::UnboundedSender<Arc<Consumer>>>,
+    broadcast_reconnect_ch: OnceLock<mpsc::UnboundedSender<Arc<Consumer>>>,
+    topic_reconnect_ch: OnceLock<mpsc::UnboundedSender<Arc<Consumer>>>,
+    /// 关闭信号
+    shutdown_tx: watch::Sender<bool>,
 }
 
 impl ConsumerBinder {
     fn new() -> Self {
         Self {
-            workqueue_reconnect_ch: None,
-            broadcast_reconnect_ch: None,
-            topic_reconnect_ch: None,
+            workqueue_reconnect_ch: OnceLock::new(),
+            broadcast_reconnect_ch: OnceLock::new(),
+            topic_reconnect_ch: OnceLock::new(),
+            shutdown_tx: watch::channel(false).0,
         }
     }
 
+    /// 是否已停止消费
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    /// 停止所有消费者
+    /// 取消订阅后在timeout内等待处理中的消息完成，返回时仍未完成的消息由broker重新投递
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.shutdown_tx.send_replace(true);
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let in_flight: u32 = CONSUMER_CONTAINER
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|consumer| consumer.in_flight.load(Ordering::Relaxed))
+                .sum();
+            if in_flight == 0 {
+                tracing::info!("all consumers stopped");
+                return;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                tracing::warn!("consumer shutdown timeout, {} messages in flight", in_flight);
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub fn get_instance() -> Arc<Self> {
+        GLOBAL_BINDER
+            .get_or_init(|| Arc::new(ConsumerBinder::new()))
+            .clone()
+    }
+
     // 绑定消费者
     pub async fn bind_consumer(&self, consumer: Arc<Consumer>) {
         match consumer.r#type {
             ConsumerType::WorkQueue => {
                 for _ in 0..consumer.concurrency {
                     if !self.bind_work_queue_consumer(consumer.clone()).await {
-                        if let Some(ch) = &self.workqueue_reconnect_ch {
-                            ch.send(consumer.clone()).unwrap();
-                        }
+                        self.request_reconnect(consumer.clone());
                     }
                 }
             }
             ConsumerType::Broadcast => {
                 if !self.bind_broadcast_consumer(consumer.clone()).await {
-                    if let Some(ch) = &self.broadcast_reconnect_ch {
-                        ch.send(consumer.clone()).unwrap();
-                    }
+                    self.request_reconnect(consumer.clone());
                 }
             }
             ConsumerType::Topic => {
                 if !self.bind_topic_consumer(consumer.clone()).await {
-                    if let Some(ch) = &self.topic_reconnect_ch {
-                        ch.send(consumer.clone()).unwrap();
-                    }
+                    self.request_reconnect(consumer.clone());
                 }
             }
             _ => {
@@ -342,429 +992,156 @@ impl ConsumerBinder {
         }
     }
 
-    // 绑定工作队列消费者
-    async fn bind_work_queue_consumer(&self, consumer: Arc<Consumer>) -> bool {
-        let pool = RabbitmqConnPool::get_instance();
-        let rec_chan = match pool.get_rec_channel().await {
-            Ok(chan) => chan,
-            Err(err) => {
-                tracing::error!("获取消费通道失败: {:?}", err);
-                return false;
-            }
+    /// 将消费者放入对应的重连通道
+    pub fn request_reconnect(&self, consumer: Arc<Consumer>) {
+        let ch = match consumer.r#type {
+            ConsumerType::WorkQueue => self.workqueue_reconnect_ch.get(),
+            ConsumerType::Broadcast => self.broadcast_reconnect_ch.get(),
+            ConsumerType::Topic => self.topic_reconnect_ch.get(),
+            _ => None,
         };
 
-        // 声明队列
-        if let Err(err) = rec_chan
-            .channel
-            .queue_declare(
-                &consumer.route_key,
-                lapin::options::QueueDeclareOptions {
-                    durable: true,
-                    auto_delete: false,
-                    exclusive: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            tracing::error!("声明队列失败: {:?}", err);
-            return false;
-        }
-
-        // 设置QoS
-        if let Err(err) = rec_chan
-            .channel
-            .basic_qos(
-                consumer.prefetch_count as u16,
-                lapin::options::BasicQosOptions::default(),
-            )
-            .await
-        {
-            tracing::error!("设置QoS失败: {:?}", err);
-            return false;
-        }
-
-        // 消费消息
-        let consumer_tag = format!("workqueue_{}", consumer.route_key);
-        let consumer_clone = consumer.clone();
-        let pool_clone = pool.clone();
-
-        let _ = rec_chan
-            .channel
-            .clone()
-            .basic_consume(
-                &consumer.route_key,
-                &consumer_tag,
-                lapin::options::BasicConsumeOptions {
-                    no_ack: false,
-                    exclusive: false,
-                    no_local: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-            .map(|consumer| {
-                tokio::spawn(async move {
-                    let mut consumer = consumer;
-                    while let Some(delivery) = consumer.next().await {
-                        match delivery {
-                            Ok(delivery) => {
-                                let content = String::from_utf8_lossy(&delivery.data).to_string();
-                                let result = consumer_clone.on_received(&content);
-
-                                if result {
-                                    let _ = delivery
-                                        .ack(lapin::options::BasicAckOptions::default())
-                                        .await;
-                                } else {
-                                    let _ = delivery
-                                        .nack(lapin::options::BasicNackOptions {
-                                            requeue: true,
-                                            ..Default::default()
-                                        })
-                                        .await;
-                                }
-                            }
-                            Err(err) => {
-                                tracing::error!("消费消息失败: {:?}", err);
-                                break;
-                            }
-                        }
-                    }
-                });
-            });
-
-        true
+        if let Some(ch) = ch {
+            consumer.reconnecting.store(true, Ordering::Relaxed);
+            if let Err(err) = ch.send(consumer) {
+                tracing::error!("消费者重连通道已关闭: {:?}", err);
+            }
+        }
     }
 
-    // 绑定广播消费者
-    async fn bind_broadcast_consumer(&self, consumer: Arc<Consumer>) -> bool {
-        let pool = RabbitmqConnPool::get_instance();
-        let rec_chan = match pool.get_rec_channel().await {
-            Ok(chan) => chan,
-            Err(err) => {
-                tracing::error!("获取消费通道失败: {:?}", err);
-                return false;
-            }
+    // 绑定工作队列消费者
+    pub async fn bind_work_queue_consumer(&self, consumer: Arc<Consumer>) -> bool {
+        let arguments = if consumer.partitioned {
+            partition::queue_arguments()
+        } else {
+            Default::default()
         };
+        let spec = ConsumeSpec {
+            queue: QueueSpec {
+                name: consumer.route_key.clone(),
+                durable: true,
+                arguments,
+                ..Default::default()
+            },
+            // 已在拓扑中注册的队列不再声明
+            declare_queue: topology::queue(&consumer.route_key).is_none(),
+            prefetch_count: consumer.prefetch_count as u16,
+            consumer_tag: format!("workqueue_{}", consumer.route_key),
+            ..Default::default()
+        };
+        self.bind_with(consumer, spec).await
+    }
 
-        // 声明交换器
-        if let Err(err) = rec_chan
-            .channel
-            .exchange_declare(
-                &consumer.exchange,
-                lapin::ExchangeKind::Fanout,
-                lapin::options::ExchangeDeclareOptions {
-                    durable: false,
-                    auto_delete: true,
-                    internal: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            tracing::error!("声明交换器失败: {:?}", err);
-            return false;
-        }
-
-        // 声明临时队列
-        let queue = match rec_chan
-            .channel
-            .queue_declare(
-                "", // name
-                lapin::options::QueueDeclareOptions {
+    // 绑定广播消费者
+    pub async fn bind_broadcast_consumer(&self, consumer: Arc<Consumer>) -> bool {
+        let spec = ConsumeSpec {
+            // 实例独占的临时队列
+            queue: QueueSpec {
+                name: consumer.broadcast_queue_name(),
+                durable: false,
+                auto_delete: true,
+                exclusive: true,
+                ..Default::default()
+            },
+            declare_queue: true,
+            // 已在拓扑中注册的交换器不再声明
+            exchange: topology::exchange(&consumer.exchange)
+                .is_none()
+                .then(|| ExchangeSpec {
+                    name: consumer.exchange.clone(),
+                    kind: lapin::ExchangeKind::Fanout,
                     durable: false,
                     auto_delete: true,
-                    exclusive: true,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            Ok(queue) => queue,
-            Err(err) => {
-                tracing::error!("声明队列失败: {:?}", err);
-                return false;
-            }
+                }),
+            binding: Some((consumer.exchange.clone(), String::new())),
+            consumer_tag: format!("broadcast_{}", consumer.exchange),
+            ..Default::default()
         };
-
-        // 绑定队列到交换器
-        if let Err(err) = rec_chan
-            .channel
-            .queue_bind(
-                queue.name().as_str(),
-                &consumer.exchange,
-                "", // routing_key
-                lapin::options::QueueBindOptions::default(),
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            tracing::error!("绑定队列失败: {:?}", err);
-            return false;
-        }
-
-        // 消费消息
-        let consumer_tag = format!("broadcast_{}", consumer.exchange);
-        let consumer_clone = consumer.clone();
-
-        let _ = rec_chan
-            .channel
-            .clone()
-            .basic_consume(
-                queue.name().as_str(),
-                &consumer_tag,
-                lapin::options::BasicConsumeOptions {
-                    no_ack: false,
-                    exclusive: false,
-                    no_local: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-            .map(|consumer| {
-                tokio::spawn(async move {
-                    let mut consumer = consumer;
-                    while let Some(delivery) = consumer.next().await {
-                        match delivery {
-                            Ok(delivery) => {
-                                let content = String::from_utf8_lossy(&delivery.data).to_string();
-                                let result = consumer_clone.on_received(&content);
-
-                                if result {
-                                    let _ = delivery
-                                        .ack(lapin::options::BasicAckOptions::default())
-                                        .await;
-                                } else {
-                                    let _ = delivery
-                                        .nack(lapin::options::BasicNackOptions {
-                                            requeue: true,
-                                            ..Default::default()
-                                        })
-                                        .await;
-                                }
-                            }
-                            Err(err) => {
-                                tracing::error!("消费消息失败: {:?}", err);
-                                break;
-                            }
-                        }
-                    }
-                });
-            });
-
-        true
+        self.bind_with(consumer, spec).await
     }
 
     // 绑定topic消费者
-    async fn bind_topic_consumer(&self, consumer: Arc<Consumer>) -> bool {
-        let pool = RabbitmqConnPool::get_instance();
-        let rec_chan = match pool.get_rec_channel().await {
-            Ok(chan) => chan,
-            Err(err) => {
-                tracing::error!("获取消费通道失败: {:?}", err);
-                return false;
-            }
-        };
-
-        // 声明交换器
-        if let Err(err) = rec_chan
-            .channel
-            .exchange_declare(
-                &consumer.exchange,
-                lapin::ExchangeKind::Topic,
-                lapin::options::ExchangeDeclareOptions {
+    pub async fn bind_topic_consumer(&self, consumer: Arc<Consumer>) -> bool {
+        let queue_name = consumer.topic_queue_name();
+        let spec = ConsumeSpec {
+            queue: QueueSpec {
+                name: queue_name.clone(),
+                durable: true,
+                ..Default::default()
+            },
+            // 已在拓扑中注册的队列与交换器不再声明
+            declare_queue: topology::queue(&queue_name).is_none(),
+            exchange: topology::exchange(&consumer.exchange)
+                .is_none()
+                .then(|| ExchangeSpec {
+                    name: consumer.exchange.clone(),
+                    kind: lapin::ExchangeKind::Topic,
                     durable: true,
                     auto_delete: false,
-                    internal: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            tracing::error!("声明交换器失败: {:?}", err);
-            return false;
-        }
-
-        // 声明队列
-        let queue_name = format!("topic_{}_{}", consumer.exchange, consumer.topic_pattern);
-        let queue = match rec_chan
-            .channel
-            .queue_declare(
-                &queue_name,
-                lapin::options::QueueDeclareOptions {
-                    durable: true,
-                    auto_delete: false,
-                    exclusive: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            Ok(queue) => queue,
-            Err(err) => {
-                tracing::error!("声明队列失败: {:?}", err);
-                return false;
-            }
+                }),
+            binding: Some((consumer.exchange.clone(), consumer.topic_pattern.clone())),
+            prefetch_count: 1,
+            consumer_tag: format!("topic_{}_{}", consumer.exchange, consumer.topic_pattern),
+            ..Default::default()
         };
+        self.bind_with(consumer, spec).await
+    }
 
-        // 绑定队列到交换器
-        if let Err(err) = rec_chan
-            .channel
-            .queue_bind(
-                queue.name().as_str(),
-                &consumer.exchange,
-                &consumer.topic_pattern,
-                lapin::options::QueueBindOptions::default(),
-                lapin::types::FieldTable::default(),
-            )
-            .await
-        {
-            tracing::error!("绑定队列失败: {:?}", err);
-            return false;
-        }
-
-        // 设置QoS
-        if let Err(err) = rec_chan
-            .channel
-            .basic_qos(
-                1, // prefetch_count
-                lapin::options::BasicQosOptions::default(),
-            )
-            .await
-        {
-            tracing::error!("设置QoS失败: {:?}", err);
-            return false;
-        }
-
-        // 消费消息
-        let consumer_tag = format!("topic_{}_{}", consumer.exchange, consumer.topic_pattern);
-        let consumer_clone = consumer.clone();
-
-        let _ = rec_chan
-            .channel
-            .clone()
-            .basic_consume(
-                queue.name().as_str(),
-                &consumer_tag,
-                lapin::options::BasicConsumeOptions {
-                    no_ack: false,
-                    exclusive: false,
-                    no_local: false,
-                    ..Default::default()
-                },
-                lapin::types::FieldTable::default(),
-            )
-            .await
-            .map(|consumer| {
-                tokio::spawn(async move {
-                    let mut consumer = consumer;
-                    while let Some(delivery) = consumer.next().await {
-                        match delivery {
-                            Ok(delivery) => {
-                                let content = String::from_utf8_lossy(&delivery.data).to_string();
-                                let result = consumer_clone.on_received(&content);
-
-                                if result {
-                                    let _ = delivery
-                                        .ack(lapin::options::BasicAckOptions::default())
-                                        .await;
-                                } else {
-                                    let _ = delivery
-                                        .nack(lapin::options::BasicNackOptions {
-                                            requeue: true,
-                                            ..Default::default()
-                                        })
-                                        .await;
-                                }
-                            }
-                            Err(err) => {
-                                tracing::error!("消费消息失败: {:?}", err);
-                                break;
-                            }
-                        }
-                    }
-                });
-            });
-
-        true
+    // 经由传输层声明并订阅，成功后启动消费循环
+    // 失败时记录消费者状态
+    async fn bind_with(&self, consumer: Arc<Consumer>, spec: ConsumeSpec) -> bool {
+        match transport::current().subscribe(spec).await {
+            Ok(subscription) => {
+                consumer.on_bound();
+                spawn_delivery_loop(consumer, subscription);
+                true
+            }
+            Err(err) => {
+                tracing::error!("绑定消费者失败: {} - {:?}", consumer.name(), err);
+                consumer.on_bind_failed(&err.to_string());
+                false
+            }
+        }
     }
 
     // 初始化消费者
-    pub async fn init_consumers(&mut self) {
-        if HAS_CONSUMER_BIND.load(std::sync::atomic::Ordering::Relaxed) {
+    pub async fn init_consumers(&self) {
+        if HAS_CONSUMER_BIND.swap(true, std::sync::atomic::Ordering::Relaxed) {
             return;
         }
 
-        let container = CONSUMER_CONTAINER.lock().unwrap();
-        let mut worker_count = 0;
-        let mut broadcaster_count = 0;
-        let mut topic_count = 0;
-
-        for consumer in container.iter() {
-            match consumer.r#type {
-                ConsumerType::WorkQueue => {
-                    worker_count += consumer.concurrency as usize;
-                }
-                ConsumerType::Broadcast => {
-                    broadcaster_count += 1;
-                }
-                ConsumerType::Topic => {
-                    topic_count += 1;
-                }
-                _ => {}
-            }
-        }
-
         // 创建重连通道
-        let (work_tx, work_rx) = mpsc::channel();
-        let (broadcast_tx, broadcast_rx) = mpsc::channel();
-        let (topic_tx, topic_rx) = mpsc::channel();
-
-        self.workqueue_reconnect_ch = Some(work_tx);
-        self.broadcast_reconnect_ch = Some(broadcast_tx);
-        self.topic_reconnect_ch = Some(topic_tx);
-
-        // 启动工作队列重连线程
-        let work_tx = self.workqueue_reconnect_ch.clone();
-        thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async move {
-                handle_workqueue_reconnect(work_rx, work_tx).await;
-            });
-        });
-
-        // 启动广播重连线程
-        let broadcast_tx = self.broadcast_reconnect_ch.clone();
-        thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async move {
-                handle_broadcast_reconnect(broadcast_rx, broadcast_tx).await;
-            });
-        });
-
-        // 启动topic重连线程
-        let topic_tx = self.topic_reconnect_ch.clone();
-        thread::spawn(move || {
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async move {
-                handle_topic_reconnect(topic_rx, topic_tx).await;
-            });
-        });
+        let (work_tx, work_rx) = mpsc::unbounded_channel();
+        let (broadcast_tx, broadcast_rx) = mpsc::unbounded_channel();
+        let (topic_tx, topic_rx) = mpsc::unbounded_channel();
+
+        let _ = self.workqueue_reconnect_ch.set(work_tx.clone());
+        let _ = self.broadcast_reconnect_ch.set(broadcast_tx.clone());
+        let _ = self.topic_reconnect_ch.set(topic_tx.clone());
+
+        // 启动工作队列重连任务
+        tokio::spawn(handle_workqueue_reconnect(work_rx, Some(work_tx)));
+
+        // 启动广播重连任务
+        tokio::spawn(handle_broadcast_reconnect(broadcast_rx, Some(broadcast_tx)));
+
+        // 启动topic重连任务
+        tokio::spawn(handle_topic_reconnect(topic_rx, Some(topic_tx)));
+
+        // 创建声明式注册的消费者并复制一份消费者列表，避免跨await持有锁
+        // 与add_consumer互斥，之后创建的消费者由add_consumer绑定
+        let consumers = {
+            let mut bound = CONTAINER_BOUND.lock().unwrap();
+            *bound = true;
+            registry::collect_consumers();
+            CONSUMER_CONTAINER.lock().unwrap().clone()
+        };
 
         // 绑定所有消费者
-        for consumer in container.iter() {
+        for consumer in consumers.iter() {
             self.bind_consumer(consumer.clone()).await;
         }
 
-        HAS_CONSUMER_BIND.store(true, std::sync::atomic::Ordering::Relaxed);
-        tracing::info!("mq init complete");
+        tracing::info!("mq init complete, {} consumers bound", consumers.len());
     }
 }
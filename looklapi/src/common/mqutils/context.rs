@@ -0,0 +1,118 @@
+use crate::common::mqutils::codec::{Codec, Envelope};
+use crate::common::mqutils::publisher::PublishOptions;
+use crate::common::mqutils::transport::Delivery;
+use lapin::types::{AMQPValue, FieldTable};
+use std::collections::BTreeMap;
+
+/// 消息头: trace id，取自发布时的请求id
+pub const HEADER_TRACE_ID: &str = "x-trace-id";
+
+tokio::task_local! {
+    static MESSAGE_CONTEXT: MessageContext;
+}
+
+/// 消费中的消息上下文
+#[derive(Debug, Clone, Default)]
+#[allow(dead_code, reason = "字段供业务处理器读取")]
+pub struct MessageContext {
+    /// 消息id
+    pub guid: String,
+    /// 当前重试次数
+    pub current_retry: i32,
+    /// 消费的队列
+    pub queue: String,
+    /// 是否为重新投递
+    pub redelivered: bool,
+    /// 编码格式
+    pub codec: Codec,
+    /// 消息体是否经过压缩
+    pub compressed: bool,
+    /// 消息类型
+    pub message_type: Option<String>,
+    /// 关联id
+    pub correlation_id: Option<String>,
+    /// 回复队列
+    pub reply_to: Option<String>,
+    /// trace id
+    pub trace_id: Option<String>,
+    /// 自定义消息头
+    pub headers: BTreeMap<String, String>,
+}
+
+impl MessageContext {
+    // 从投递的消息中提取上下文
+    pub(crate) fn from_delivery(
+        delivery: &Delivery,
+        envelope: &Envelope,
+        queue: &str,
+    ) -> Self {
+        let properties = &delivery.properties;
+        let mut headers = properties
+            .headers()
+            .as_ref()
+            .map(header_strings)
+            .unwrap_or_default();
+        let trace_id = headers.remove(HEADER_TRACE_ID);
+
+        MessageContext {
+            guid: envelope.guid.clone(),
+            current_retry: envelope.current_retry,
+            queue: queue.to_string(),
+            redelivered: delivery.redelivered,
+            codec: envelope.codec,
+            compressed: properties.content_encoding().is_some(),
+            message_type: properties.kind().as_ref().map(|v| v.to_string()),
+            correlation_id: properties.correlation_id().as_ref().map(|v| v.to_string()),
+            reply_to: properties.reply_to().as_ref().map(|v| v.to_string()),
+            trace_id,
+            headers,
+        }
+    }
+
+    /// 获取自定义消息头
+    #[allow(dead_code, reason = "供业务处理器读取自定义消息头")]
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers.get(key).map(|v| v.as_str())
+    }
+
+    // 转发消息(重试/死信)时沿用原消息的属性
+    pub(crate) fn forward_options(&self) -> PublishOptions {
+        PublishOptions {
+            message_type: self.message_type.clone(),
+            correlation_id: self.correlation_id.clone(),
+            reply_to: self.reply_to.clone(),
+            trace_id: self.trace_id.clone(),
+            headers: self.headers.clone(),
+            codec: self.codec,
+            compress_threshold: self.compressed.then_some(0),
+            ..Default::default()
+        }
+    }
+}
+
+/// 获取当前消费中的消息上下文，仅在消息处理器内有效
+#[allow(dead_code, reason = "供业务处理器获取当前消息上下文")]
+pub fn current() -> Option<MessageContext> {
+    MESSAGE_CONTEXT.try_with(|ctx| ctx.clone()).ok()
+}
+
+// 在消息上下文中执行
+pub(crate) async fn scope<F: std::future::Future>(ctx: MessageContext, f: F) -> F::Output {
+    MESSAGE_CONTEXT.scope(ctx, f).await
+}
+
+// 将字符串类型的消息头转换为map，其余类型忽略
+fn header_strings(headers: &FieldTable) -> BTreeMap<String, String> {
+    headers
+        .inner()
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                AMQPValue::LongString(v) => v.to_string(),
+                AMQPValue::ShortString(v) => v.to_string(),
+                _ => return None,
+            };
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
@@ -0,0 +1,359 @@
+use crate::common::overridable::Overridable;
+use crate::common::redisutils::key::RedisKey;
+use crate::common::redisutils::redipool::get_conn;
+use futures::future::BoxFuture;
+use redis::{AsyncCommands, RedisResult};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 去重记录默认保留时间
+const DEFAULT_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+/// 处理中租约默认时长
+const DEFAULT_LEASE: Duration = Duration::from_secs(60);
+/// 去重键默认前缀
+const DEFAULT_KEY_PREFIX: &str = "mq_dedupe";
+/// 已处理标记
+const DONE_MARKER: &str = "done";
+
+/// 消息去重配置
+/// 以消息guid为键在redis中记录处理状态: 处理前写入带过期时间的租约，处理成功后改为保留retention的已处理标记
+#[derive(Debug, Clone)]
+pub struct DedupeOptions {
+    /// 已处理记录保留时间，超过后同一guid的消息会再次处理
+    pub retention: Duration,
+    /// 处理中租约时长，应大于消息的最长处理时间，消费者异常退出时租约到期后其他消费者可重新处理
+    pub lease: Duration,
+    /// 去重键前缀，键格式为 {前缀}_{作用域}_{guid}
+    pub key_prefix: String,
+    /// 去重键所在的redis db，未设置时与字符串key的规则相同
+    pub db: Option<u8>,
+    /// 去重作用域，为空时使用消费者名称
+    /// 广播消费者的每个实例都应处理同一消息，为空时使用实例队列名称
+    pub scope: String,
+}
+
+impl Default for DedupeOptions {
+    fn default() -> Self {
+        Self {
+            retention: DEFAULT_RETENTION,
+            lease: DEFAULT_LEASE,
+            key_prefix: DEFAULT_KEY_PREFIX.to_string(),
+            db: None,
+            scope: String::new(),
+        }
+    }
+}
+
+#[allow(dead_code, reason = "去重选项的构造方法，由业务代码按需调用")]
+impl DedupeOptions {
+    /// 设置已处理记录保留时间
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    /// 设置处理中租约时长
+    pub fn lease(mut self, lease: Duration) -> Self {
+        self.lease = lease;
+        self
+    }
+
+    /// 设置去重键前缀
+    pub fn key_prefix(mut self, key_prefix: &str) -> Self {
+        self.key_prefix = key_prefix.to_string();
+        self
+    }
+
+    /// 设置去重键所在的redis db
+    pub fn db(mut self, db: u8) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// 设置去重作用域
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scope = scope.to_string();
+        self
+    }
+
+    // 消息的去重键
+    pub(crate) fn key(&self, default_scope: &str, guid: &str) -> RedisKey {
+        let scope = if self.scope.is_empty() {
+            default_scope
+        } else {
+            &self.scope
+        };
+        let key = format!("{}_{}_{}", self.key_prefix, scope, guid);
+        match self.db {
+            Some(db) => RedisKey::new(db, key),
+            None => RedisKey::from(key),
+        }
+    }
+}
+
+/// 获取租约的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Acquire {
+    /// 获取成功，可以处理消息
+    Acquired,
+    /// 消息已处理过
+    Duplicate,
+    /// 消息正在被其他消费者处理
+    InFlight,
+}
+
+/// 去重记录存储
+pub trait DedupeStore: Send + Sync {
+    /// 获取处理中租约，token用于释放时校验租约归属
+    fn acquire<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        lease: Duration,
+    ) -> BoxFuture<'a, RedisResult<Acquire>>;
+
+    /// 标记消息已处理，记录保留retention
+    fn complete<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        retention: Duration,
+    ) -> BoxFuture<'a, RedisResult<()>>;
+
+    /// 释放租约，仅删除token一致的租约
+    fn release<'a>(&'a self, key: &'a RedisKey, token: &'a str) -> BoxFuture<'a, RedisResult<()>>;
+}
+
+// 当前去重存储，默认使用redis
+static STORE: Overridable<dyn DedupeStore> = Overridable::new(|| Arc::new(RedisDedupeStore));
+
+/// 获取当前去重存储
+pub fn current() -> Arc<dyn DedupeStore> {
+    STORE.current()
+}
+
+// 不存在时写入租约，已存在时返回已有的值
+const ACQUIRE_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+    return false
+end
+return redis.call('GET', KEYS[1])
+"#;
+
+// 值与token一致时删除
+const RELEASE_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+/// 基于redis的去重存储
+pub struct RedisDedupeStore;
+
+impl DedupeStore for RedisDedupeStore {
+    fn acquire<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        lease: Duration,
+    ) -> BoxFuture<'a, RedisResult<Acquire>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            let existing: Option<String> = redis::Script::new(ACQUIRE_SCRIPT)
+                .key(key)
+                .arg(token)
+                .arg(lease.as_millis().max(1) as u64)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(match existing {
+                None => Acquire::Acquired,
+                Some(ref value) if value == DONE_MARKER => Acquire::Duplicate,
+                // 租约在SET与GET之间过期时同样视为处理中，稍后重试
+                Some(_) => Acquire::InFlight,
+            })
+        })
+    }
+
+    fn complete<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        retention: Duration,
+    ) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            conn.pset_ex(key, DONE_MARKER, retention.as_millis().max(1) as u64)
+                .await
+        })
+    }
+
+    fn release<'a>(&'a self, key: &'a RedisKey, token: &'a str) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            let _: i64 = redis::Script::new(RELEASE_SCRIPT)
+                .key(key)
+                .arg(token)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+/// 消息去重检查结果
+pub(crate) enum Admission {
+    /// 继续处理，开启去重时持有租约
+    Proceed(Option<Lease>),
+    /// 消息已处理过
+    Duplicate,
+    /// 消息正在被其他消费者处理
+    InFlight,
+}
+
+/// 消息处理租约
+pub(crate) struct Lease {
+    key: RedisKey,
+    token: String,
+    retention: Duration,
+}
+
+impl Lease {
+    /// 处理成功，标记为已处理
+    pub(crate) async fn complete(self) {
+        if let Err(err) = current().complete(&self.key, self.retention).await {
+            tracing::warn!("消息去重记录写入失败: {} - {}", self.key, err);
+        }
+    }
+
+    /// 处理失败，释放租约以便重试时再次处理
+    pub(crate) async fn release(self) {
+        if let Err(err) = current().release(&self.key, &self.token).await {
+            tracing::warn!("消息去重租约释放失败: {} - {}", self.key, err);
+        }
+    }
+}
+
+// 检查消息是否需要处理，去重存储不可用时不去重，继续处理消息
+pub(crate) async fn admit(options: &DedupeOptions, default_scope: &str, guid: &str) -> Admission {
+    if guid.is_empty() {
+        return Admission::Proceed(None);
+    }
+
+    let key = options.key(default_scope, guid);
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    match current().acquire(&key, &token, options.lease).await {
+        Ok(Acquire::Acquired) => Admission::Proceed(Some(Lease {
+            key,
+            token,
+            retention: options.retention,
+        })),
+        Ok(Acquire::Duplicate) => Admission::Duplicate,
+        Ok(Acquire::InFlight) => Admission::InFlight,
+        Err(err) => {
+            tracing::warn!("消息去重检查失败，跳过去重: {} - {}", key, err);
+            Admission::Proceed(None)
+        }
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryDedupeStore;
+
+// 测试用的内存实现
+#[cfg(test)]
+mod memory {
+    use super::{Acquire, DONE_MARKER, DedupeStore, STORE};
+    use crate::common::redisutils::key::RedisKey;
+    use crate::common::testutil;
+    use futures::future::BoxFuture;
+    use redis::RedisResult;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    /// 进程内的内存去重存储，用于在没有redis的环境下测试
+    #[derive(Default)]
+    pub struct MemoryDedupeStore {
+        /// key -> (值, 过期时间)
+        entries: Mutex<HashMap<RedisKey, (String, Instant)>>,
+    }
+
+    impl MemoryDedupeStore {
+        /// 安装进程内共享的内存去重存储，重复调用返回同一实例
+        pub fn install() -> Arc<Self> {
+            let store = testutil::shared(Arc::<Self>::default);
+            STORE.install(store.clone());
+            store
+        }
+
+        /// 是否已标记为已处理
+        pub fn is_done(&self, key: &RedisKey) -> bool {
+            self.value(key).as_deref() == Some(DONE_MARKER)
+        }
+
+        // 获取未过期的值
+        fn value(&self, key: &RedisKey) -> Option<String> {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .get(key)
+                .filter(|(_, expires_at)| *expires_at > Instant::now())
+                .map(|(value, _)| value.clone())
+        }
+    }
+
+    impl DedupeStore for MemoryDedupeStore {
+        fn acquire<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            token: &'a str,
+            lease: Duration,
+        ) -> BoxFuture<'a, RedisResult<Acquire>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let now = Instant::now();
+                let result = match entries.get(key) {
+                    Some((value, expires_at)) if *expires_at > now => {
+                        if value == DONE_MARKER {
+                            Acquire::Duplicate
+                        } else {
+                            Acquire::InFlight
+                        }
+                    }
+                    _ => {
+                        entries.insert(key.clone(), (token.to_string(), now + lease));
+                        Acquire::Acquired
+                    }
+                };
+                Ok(result)
+            })
+        }
+
+        fn complete<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            retention: Duration,
+        ) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                entries.insert(
+                    key.clone(),
+                    (DONE_MARKER.to_string(), Instant::now() + retention),
+                );
+                Ok(())
+            })
+        }
+
+        fn release<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            token: &'a str,
+        ) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                if entries.get(key).is_some_and(|(value, _)| value == token) {
+                    entries.remove(key);
+                }
+                Ok(())
+            })
+        }
+    }
+}
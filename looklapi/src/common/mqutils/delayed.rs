@@ -0,0 +1,372 @@
+use crate::app::appcontext::events::AppEventMqReady;
+use crate::app::appcontext::observer::AppObserver;
+use crate::common::mqutils::publisher::{self, PublishOptions};
+use crate::common::mqutils::rabbitmq_pool::RabbitmqConnPool;
+use crate::common::overridable::Overridable;
+use crate::common::redisutils::key::RedisKey;
+use crate::common::redisutils::redipool::get_conn;
+use crate::register_observer_for;
+use futures::future::BoxFuture;
+use redis::RedisResult;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+// 调度有序集合与内容hash表在同一脚本或事务中操作，
+// 使用相同的hash tag，集群模式下落在同一个slot
+/// 延迟消息调度有序集合，member: 消息id，score: 到期时间戳 毫秒
+pub const DELAYED_SCHEDULE_KEY: &str = "{mq_delayed}:schedule";
+/// 延迟消息内容hash表，key: 消息id，value: 延迟消息记录
+pub const DELAYED_PAYLOAD_KEY: &str = "{mq_delayed}:payload";
+
+/// 轮询到期消息的间隔，决定延迟消息的投递精度
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 每轮最多投递的消息数
+const BATCH_SIZE: usize = 100;
+/// 领取后的租约时间 毫秒，超时未投递完成视为调度进程异常，可被重新领取
+const CLAIM_LEASE_MILLS: i64 = 30 * 1000;
+
+/// 延迟消息记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DelayedRecord {
+    /// 消息id，与MqMessage.guid一致
+    pub id: String,
+    /// 工作队列路由键
+    pub route_key: String,
+    /// 序列化后的MqMessage
+    pub message: String,
+    /// 到期时间戳 毫秒
+    pub due_at: i64,
+    /// 发布时的trace id
+    pub trace_id: Option<String>,
+}
+
+/// 延迟消息存储
+pub trait DelayedStore: Send + Sync {
+    /// 保存延迟消息
+    fn schedule<'a>(&'a self, record: &'a DelayedRecord) -> BoxFuture<'a, RedisResult<()>>;
+
+    /// 删除尚未投递的延迟消息，返回是否存在
+    fn cancel<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<bool>>;
+
+    /// 领取到期的延迟消息，领取后的消息在lease_ms后可被重新领取
+    fn claim_due(
+        &self,
+        now_ms: i64,
+        lease_ms: i64,
+        limit: usize,
+    ) -> BoxFuture<'_, RedisResult<Vec<DelayedRecord>>>;
+
+    /// 删除已投递的延迟消息
+    fn remove<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<()>>;
+}
+
+// 当前延迟消息存储，默认使用redis
+static STORE: Overridable<dyn DelayedStore> = Overridable::new(|| Arc::new(RedisDelayedStore));
+
+/// 获取当前延迟消息存储
+pub fn current() -> Arc<dyn DelayedStore> {
+    STORE.current()
+}
+
+// 删除调度项与消息内容，返回调度项是否存在
+const CANCEL_SCRIPT: &str = r#"
+local removed = redis.call('ZREM', KEYS[1], ARGV[1])
+redis.call('HDEL', KEYS[2], ARGV[1])
+return removed
+"#;
+
+// 领取到期的消息: 将分数推后至租约到期时间，返回消息内容
+// 内容已丢失的调度项直接删除
+const CLAIM_SCRIPT: &str = r#"
+local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, ARGV[3])
+local result = {}
+for _, id in ipairs(ids) do
+    local payload = redis.call('HGET', KEYS[2], id)
+    if payload then
+        redis.call('ZADD', KEYS[1], ARGV[2], id)
+        table.insert(result, payload)
+    else
+        redis.call('ZREM', KEYS[1], id)
+    end
+end
+return result
+"#;
+
+/// 基于redis有序集合的延迟消息存储
+pub struct RedisDelayedStore;
+
+impl DelayedStore for RedisDelayedStore {
+    fn schedule<'a>(&'a self, record: &'a DelayedRecord) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let payload = serde_json::to_string(record)?;
+            let mut conn = get_conn(&RedisKey::from(DELAYED_SCHEDULE_KEY)).await?;
+            redis::pipe()
+                .atomic()
+                .hset(DELAYED_PAYLOAD_KEY, &record.id, payload)
+                .ignore()
+                .zadd(DELAYED_SCHEDULE_KEY, &record.id, record.due_at)
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        })
+    }
+
+    fn cancel<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<bool>> {
+        Box::pin(async move {
+            let mut conn = get_conn(&RedisKey::from(DELAYED_SCHEDULE_KEY)).await?;
+            let removed: i64 = redis::Script::new(CANCEL_SCRIPT)
+                .key(DELAYED_SCHEDULE_KEY)
+                .key(DELAYED_PAYLOAD_KEY)
+                .arg(id)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(removed > 0)
+        })
+    }
+
+    fn claim_due(
+        &self,
+        now_ms: i64,
+        lease_ms: i64,
+        limit: usize,
+    ) -> BoxFuture<'_, RedisResult<Vec<DelayedRecord>>> {
+        Box::pin(async move {
+            let mut conn = get_conn(&RedisKey::from(DELAYED_SCHEDULE_KEY)).await?;
+            let payloads: Vec<String> = redis::Script::new(CLAIM_SCRIPT)
+                .key(DELAYED_SCHEDULE_KEY)
+                .key(DELAYED_PAYLOAD_KEY)
+                .arg(now_ms)
+                .arg(now_ms + lease_ms)
+                .arg(limit)
+                .invoke_async(&mut conn)
+                .await?;
+            payloads
+                .iter()
+                .map(|payload| serde_json::from_str(payload).map_err(redis::RedisError::from))
+                .collect()
+        })
+    }
+
+    fn remove<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let mut conn = get_conn(&RedisKey::from(DELAYED_SCHEDULE_KEY)).await?;
+            redis::pipe()
+                .atomic()
+                .zrem(DELAYED_SCHEDULE_KEY, id)
+                .ignore()
+                .hdel(DELAYED_PAYLOAD_KEY, id)
+                .ignore()
+                .query_async(&mut conn)
+                .await
+        })
+    }
+}
+
+/// 延迟消息调度器
+/// MQ就绪后启动，定时领取到期的延迟消息并发布到工作队列
+/// 多个实例同时运行时通过领取租约避免重复投递，投递失败的消息在租约到期后重新领取
+pub struct DelayedScheduler {
+    started: AtomicBool,
+}
+
+impl AppObserver for DelayedScheduler {
+    fn on_application_event(&self, event: &dyn std::any::Any) {
+        if event.downcast_ref::<AppEventMqReady>().is_some() {
+            tokio::spawn(async {
+                DelayedScheduler::get_instance().start();
+            });
+        }
+    }
+}
+
+// 注册DelayedScheduler作为应用事件观察者，订阅AppEventMqReady事件
+register_observer_for!(DelayedScheduler, AppEventMqReady);
+
+// 全局调度器实例
+static GLOBAL_SCHEDULER: OnceLock<Arc<DelayedScheduler>> = OnceLock::new();
+
+impl DelayedScheduler {
+    pub fn get_instance() -> Arc<Self> {
+        GLOBAL_SCHEDULER
+            .get_or_init(|| {
+                Arc::new(DelayedScheduler {
+                    started: AtomicBool::new(false),
+                })
+            })
+            .clone()
+    }
+
+    // 启动调度循环
+    fn start(self: Arc<Self>) {
+        if self.started.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        tokio::spawn(async move {
+            loop {
+                // 连接池关闭后停止调度，未投递的消息在下次启动后继续处理
+                if RabbitmqConnPool::get_instance().is_shutdown() {
+                    break;
+                }
+                match self.dispatch_due().await {
+                    // 本轮已满，可能仍有积压，立即继续
+                    Ok(count) if count >= BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(err) => tracing::error!("延迟消息调度失败: {:?}", err),
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+        tracing::info!("delayed message scheduler started");
+    }
+
+    /// 投递一批到期的延迟消息，返回领取的条数
+    pub async fn dispatch_due(&self) -> RedisResult<usize> {
+        let store = current();
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        let records = store
+            .claim_due(now_ms, CLAIM_LEASE_MILLS, BATCH_SIZE)
+            .await?;
+
+        for record in records.iter() {
+            let options = PublishOptions {
+                trace_id: record.trace_id.clone(),
+                ..Default::default()
+            };
+            match publisher::pub_work_queue_msg_internal(
+                &record.route_key,
+                record.message.as_bytes(),
+                &options,
+            )
+            .await
+            {
+                Ok(()) => store.remove(&record.id).await?,
+                Err(err) => {
+                    tracing::warn!(
+                        "延迟消息投递失败，{}毫秒后重试: {} - {:?}",
+                        CLAIM_LEASE_MILLS,
+                        record.id,
+                        err
+                    );
+                }
+            }
+        }
+
+        Ok(records.len())
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryDelayedStore;
+
+// 测试用的内存实现
+#[cfg(test)]
+mod memory {
+    use super::{DelayedRecord, DelayedStore, STORE};
+    use crate::common::testutil;
+    use futures::future::BoxFuture;
+    use redis::RedisResult;
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::{Arc, Mutex};
+
+    /// 进程内的内存延迟消息存储，用于在没有redis的环境下测试
+    #[derive(Default)]
+    pub struct MemoryDelayedStore {
+        state: Mutex<MemoryDelayedState>,
+    }
+
+    #[derive(Default)]
+    struct MemoryDelayedState {
+        /// (到期时间, 消息id)
+        schedule: BTreeMap<(i64, String), ()>,
+        /// 消息id -> (当前分数, 记录)
+        records: HashMap<String, (i64, DelayedRecord)>,
+    }
+
+    impl MemoryDelayedStore {
+        /// 安装进程内共享的内存延迟消息存储，重复调用返回同一实例
+        pub fn install() -> Arc<Self> {
+            let store = testutil::shared(Arc::<Self>::default);
+            STORE.install(store.clone());
+            store
+        }
+
+        /// 是否存在尚未投递的延迟消息
+        pub fn contains(&self, id: &str) -> bool {
+            self.state.lock().unwrap().records.contains_key(id)
+        }
+    }
+
+    impl DelayedStore for MemoryDelayedStore {
+        fn schedule<'a>(&'a self, record: &'a DelayedRecord) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                if let Some((score, _)) = state.records.remove(&record.id) {
+                    state.schedule.remove(&(score, record.id.clone()));
+                }
+                state
+                    .schedule
+                    .insert((record.due_at, record.id.clone()), ());
+                state
+                    .records
+                    .insert(record.id.clone(), (record.due_at, record.clone()));
+                Ok(())
+            })
+        }
+
+        fn cancel<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<bool>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                match state.records.remove(id) {
+                    Some((score, _)) => {
+                        state.schedule.remove(&(score, id.to_string()));
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            })
+        }
+
+        fn claim_due(
+            &self,
+            now_ms: i64,
+            lease_ms: i64,
+            limit: usize,
+        ) -> BoxFuture<'_, RedisResult<Vec<DelayedRecord>>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                let due: Vec<(i64, String)> = state
+                    .schedule
+                    .keys()
+                    .take_while(|(score, _)| *score <= now_ms)
+                    .take(limit)
+                    .cloned()
+                    .collect();
+
+                let mut claimed = Vec::with_capacity(due.len());
+                for (score, id) in due {
+                    state.schedule.remove(&(score, id.clone()));
+                    let lease_until = now_ms + lease_ms;
+                    if let Some((current, record)) = state.records.get_mut(&id) {
+                        *current = lease_until;
+                        claimed.push(record.clone());
+                        state.schedule.insert((lease_until, id), ());
+                    }
+                }
+                Ok(claimed)
+            })
+        }
+
+        fn remove<'a>(&'a self, id: &'a str) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                let mut state = self.state.lock().unwrap();
+                if let Some((score, _)) = state.records.remove(id) {
+                    state.schedule.remove(&(score, id.to_string()));
+                }
+                Ok(())
+            })
+        }
+    }
+}
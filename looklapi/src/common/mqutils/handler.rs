@@ -0,0 +1,122 @@
+use crate::app::AppError;
+use crate::common::mqutils::codec::Envelope;
+use crate::common::mqutils::rpc;
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::sync::Arc;
+
+/// 消费失败时的处理方式
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code, reason = "各变体由业务处理器按处理结果返回")]
+pub enum ConsumeError {
+    /// 延迟重试，超过最大重试次数后转入死信队列
+    #[error("retry: {0}")]
+    Retry(String),
+    /// 立即重新入队
+    #[error("requeue: {0}")]
+    Requeue(String),
+    /// 不再重试，直接转入死信队列
+    #[error("dead letter: {0}")]
+    DeadLetter(String),
+    /// 确认并丢弃消息
+    #[error("discard: {0}")]
+    Discard(String),
+}
+
+impl From<anyhow::Error> for ConsumeError {
+    fn from(err: anyhow::Error) -> Self {
+        ConsumeError::Retry(err.to_string())
+    }
+}
+
+impl From<AppError> for ConsumeError {
+    fn from(err: AppError) -> Self {
+        ConsumeError::Retry(err.to_string())
+    }
+}
+
+/// 消息处理器，入参为解码后的消息，消息内容按其编码格式解码
+pub type ConsumeHandler =
+    Arc<dyn Fn(Envelope) -> BoxFuture<'static, Result<(), ConsumeError>> + Send + Sync>;
+
+/// 将同步的bool处理器包装为消息处理器，返回false时进入延迟重试
+#[allow(dead_code, reason = "由保留的旧消费者构造方法使用")]
+pub fn legacy_handler(
+    consume: impl Fn(serde_json::Value) -> bool + Send + Sync + 'static,
+) -> ConsumeHandler {
+    Arc::new(move |envelope: Envelope| {
+        let result = match envelope.decode_content::<serde_json::Value>() {
+            Ok(value) => {
+                if consume(value) {
+                    Ok(())
+                } else {
+                    Err(ConsumeError::Retry("consume returned false".to_string()))
+                }
+            }
+            Err(err) => {
+                tracing::error!("消息内容反序列化失败: {:?}", err);
+                Err(ConsumeError::Retry(err.to_string()))
+            }
+        };
+        futures::future::ready(result).boxed()
+    })
+}
+
+/// 将类型化的异步处理器包装为消息处理器
+/// 消息内容无法反序列化为T时，消息直接转入死信队列
+pub fn typed_handler<T, F, Fut, E>(handler: F) -> ConsumeHandler
+where
+    T: DeserializeOwned + Send + 'static,
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), E>> + Send + 'static,
+    E: Into<ConsumeError>,
+{
+    let handler = Arc::new(handler);
+    Arc::new(move |envelope: Envelope| {
+        let handler = handler.clone();
+        async move {
+            let msg: T = match envelope.decode_content() {
+                Ok(msg) => msg,
+                Err(err) => {
+                    tracing::error!("消息内容反序列化失败: {:?}", err);
+                    return Err(ConsumeError::DeadLetter(format!(
+                        "deserialize failed: {}",
+                        err
+                    )));
+                }
+            };
+            handler(msg).await.map_err(Into::into)
+        }
+        .boxed()
+    })
+}
+
+/// 将RPC处理器包装为消息处理器
+/// 处理结果(含请求反序列化失败与处理器错误)自动回复给调用方，请求消息总是被确认
+pub fn rpc_handler<Req, Resp, F, Fut, E>(handler: F) -> ConsumeHandler
+where
+    Req: DeserializeOwned + Send + 'static,
+    Resp: Serialize + Send + 'static,
+    F: Fn(Req) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Resp, E>> + Send + 'static,
+    E: std::fmt::Display,
+{
+    let handler = Arc::new(handler);
+    Arc::new(move |envelope: Envelope| {
+        let handler = handler.clone();
+        async move {
+            let result = match envelope.decode_content::<Req>() {
+                Ok(req) => handler(req).await.map_err(|err| err.to_string()),
+                Err(err) => {
+                    tracing::error!("RPC请求反序列化失败: {:?}", err);
+                    Err(format!("deserialize failed: {}", err))
+                }
+            };
+            rpc::reply(result).await
+        }
+        .boxed()
+    })
+}
@@ -1,6 +1,61 @@
+pub mod admin;
+pub mod codec;
 pub mod consts;
 pub mod consumer;
+pub mod context;
+pub mod dedupe;
+pub mod delayed;
+pub mod handler;
 pub mod models;
+pub mod outbox;
+pub mod partition;
 pub mod publisher;
 pub mod rabbitmq_pool;
-pub mod retry;
\ No newline at end of file
+pub mod registry;
+pub mod retry;
+pub mod rpc;
+pub mod topology;
+pub mod transport;
+
+#[cfg(test)]
+mod test;
+
+/// 注册消费者的宏
+/// 参数为返回 `Arc<Consumer>` 的无捕获函数或闭包，MQ就绪后自动创建并绑定
+#[macro_export]
+macro_rules! register_consumer {
+    ($factory:expr) => {
+        inventory::submit! {
+            $crate::common::mqutils::registry::ConsumerRegistration {
+                consumer: $factory,
+            }
+        }
+    };
+}
+
+/// 注册分区消费者的宏
+/// 参数为返回 `Vec<Arc<Consumer>>` 的无捕获函数或闭包，通常为 `Consumer::partitioned`
+#[macro_export]
+macro_rules! register_partitioned_consumer {
+    ($factory:expr) => {
+        inventory::submit! {
+            $crate::common::mqutils::registry::PartitionedConsumerRegistration {
+                consumers: $factory,
+            }
+        }
+    };
+}
+
+/// 注册拓扑的宏
+/// 参数为返回 `Topology` 的无捕获函数或闭包，连接池初始化时统一声明
+/// 已注册的队列与交换器在发布和绑定消费者时不再重复声明
+#[macro_export]
+macro_rules! register_topology {
+    ($factory:expr) => {
+        inventory::submit! {
+            $crate::common::mqutils::topology::TopologyRegistration {
+                topology: $factory,
+            }
+        }
+    };
+}
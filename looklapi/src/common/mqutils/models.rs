@@ -2,21 +2,11 @@ use crate::common::mqutils::consts::ConsumerType;
 use chrono::{DateTime, Utc};
 use lapin::{Channel, Connection};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicI32, AtomicI64, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex, RwLock};
 
-/// connect 空闲超时时间 分钟
-const CONN_IDLE_TIMEOUT_MIN: i32 = 10;
-/// channel 空闲超时时间 分钟
-const CH_IDLE_TIMEOUT_MIN: i32 = 5;
-
-/// connect 数量限制
-const CONN_LIMIT: i32 = 100;
-/// channel 数量限制
-const CH_LIMIT_FOR_CONN: i32 = 100;
-
 /// 通道状态
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ChannelStatus {
@@ -37,9 +27,33 @@ pub struct RabbitMqConnData {
     pub live_ch: Arc<AtomicI32>,
     /// 最近一次使用时间 毫秒
     pub last_use_mills: Arc<AtomicI64>,
+    /// 本连接已声明的队列与交换器，连接重建后重新声明
+    declared: Mutex<HashSet<String>>,
 }
 
 impl RabbitMqConnData {
+    /// 是否已声明
+    pub fn is_declared(&self, key: &str) -> bool {
+        self.declared
+            .lock()
+            .map(|declared| declared.contains(key))
+            .unwrap_or(false)
+    }
+
+    /// 标记为已声明
+    pub fn mark_declared(&self, key: String) {
+        if let Ok(mut declared) = self.declared.lock() {
+            declared.insert(key);
+        }
+    }
+
+    /// 移除声明标记
+    pub fn forget_declared(&self, key: &str) {
+        if let Ok(mut declared) = self.declared.lock() {
+            declared.remove(key);
+        }
+    }
+
     /// 增加channel数
     pub fn inc_chan(&self) {
         self.live_ch.fetch_add(1, Ordering::Relaxed);
@@ -59,6 +73,7 @@ impl RabbitMqConnData {
             conn,
             live_ch: Arc::new(AtomicI32::new(0)),
             last_use_mills: Arc::new(AtomicI64::new(Utc::now().timestamp_millis())),
+            declared: Mutex::new(HashSet::new()),
         }
     }
 }
@@ -110,6 +125,56 @@ impl MqChannel {
     }
 }
 
+/// 通道数量统计
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChannelStats {
+    /// 空闲
+    pub idle: usize,
+    /// 使用中
+    pub busy: usize,
+    /// 超时空闲
+    pub timeout: usize,
+    /// 已关闭待清理
+    pub close: usize,
+}
+
+impl ChannelStats {
+    /// 统计一个通道
+    pub fn add(&mut self, status: ChannelStatus) {
+        match status {
+            ChannelStatus::Idle => self.idle += 1,
+            ChannelStatus::Busy => self.busy += 1,
+            ChannelStatus::Timeout => self.timeout += 1,
+            ChannelStatus::Close => self.close += 1,
+        }
+    }
+}
+
+/// 连接池运行状态快照
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PoolStats {
+    /// 发布连接数
+    pub pub_connections: usize,
+    /// 消费连接数
+    pub rec_connections: usize,
+    /// 发布通道
+    pub pub_channels: ChannelStats,
+    /// 消费通道
+    pub rec_channels: ChannelStats,
+    /// 获取发布通道次数
+    pub pipeline_waits: u64,
+    /// 获取发布通道平均等待时间 毫秒
+    pub pipeline_wait_avg_ms: f64,
+    /// 获取发布通道最大等待时间 毫秒
+    pub pipeline_wait_max_ms: f64,
+    /// 发布消息数
+    pub publishes: u64,
+    /// 发布失败消息数
+    pub publish_failures: u64,
+    /// 是否已关闭
+    pub shutdown: bool,
+}
+
 /// 消息体
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MqMessage {
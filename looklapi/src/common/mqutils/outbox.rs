@@ -0,0 +1,434 @@
+use crate::app::app_config::AppConfig;
+use crate::app::appcontext;
+use crate::app::appcontext::events::AppEventMqReady;
+use crate::app::appcontext::observer::AppObserver;
+use crate::common::mqutils::publisher::{self, PublishOptions};
+use crate::common::mqutils::rabbitmq_pool::RabbitmqConnPool;
+use crate::common::mqutils::retry::RetryBackoff;
+use crate::register_observer_for;
+use crate::request_context;
+use futures::TryStreamExt;
+use mongodb::bson::{self, DateTime, doc};
+use mongodb::options::{IndexOptions, ReturnDocument};
+use mongodb::{Client, ClientSession, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+/// outbox集合名称
+pub const OUTBOX_COLLECTION: &str = "mq_outbox";
+
+/// 轮询待发送消息的间隔
+const RELAY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// 每轮最多转发的消息数
+const RELAY_BATCH_SIZE: usize = 100;
+/// 转发中消息的租约时间，超时未完成视为转发进程异常，可被重新领取
+const RELAY_LEASE_MILLS: i64 = 30 * 1000;
+/// 已发送消息的保留时间
+const SENT_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// outbox错误
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code, reason = "部分变体只在对外的写入接口中构造")]
+pub enum OutboxError {
+    /// 缺少mongodb配置
+    #[error("mongodb config not found")]
+    NoConfig,
+    /// 参数无效
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// 消息序列化失败
+    #[error("serialize message failed: {0}")]
+    Serialization(String),
+    /// mongodb错误
+    #[error("mongodb error: {0}")]
+    Mongo(#[from] mongodb::error::Error),
+}
+
+/// 消息发布类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OutboxKind {
+    WorkQueue,
+    Broadcast,
+    Topic,
+}
+
+/// 消息状态
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OutboxStatus {
+    /// 待发送
+    Pending,
+    /// 转发中
+    Sending,
+    /// 已发送
+    Sent,
+}
+
+/// outbox消息记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxRecord {
+    /// 消息id，与MqMessage.guid一致
+    #[serde(rename = "_id")]
+    pub id: String,
+    /// 发布类型
+    pub kind: OutboxKind,
+    /// 交换器
+    pub exchange: String,
+    /// 路由键
+    pub route_key: String,
+    /// 序列化后的MqMessage
+    pub message: String,
+    /// 状态
+    pub status: OutboxStatus,
+    /// 已尝试转发次数
+    pub attempts: i32,
+    /// 下次可转发时间
+    pub next_attempt_at: DateTime,
+    /// 转发租约到期时间
+    pub lease_until: Option<DateTime>,
+    /// 创建时间
+    pub created_at: DateTime,
+    /// 发送成功时间
+    pub sent_at: Option<DateTime>,
+    /// 最近一次失败原因
+    pub last_error: Option<String>,
+    /// 保存时的请求id，转发时作为trace id
+    #[serde(default)]
+    pub trace_id: Option<String>,
+}
+
+static MONGO_CLIENT: OnceCell<Client> = OnceCell::const_new();
+
+/// 获取outbox使用的mongodb客户端
+/// 业务方应使用该客户端开启会话，使业务数据与outbox记录在同一事务中写入
+pub async fn mongo_client() -> Result<Client, OutboxError> {
+    MONGO_CLIENT
+        .get_or_try_init(|| async {
+            let rudi_context = appcontext::rudi_context::instance();
+            let ctx = rudi_context.read().await;
+            let app_config = ctx.get_ctx().get_single::<AppConfig>();
+            let mongo = app_config.mongodb.as_ref().ok_or(OutboxError::NoConfig)?;
+            let client = Client::with_uri_str(mongo.uri.as_str()).await?;
+            Ok(client)
+        })
+        .await
+        .cloned()
+}
+
+// 获取outbox集合，使用连接串中的默认数据库
+async fn collection() -> Result<Collection<OutboxRecord>, OutboxError> {
+    let client = mongo_client().await?;
+    let database = client.default_database().ok_or_else(|| {
+        OutboxError::InvalidArgument("mongodb uri has no default database".to_string())
+    })?;
+    Ok(database.collection(OUTBOX_COLLECTION))
+}
+
+/// 在调用方会话中保存工作队列消息
+#[allow(dead_code, reason = "在业务事务中写入发件箱，由业务代码调用")]
+pub async fn save_work_queue_msg<T: Serialize>(
+    session: &mut ClientSession,
+    route_key: &str,
+    msg: T,
+) -> Result<String, OutboxError> {
+    if route_key.is_empty() {
+        return Err(OutboxError::InvalidArgument("empty route key".to_string()));
+    }
+    save(session, OutboxKind::WorkQueue, "", route_key, msg).await
+}
+
+/// 在调用方会话中保存广播消息
+#[allow(dead_code, reason = "在业务事务中写入发件箱，由业务代码调用")]
+pub async fn save_broadcast_msg<T: Serialize>(
+    session: &mut ClientSession,
+    exchange: &str,
+    msg: T,
+) -> Result<String, OutboxError> {
+    if exchange.is_empty() {
+        return Err(OutboxError::InvalidArgument("empty exchange".to_string()));
+    }
+    save(session, OutboxKind::Broadcast, exchange, "", msg).await
+}
+
+/// 在调用方会话中保存topic消息
+#[allow(dead_code, reason = "在业务事务中写入发件箱，由业务代码调用")]
+pub async fn save_topic_msg<T: Serialize>(
+    session: &mut ClientSession,
+    exchange: &str,
+    route_key: &str,
+    msg: T,
+) -> Result<String, OutboxError> {
+    if exchange.is_empty() || route_key.is_empty() {
+        return Err(OutboxError::InvalidArgument(
+            "empty exchange or route key".to_string(),
+        ));
+    }
+    save(session, OutboxKind::Topic, exchange, route_key, msg).await
+}
+
+// 保存消息记录，返回消息id
+async fn save<T: Serialize>(
+    session: &mut ClientSession,
+    kind: OutboxKind,
+    exchange: &str,
+    route_key: &str,
+    msg: T,
+) -> Result<String, OutboxError> {
+    let meta_msg = publisher::convert_message(msg)
+        .ok_or_else(|| OutboxError::Serialization("convert message failed".to_string()))?;
+    let message = serde_json::to_string(&meta_msg)
+        .map_err(|err| OutboxError::Serialization(err.to_string()))?;
+
+    let now = DateTime::now();
+    let record = OutboxRecord {
+        id: meta_msg.guid.clone(),
+        kind,
+        exchange: exchange.to_string(),
+        route_key: route_key.to_string(),
+        message,
+        status: OutboxStatus::Pending,
+        attempts: 0,
+        next_attempt_at: now,
+        lease_until: None,
+        created_at: now,
+        sent_at: None,
+        last_error: None,
+        trace_id: request_context::current_request_id(),
+    };
+
+    collection()
+        .await?
+        .insert_one(record)
+        .session(&mut *session)
+        .await?;
+    Ok(meta_msg.guid)
+}
+
+/// outbox转发器
+/// MQ就绪后启动，将待发送记录通过连接池发布，失败时按退避策略重试
+pub struct OutboxRelay {
+    started: AtomicBool,
+    backoff: RetryBackoff,
+}
+
+impl AppObserver for OutboxRelay {
+    fn on_application_event(&self, event: &dyn std::any::Any) {
+        if event.downcast_ref::<AppEventMqReady>().is_some() {
+            tokio::spawn(async {
+                OutboxRelay::get_instance().start().await;
+            });
+        }
+    }
+}
+
+// 注册OutboxRelay作为应用事件观察者，订阅AppEventMqReady事件
+register_observer_for!(OutboxRelay, AppEventMqReady);
+
+// 全局转发器实例
+static GLOBAL_RELAY: OnceLock<Arc<OutboxRelay>> = OnceLock::new();
+
+impl OutboxRelay {
+    pub fn get_instance() -> Arc<Self> {
+        GLOBAL_RELAY
+            .get_or_init(|| {
+                Arc::new(OutboxRelay {
+                    started: AtomicBool::new(false),
+                    backoff: RetryBackoff::Exponential {
+                        initial_ms: 1000,
+                        multiplier: 2,
+                        max_ms: 10 * 60 * 1000,
+                    },
+                })
+            })
+            .clone()
+    }
+
+    // 启动转发循环，未配置mongodb时不启动
+    async fn start(self: Arc<Self>) {
+        if self.started.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let coll = match collection().await {
+            Ok(coll) => coll,
+            Err(OutboxError::NoConfig) => return,
+            Err(err) => {
+                tracing::error!("outbox转发器启动失败: {:?}", err);
+                self.started.store(false, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        if let Err(err) = ensure_indexes(&coll).await {
+            tracing::error!("创建outbox索引失败: {:?}", err);
+        }
+
+        tokio::spawn(async move {
+            loop {
+                // 连接池关闭后停止转发，未转发的记录在下次启动后继续处理
+                if RabbitmqConnPool::get_instance().is_shutdown() {
+                    break;
+                }
+                match self.relay_batch(&coll).await {
+                    // 本轮已满，可能仍有积压，立即继续
+                    Ok(count) if count >= RELAY_BATCH_SIZE => continue,
+                    Ok(_) => {}
+                    Err(err) => tracing::error!("outbox转发失败: {:?}", err),
+                }
+                tokio::time::sleep(RELAY_POLL_INTERVAL).await;
+            }
+        });
+        tracing::info!("outbox relay started");
+    }
+
+    // 转发一批到期的记录，返回处理条数
+    async fn relay_batch(&self, coll: &Collection<OutboxRecord>) -> Result<usize, OutboxError> {
+        let mut count = 0;
+        while count < RELAY_BATCH_SIZE {
+            let record = match claim(coll).await? {
+                Some(record) => record,
+                None => break,
+            };
+            count += 1;
+
+            match relay(&record).await {
+                Ok(()) => {
+                    coll.update_one(
+                        doc! { "_id": &record.id },
+                        doc! {
+                            "$set": {
+                                "status": bson::to_bson(&OutboxStatus::Sent).unwrap_or_default(),
+                                "sent_at": DateTime::now(),
+                                "lease_until": bson::Bson::Null,
+                                "last_error": bson::Bson::Null,
+                            }
+                        },
+                    )
+                    .await?;
+                }
+                Err(err) => {
+                    let delay_ms = self.backoff.delay_ms(record.attempts.max(1) as u32);
+                    tracing::warn!(
+                        "outbox消息转发失败，{}毫秒后重试: {} - {:?}",
+                        delay_ms,
+                        record.id,
+                        err
+                    );
+                    let next_attempt_at =
+                        DateTime::from_millis(DateTime::now().timestamp_millis() + delay_ms as i64);
+                    coll.update_one(
+                        doc! { "_id": &record.id },
+                        doc! {
+                            "$set": {
+                                "status": bson::to_bson(&OutboxStatus::Pending).unwrap_or_default(),
+                                "next_attempt_at": next_attempt_at,
+                                "lease_until": bson::Bson::Null,
+                                "last_error": err.to_string(),
+                            }
+                        },
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+// 领取一条到期的待发送记录，或租约已过期的转发中记录
+async fn claim(coll: &Collection<OutboxRecord>) -> Result<Option<OutboxRecord>, OutboxError> {
+    let now = DateTime::now();
+    let lease_until = DateTime::from_millis(now.timestamp_millis() + RELAY_LEASE_MILLS);
+    let pending = bson::to_bson(&OutboxStatus::Pending).unwrap_or_default();
+    let sending = bson::to_bson(&OutboxStatus::Sending).unwrap_or_default();
+
+    let record = coll
+        .find_one_and_update(
+            doc! {
+                "$or": [
+                    { "status": pending, "next_attempt_at": { "$lte": now } },
+                    { "status": sending.clone(), "lease_until": { "$lte": now } },
+                ]
+            },
+            doc! {
+                "$set": { "status": sending, "lease_until": lease_until },
+                "$inc": { "attempts": 1 },
+            },
+        )
+        .sort(doc! { "next_attempt_at": 1 })
+        .return_document(ReturnDocument::After)
+        .await?;
+    Ok(record)
+}
+
+// 发布记录中的消息
+async fn relay(record: &OutboxRecord) -> publisher::PublishResult {
+    let options = PublishOptions {
+        trace_id: record.trace_id.clone(),
+        ..Default::default()
+    };
+    match record.kind {
+        OutboxKind::WorkQueue => {
+            publisher::pub_work_queue_msg_internal(
+                &record.route_key,
+                record.message.as_bytes(),
+                &options,
+            )
+            .await
+        }
+        OutboxKind::Broadcast => {
+            publisher::pub_broadcast_msg_internal(
+                &record.exchange,
+                record.message.as_bytes(),
+                &options,
+            )
+            .await
+        }
+        OutboxKind::Topic => {
+            publisher::pub_topic_msg_internal(
+                &record.exchange,
+                &record.route_key,
+                record.message.as_bytes(),
+                &options,
+            )
+            .await
+        }
+    }
+}
+
+// 创建轮询索引与已发送记录的过期索引
+async fn ensure_indexes(coll: &Collection<OutboxRecord>) -> Result<(), OutboxError> {
+    coll.create_index(
+        IndexModel::builder()
+            .keys(doc! { "status": 1, "next_attempt_at": 1 })
+            .build(),
+    )
+    .await?;
+    coll.create_index(
+        IndexModel::builder()
+            .keys(doc! { "sent_at": 1 })
+            .options(IndexOptions::builder().expire_after(SENT_RETENTION).build())
+            .build(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// 查询指定状态的outbox记录，按创建时间倒序
+#[allow(dead_code, reason = "供排查发件箱积压使用")]
+pub async fn list_records(
+    status: OutboxStatus,
+    limit: i64,
+) -> Result<Vec<OutboxRecord>, OutboxError> {
+    let status = bson::to_bson(&status).unwrap_or_default();
+    let cursor = collection()
+        .await?
+        .find(doc! { "status": status })
+        .sort(doc! { "created_at": -1 })
+        .limit(limit)
+        .await?;
+    Ok(cursor.try_collect().await?)
+}
@@ -0,0 +1,35 @@
+use lapin::types::{AMQPValue, FieldTable};
+
+/// 分区键消息头
+pub const HEADER_PARTITION_KEY: &str = "x-partition-key";
+
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+/// 计算32位FNV-1a哈希，发布方与消费方需使用相同的算法
+pub fn fnv1a(key: &str) -> u32 {
+    key.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// 分区键对应的分区序号
+pub fn partition_of(partition_key: &str, partitions: u32) -> u32 {
+    if partitions <= 1 {
+        return 0;
+    }
+    fnv1a(partition_key) % partitions
+}
+
+/// 分区队列名称
+pub fn partition_queue(route_key: &str, index: u32) -> String {
+    format!("{}.p{}", route_key, index)
+}
+
+// 分区队列声明参数
+// 单活消费者保证同一时刻只有一个消费者处理分区内的消息，其余消费者作为备用
+pub(crate) fn queue_arguments() -> FieldTable {
+    let mut arguments = FieldTable::default();
+    arguments.insert("x-single-active-consumer".into(), AMQPValue::Boolean(true));
+    arguments
+}
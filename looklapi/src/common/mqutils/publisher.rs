@@ -1,198 +1,708 @@
+use crate::common::mqutils::codec::{self, Codec, Envelope};
+use crate::common::mqutils::context::HEADER_TRACE_ID;
+use crate::common::mqutils::delayed::{self, DelayedRecord};
 use crate::common::mqutils::models::MqMessage;
-use crate::common::mqutils::rabbitmq_pool::RabbitmqConnPool;
+use crate::common::mqutils::partition;
+use crate::common::mqutils::topology;
+use crate::common::mqutils::transport::{self, Declare, Publishing, TransportError};
+use crate::request_context;
 use chrono;
 use lapin;
+use lapin::types::{AMQPValue, FieldTable};
 use serde::Serialize;
 use serde_json;
+use std::collections::BTreeMap;
+use std::time::Duration;
 use tracing;
-use uuid::Uuid;
+
+/// 默认等待broker确认的超时时间
+const DEFAULT_CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 消息发布失败原因
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code, reason = "部分变体只在对外的发布接口中构造")]
+pub enum PublishError {
+    /// 参数无效
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// 消息序列化失败
+    #[error("serialize message failed: {0}")]
+    Serialization(String),
+    /// 无可用的发布通道
+    #[error("no publish channel available: {0}")]
+    NoChannel(String),
+    /// broker拒绝了消息
+    #[error("message nacked by broker")]
+    Nack,
+    /// mandatory消息无法路由到任何队列
+    #[error("message unroutable: {reply_code} {reply_text}")]
+    Unroutable { reply_code: u16, reply_text: String },
+    /// 等待broker确认超时
+    #[error("wait for publisher confirm timeout")]
+    ConfirmTimeout,
+    /// 声明或发布时broker返回错误
+    #[error("broker error: {0}")]
+    Broker(#[from] lapin::Error),
+    /// 传输层错误
+    #[error("transport error: {0}")]
+    Transport(#[from] TransportError),
+    /// 延迟消息存储错误
+    #[error("delayed store error: {0}")]
+    DelayedStore(#[from] redis::RedisError),
+    /// 批量发布中部分消息失败，已成功的消息不会撤回
+    #[error("batch publish failed: {failed}/{total}, first error: {first}")]
+    Batch {
+        failed: usize,
+        total: usize,
+        first: Box<PublishError>,
+    },
+}
+
+/// 发布结果
+pub type PublishResult = Result<(), PublishError>;
+
+/// 发布选项
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    /// 无法路由到任何队列时由broker退回并报告为Unroutable
+    pub mandatory: bool,
+    /// 等待broker确认的超时时间
+    pub confirm_timeout: Duration,
+    /// 消息类型
+    pub message_type: Option<String>,
+    /// 关联id
+    pub correlation_id: Option<String>,
+    /// 回复队列
+    pub reply_to: Option<String>,
+    /// trace id，未设置时取当前任务的请求id
+    pub trace_id: Option<String>,
+    /// 自定义消息头
+    pub headers: BTreeMap<String, String>,
+    /// 消息过期时间，过期未消费的消息由broker丢弃
+    pub expiration: Option<Duration>,
+    /// 编码格式
+    pub codec: Codec,
+    /// 消息体超过该字节数时gzip压缩，None表示不压缩
+    pub compress_threshold: Option<usize>,
+}
+
+impl Default for PublishOptions {
+    fn default() -> Self {
+        Self {
+            mandatory: false,
+            confirm_timeout: DEFAULT_CONFIRM_TIMEOUT,
+            message_type: None,
+            correlation_id: None,
+            reply_to: None,
+            trace_id: None,
+            headers: BTreeMap::new(),
+            expiration: None,
+            codec: Codec::default(),
+            compress_threshold: None,
+        }
+    }
+}
+
+#[allow(dead_code, reason = "发布选项的构造方法，由业务代码按需调用")]
+impl PublishOptions {
+    /// 设置mandatory标志
+    pub fn mandatory(mut self, mandatory: bool) -> Self {
+        self.mandatory = mandatory;
+        self
+    }
+
+    /// 设置确认超时时间
+    pub fn confirm_timeout(mut self, timeout: Duration) -> Self {
+        self.confirm_timeout = timeout;
+        self
+    }
+
+    /// 设置消息类型
+    pub fn message_type(mut self, message_type: &str) -> Self {
+        self.message_type = Some(message_type.to_string());
+        self
+    }
+
+    /// 设置关联id
+    pub fn correlation_id(mut self, correlation_id: &str) -> Self {
+        self.correlation_id = Some(correlation_id.to_string());
+        self
+    }
+
+    /// 设置回复队列
+    pub fn reply_to(mut self, reply_to: &str) -> Self {
+        self.reply_to = Some(reply_to.to_string());
+        self
+    }
+
+    /// 设置trace id
+    pub fn trace_id(mut self, trace_id: &str) -> Self {
+        self.trace_id = Some(trace_id.to_string());
+        self
+    }
+
+    /// 添加自定义消息头
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// 设置消息过期时间
+    pub fn expiration(mut self, expiration: Duration) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// 设置编码格式
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// 设置压缩阈值
+    pub fn compress_threshold(mut self, threshold: usize) -> Self {
+        self.compress_threshold = Some(threshold);
+        self
+    }
+
+    // 将选项写入消息属性，已有的消息头保留
+    fn apply(&self, properties: lapin::BasicProperties) -> lapin::BasicProperties {
+        let mut headers = properties.headers().clone().unwrap_or_default();
+        for (key, value) in &self.headers {
+            headers.insert(key.as_str().into(), AMQPValue::LongString(value.as_str().into()));
+        }
+        if let Some(trace_id) = self
+            .trace_id
+            .clone()
+            .or_else(request_context::current_request_id)
+        {
+            headers.insert(HEADER_TRACE_ID.into(), AMQPValue::LongString(trace_id.into()));
+        }
+
+        let mut properties = properties
+            .with_headers(headers)
+            .with_content_type(self.codec.content_type().into());
+        if let Some(message_type) = &self.message_type {
+            properties = properties.with_type(message_type.as_str().into());
+        }
+        if let Some(correlation_id) = &self.correlation_id {
+            properties = properties.with_correlation_id(correlation_id.as_str().into());
+        }
+        if let Some(reply_to) = &self.reply_to {
+            properties = properties.with_reply_to(reply_to.as_str().into());
+        }
+        if let Some(expiration) = self.expiration {
+            properties = properties.with_expiration(expiration.as_millis().to_string().into());
+        }
+        properties
+    }
+}
 
 // 发布工作队列消息
-pub async fn pub_work_queue_msg<T: Serialize>(route_key: &str, msg: T) -> bool {
+pub async fn pub_work_queue_msg<T: Serialize>(route_key: &str, msg: T) -> PublishResult {
+    pub_work_queue_msg_with_options(route_key, msg, &PublishOptions::default()).await
+}
+
+// 发布工作队列消息(指定发布选项)
+#[allow(dead_code, reason = "发布接口，由业务代码按需调用")]
+pub async fn pub_work_queue_msg_with_options<T: Serialize>(
+    route_key: &str,
+    msg: T,
+    options: &PublishOptions,
+) -> PublishResult {
     if route_key.is_empty() {
-        return false;
+        return Err(PublishError::InvalidArgument("empty route key".to_string()));
     }
 
-    let meta_msg = convert_message(msg);
-    if meta_msg.is_none() {
-        return false;
+    let payload = serialize_message(msg, options.codec)?;
+    let result = pub_work_queue_msg_internal(route_key, &payload, options).await;
+    if let Err(ref err) = result {
+        tracing::error!("发布工作队列消息失败: {:?}", err);
     }
+    result
+}
 
-    let json_msg = serde_json::to_string(&meta_msg.unwrap()).unwrap();
-    if let Err(err) = pub_work_queue_msg_internal(route_key, &json_msg).await {
-        tracing::error!("发布工作队列消息失败: {:?}", err);
-        return false;
+// 批量发布工作队列消息
+#[allow(dead_code, reason = "批量发布接口，由业务代码调用")]
+pub async fn pub_work_queue_batch<T: Serialize>(route_key: &str, msgs: Vec<T>) -> PublishResult {
+    pub_work_queue_batch_with_options(route_key, msgs, &PublishOptions::default()).await
+}
+
+// 批量发布工作队列消息(指定发布选项)
+// 所有消息通过同一通道发布后统一等待确认，部分失败时返回PublishError::Batch
+#[allow(dead_code, reason = "批量发布接口，由业务代码调用")]
+pub async fn pub_work_queue_batch_with_options<T: Serialize>(
+    route_key: &str,
+    msgs: Vec<T>,
+    options: &PublishOptions,
+) -> PublishResult {
+    if route_key.is_empty() {
+        return Err(PublishError::InvalidArgument("empty route key".to_string()));
     }
+    if msgs.is_empty() {
+        return Ok(());
+    }
+
+    let payloads = msgs
+        .into_iter()
+        .map(|msg| serialize_message(msg, options.codec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let payloads = payloads.iter().map(|p| p.as_slice()).collect::<Vec<_>>();
 
-    true
+    let result = publish_batch_internal(
+        "",
+        route_key,
+        Declare::Queue {
+            name: route_key,
+            durable: true,
+            arguments: FieldTable::default(),
+        },
+        &payloads,
+        persistent_properties(),
+        options,
+    )
+    .await
+    .and_then(|results| {
+        let total = results.len();
+        let mut errors = results.into_iter().filter_map(|r| r.err());
+        match errors.next() {
+            Some(first) => Err(PublishError::Batch {
+                failed: errors.count() + 1,
+                total,
+                first: Box::new(first),
+            }),
+            None => Ok(()),
+        }
+    });
+    if let Err(ref err) = result {
+        tracing::error!("批量发布工作队列消息失败: {:?}", err);
+    }
+    result
 }
 
-// 发布广播消息
-pub async fn pub_broadcast_msg<T: Serialize>(exchange: &str, msg: T) -> bool {
-    if exchange.is_empty() {
-        return false;
+// 发布延迟消息，delay后投递到工作队列
+// 消息保存在redis中由延迟消息调度器定时投递，投递精度为秒级
+// 返回消息guid，可用于cancel_delayed_msg取消
+#[allow(dead_code, reason = "延迟发布接口，由业务代码调用")]
+pub async fn pub_delayed_msg<T: Serialize>(
+    route_key: &str,
+    msg: T,
+    delay: Duration,
+) -> Result<String, PublishError> {
+    if route_key.is_empty() {
+        return Err(PublishError::InvalidArgument("empty route key".to_string()));
     }
 
-    let meta_msg = convert_message(msg);
-    if meta_msg.is_none() {
-        return false;
+    let meta_msg = convert_message(msg)
+        .ok_or_else(|| PublishError::Serialization("convert message failed".to_string()))?;
+    let message = serde_json::to_string(&meta_msg)
+        .map_err(|err| PublishError::Serialization(err.to_string()))?;
+    let record = DelayedRecord {
+        id: meta_msg.guid.clone(),
+        route_key: route_key.to_string(),
+        message,
+        due_at: chrono::Utc::now().timestamp_millis() + delay.as_millis() as i64,
+        trace_id: request_context::current_request_id(),
+    };
+
+    if let Err(err) = delayed::current().schedule(&record).await {
+        tracing::error!("保存延迟消息失败: {:?}", err);
+        return Err(err.into());
     }
+    Ok(meta_msg.guid)
+}
 
-    let json_msg = serde_json::to_string(&meta_msg.unwrap()).unwrap();
-    if let Err(err) = pub_broadcast_msg_internal(exchange, &json_msg).await {
-        tracing::error!("发布广播消息失败: {:?}", err);
-        return false;
+// 取消尚未投递的延迟消息，返回是否取消成功
+// 消息已投递或不存在时返回false
+#[allow(dead_code, reason = "延迟发布接口，由业务代码调用")]
+pub async fn cancel_delayed_msg(guid: &str) -> Result<bool, PublishError> {
+    if guid.is_empty() {
+        return Err(PublishError::InvalidArgument("empty guid".to_string()));
     }
 
-    true
+    Ok(delayed::current().cancel(guid).await?)
+}
+
+// 发布分区消息
+// 按分区键哈希到partitions个分区队列之一，同一分区键的消息按发布顺序消费
+// partitions需与消费方Consumer::partitioned的分区数一致
+#[allow(dead_code, reason = "分区发布接口，由业务代码调用")]
+pub async fn pub_partitioned_msg<T: Serialize>(
+    route_key: &str,
+    partitions: u32,
+    partition_key: &str,
+    msg: T,
+) -> PublishResult {
+    pub_partitioned_msg_with_options(
+        route_key,
+        partitions,
+        partition_key,
+        msg,
+        &PublishOptions::default(),
+    )
+    .await
+}
+
+// 发布分区消息(指定发布选项)
+#[allow(dead_code, reason = "分区发布接口，由业务代码调用")]
+pub async fn pub_partitioned_msg_with_options<T: Serialize>(
+    route_key: &str,
+    partitions: u32,
+    partition_key: &str,
+    msg: T,
+    options: &PublishOptions,
+) -> PublishResult {
+    if route_key.is_empty() || partition_key.is_empty() {
+        return Err(PublishError::InvalidArgument(
+            "empty route key or partition key".to_string(),
+        ));
+    }
+    if partitions < 1 {
+        return Err(PublishError::InvalidArgument(
+            "partitions must greater than 0".to_string(),
+        ));
+    }
+
+    let queue = partition::partition_queue(
+        route_key,
+        partition::partition_of(partition_key, partitions),
+    );
+    let mut headers = FieldTable::default();
+    headers.insert(
+        partition::HEADER_PARTITION_KEY.into(),
+        AMQPValue::LongString(partition_key.into()),
+    );
+
+    let payload = serialize_message(msg, options.codec)?;
+    let result = pub_queue_msg_internal(
+        &queue,
+        true,
+        partition::queue_arguments(),
+        headers,
+        &payload,
+        options,
+    )
+    .await;
+    if let Err(ref err) = result {
+        tracing::error!("发布分区消息失败: {} - {:?}", queue, err);
+    }
+    result
+}
+
+// 发布广播消息
+pub async fn pub_broadcast_msg<T: Serialize>(exchange: &str, msg: T) -> PublishResult {
+    pub_broadcast_msg_with_options(exchange, msg, &PublishOptions::default()).await
+}
+
+// 发布广播消息(指定发布选项)
+#[allow(dead_code, reason = "发布接口，由业务代码按需调用")]
+pub async fn pub_broadcast_msg_with_options<T: Serialize>(
+    exchange: &str,
+    msg: T,
+    options: &PublishOptions,
+) -> PublishResult {
+    if exchange.is_empty() {
+        return Err(PublishError::InvalidArgument("empty exchange".to_string()));
+    }
+
+    let payload = serialize_message(msg, options.codec)?;
+    let result = pub_broadcast_msg_internal(exchange, &payload, options).await;
+    if let Err(ref err) = result {
+        tracing::error!("发布广播消息失败: {:?}", err);
+    }
+    result
 }
 
 // 发布topic消息
-pub async fn pub_topic_msg<T: Serialize>(exchange: &str, route_key: &str, msg: T) -> bool {
+pub async fn pub_topic_msg<T: Serialize>(exchange: &str, route_key: &str, msg: T) -> PublishResult {
+    pub_topic_msg_with_options(exchange, route_key, msg, &PublishOptions::default()).await
+}
+
+// 发布topic消息(指定发布选项)
+#[allow(dead_code, reason = "发布接口，由业务代码按需调用")]
+pub async fn pub_topic_msg_with_options<T: Serialize>(
+    exchange: &str,
+    route_key: &str,
+    msg: T,
+    options: &PublishOptions,
+) -> PublishResult {
     if exchange.is_empty() || route_key.is_empty() {
-        return false;
+        return Err(PublishError::InvalidArgument(
+            "empty exchange or route key".to_string(),
+        ));
     }
 
-    let meta_msg = convert_message(msg);
-    if meta_msg.is_none() {
-        return false;
+    let payload = serialize_message(msg, options.codec)?;
+    let result = pub_topic_msg_internal(exchange, route_key, &payload, options).await;
+    if let Err(ref err) = result {
+        tracing::error!("发布topic消息失败: {:?}", err);
     }
+    result
+}
 
-    let json_msg = serde_json::to_string(&meta_msg.unwrap()).unwrap();
-    if let Err(err) = pub_topic_msg_internal(exchange, route_key, &json_msg).await {
-        tracing::error!("发布topic消息失败: {:?}", err);
-        return false;
+// 发布消息到已注册的交换器，交换器类型与参数以拓扑定义为准
+#[allow(dead_code, reason = "发布到已注册交换器的接口，由业务代码调用")]
+pub async fn pub_exchange_msg<T: Serialize>(
+    exchange: &str,
+    route_key: &str,
+    msg: T,
+    options: &PublishOptions,
+) -> PublishResult {
+    if topology::exchange(exchange).is_none() {
+        return Err(PublishError::InvalidArgument(format!(
+            "exchange not registered: {}",
+            exchange
+        )));
     }
 
-    true
+    let payload = serialize_message(msg, options.codec)?;
+    let result = publish_internal(
+        exchange,
+        route_key,
+        Declare::Nothing,
+        &payload,
+        persistent_properties(),
+        options,
+    )
+    .await;
+    if let Err(ref err) = result {
+        tracing::error!("发布消息失败: {} - {:?}", exchange, err);
+    }
+    result
 }
 
 // 发布工作队列消息内部实现
-async fn pub_work_queue_msg_internal(
+pub(crate) async fn pub_work_queue_msg_internal(
     route_key: &str,
-    json_msg: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = RabbitmqConnPool::get_instance();
-    let pub_chan = pool.get_pub_channel().await?;
-
-    // 声明队列
-    pub_chan
-        .channel
-        .queue_declare(
-            route_key,
-            lapin::options::QueueDeclareOptions {
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    publish_internal(
+        "",
+        route_key,
+        Declare::Queue {
+            name: route_key,
+            durable: true,
+            arguments: FieldTable::default(),
+        },
+        payload,
+        persistent_properties(),
+        options,
+    )
+    .await
+}
+
+// 通过默认交换器发布消息到指定队列
+// arguments为队列声明参数，headers为消息头
+pub(crate) async fn pub_queue_msg_internal(
+    queue: &str,
+    durable: bool,
+    arguments: FieldTable,
+    headers: FieldTable,
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    let properties = if durable {
+        persistent_properties()
+    } else {
+        default_properties()
+    };
+
+    publish_internal(
+        "",
+        queue,
+        Declare::Queue {
+            name: queue,
+            durable,
+            arguments,
+        },
+        payload,
+        properties.with_headers(headers),
+        options,
+    )
+    .await
+}
+
+// 按原样发布消息体到指定队列，不编码、不压缩，沿用传入的消息属性
+// 用于转发无法解码的消息
+pub(crate) async fn pub_raw_queue_msg_internal(
+    queue: &str,
+    payload: &[u8],
+    properties: lapin::BasicProperties,
+) -> PublishResult {
+    let options = PublishOptions::default();
+    let mut results = transport::current()
+        .publish(Publishing {
+            exchange: "",
+            route_key: queue,
+            declare: Declare::Queue {
+                name: queue,
                 durable: true,
-                auto_delete: false,
-                exclusive: false,
-                ..Default::default()
+                arguments: FieldTable::default(),
             },
-            lapin::types::FieldTable::default(),
-        )
+            messages: vec![(payload.to_vec(), properties.with_delivery_mode(2))],
+            mandatory: options.mandatory,
+            confirm_timeout: options.confirm_timeout,
+        })
         .await?;
+    results.pop().unwrap_or(Ok(()))
+}
 
-    // 发布消息
-    pub_chan
-        .channel
-        .basic_publish(
-            "",        // exchange
-            route_key, // routing_key
-            lapin::options::BasicPublishOptions::default(),
-            json_msg.as_bytes(),
-            lapin::BasicProperties::default()
-                .with_content_type("application/octet-stream".into())
-                .with_delivery_mode(2), // persistent
-        )
-        .await?;
+// 发布RPC回复消息
+// 回复队列为调用方连接独占，不能声明，无法路由时说明调用方已断开
+pub(crate) async fn pub_reply_msg_internal(
+    reply_to: &str,
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    let options = options.clone().mandatory(true);
+    publish_internal(
+        "",
+        reply_to,
+        Declare::Nothing,
+        payload,
+        default_properties(),
+        &options,
+    )
+    .await
+}
 
-    pool.release_channel(pub_chan);
-    Ok(())
+// 通过默认交换器重新发布消息到已存在的队列，不声明队列
+// 队列不存在时返回Unroutable
+pub(crate) async fn pub_existing_queue_msg_internal(
+    queue: &str,
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    let options = options.clone().mandatory(true);
+    publish_internal(
+        "",
+        queue,
+        Declare::Nothing,
+        payload,
+        persistent_properties(),
+        &options,
+    )
+    .await
 }
 
 // 发布广播消息内部实现
-async fn pub_broadcast_msg_internal(
+pub(crate) async fn pub_broadcast_msg_internal(
     exchange: &str,
-    json_msg: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = RabbitmqConnPool::get_instance();
-    let pub_chan = pool.get_pub_channel().await?;
-
-    // 声明交换器
-    pub_chan
-        .channel
-        .exchange_declare(
-            exchange,
-            lapin::ExchangeKind::Fanout,
-            lapin::options::ExchangeDeclareOptions {
-                durable: false,
-                auto_delete: true,
-                internal: false,
-                ..Default::default()
-            },
-            lapin::types::FieldTable::default(),
-        )
-        .await?;
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    publish_internal(
+        exchange,
+        "",
+        Declare::Exchange {
+            name: exchange,
+            kind: lapin::ExchangeKind::Fanout,
+            durable: false,
+            auto_delete: true,
+        },
+        payload,
+        default_properties(),
+        options,
+    )
+    .await
+}
 
-    // 发布消息
-    pub_chan
-        .channel
-        .basic_publish(
-            exchange, // exchange
-            "",       // routing_key
-            lapin::options::BasicPublishOptions::default(),
-            json_msg.as_bytes(),
-            lapin::BasicProperties::default().with_content_type("application/octet-stream".into()),
-        )
-        .await?;
+// 发布topic消息内部实现
+pub(crate) async fn pub_topic_msg_internal(
+    exchange: &str,
+    route_key: &str,
+    payload: &[u8],
+    options: &PublishOptions,
+) -> PublishResult {
+    publish_internal(
+        exchange,
+        route_key,
+        Declare::Exchange {
+            name: exchange,
+            kind: lapin::ExchangeKind::Topic,
+            durable: true,
+            auto_delete: false,
+        },
+        payload,
+        persistent_properties(),
+        options,
+    )
+    .await
+}
 
-    pool.release_channel(pub_chan);
-    Ok(())
+// 发布消息并等待broker确认
+// 发布通道均处于confirm模式，通道出错时标记为关闭，由定时任务清理
+pub(crate) async fn publish_internal(
+    exchange: &str,
+    route_key: &str,
+    declare: Declare<'_>,
+    payload: &[u8],
+    properties: lapin::BasicProperties,
+    options: &PublishOptions,
+) -> PublishResult {
+    let mut results =
+        publish_batch_internal(exchange, route_key, declare, &[payload], properties, options)
+            .await?;
+    results.pop().unwrap_or(Ok(()))
 }
 
-// 发布topic消息内部实现
-async fn pub_topic_msg_internal(
+// 通过同一通道发布多条消息，统一等待broker确认
+// 外层错误表示未能发布(无通道、声明或发布失败)，内层为每条消息的确认结果
+pub(crate) async fn publish_batch_internal(
     exchange: &str,
     route_key: &str,
-    json_msg: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let pool = RabbitmqConnPool::get_instance();
-    let pub_chan = pool.get_pub_channel().await?;
-
-    // 声明交换器
-    pub_chan
-        .channel
-        .exchange_declare(
+    declare: Declare<'_>,
+    payloads: &[&[u8]],
+    properties: lapin::BasicProperties,
+    options: &PublishOptions,
+) -> Result<Vec<PublishResult>, PublishError> {
+    let properties = options.apply(properties);
+    let mut messages = Vec::with_capacity(payloads.len());
+    for payload in payloads {
+        let (payload, content_encoding) =
+            codec::compress(payload.to_vec(), options.compress_threshold)
+                .map_err(|err| PublishError::Serialization(err.to_string()))?;
+        let properties = match content_encoding {
+            Some(content_encoding) => properties
+                .clone()
+                .with_content_encoding(content_encoding.into()),
+            None => properties.clone(),
+        };
+        messages.push((payload, properties));
+    }
+
+    transport::current()
+        .publish(Publishing {
             exchange,
-            lapin::ExchangeKind::Topic,
-            lapin::options::ExchangeDeclareOptions {
-                durable: true,
-                auto_delete: false,
-                internal: false,
-                ..Default::default()
-            },
-            lapin::types::FieldTable::default(),
-        )
-        .await?;
+            route_key,
+            declare,
+            messages,
+            mandatory: options.mandatory,
+            confirm_timeout: options.confirm_timeout,
+        })
+        .await
+}
 
-    // 发布消息
-    pub_chan
-        .channel
-        .basic_publish(
-            exchange,  // exchange
-            route_key, // routing_key
-            lapin::options::BasicPublishOptions::default(),
-            json_msg.as_bytes(),
-            lapin::BasicProperties::default()
-                .with_content_type("application/octet-stream".into())
-                .with_delivery_mode(2), // persistent
-        )
-        .await?;
+// 默认消息属性，content-type由发布选项中的编码格式决定
+fn default_properties() -> lapin::BasicProperties {
+    lapin::BasicProperties::default()
+}
+
+// 持久化消息属性
+fn persistent_properties() -> lapin::BasicProperties {
+    default_properties().with_delivery_mode(2) // persistent
+}
 
-    pool.release_channel(pub_chan);
-    Ok(())
+// 按指定格式编码为MQ消息体
+pub(crate) fn serialize_message<T: Serialize>(msg: T, codec: Codec) -> Result<Vec<u8>, PublishError> {
+    Envelope::new(codec, &msg)
+        .and_then(|envelope| envelope.to_bytes())
+        .map_err(|err| PublishError::Serialization(err.to_string()))
 }
 
 // 转换消息为MQ消息格式
-fn convert_message<T: Serialize>(msg: T) -> Option<MqMessage> {
+pub(crate) fn convert_message<T: Serialize>(msg: T) -> Option<MqMessage> {
     let json_content = match serde_json::to_string(&msg) {
         Ok(content) => content,
         Err(err) => {
@@ -1,17 +1,25 @@
 use crate::app::app_config;
-use crate::app::appcontext::events::AppEventBeanInjected;
+use crate::app::appcontext::events::{AppEventBeanInjected, AppEventMqReady};
 use crate::app::appcontext::observer::AppObserver;
-use crate::common::mqutils::models::{ChannelStatus, MqChannel, RabbitMqConnData};
+use crate::common::mqutils::codec;
+use crate::common::mqutils::consumer::ConsumerBinder;
+use crate::common::mqutils::models::{
+    ChannelStats, ChannelStatus, MqChannel, PoolStats, RabbitMqConnData,
+};
+use crate::common::mqutils::topology;
 use crate::{app, register_observer_for};
 use chrono::Utc;
 use futures::StreamExt;
+use lapin::tcp::{OwnedIdentity, OwnedTLSConfig};
+use lapin::uri::AMQPUri;
 use lapin::{Connection, ConnectionProperties};
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant};
-use tokio::sync::mpsc;
 use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
 use tracing;
 
 unsafe impl Send for RabbitmqConnPool {}
@@ -22,7 +30,11 @@ impl AppObserver for RabbitmqConnPool {
         if event.downcast_ref::<AppEventBeanInjected>().is_some() {
             // 初始化RabbitMQ连接池
             tokio::spawn(async {
-                RabbitmqConnPool::init().await;
+                // 配置无效时MQ不可用，消费者不会绑定，终止启动
+                if let Err(err) = RabbitmqConnPool::init().await {
+                    tracing::error!("RabbitMQ连接池初始化失败: {:?}", err);
+                    std::process::exit(1);
+                }
             });
             tracing::info!("RabbitMQ connection pool initialized via AppEventBeanInjected");
         }
@@ -32,12 +44,6 @@ impl AppObserver for RabbitmqConnPool {
 // 注册RabbitmqConnPool作为应用事件观察者，订阅AppEventBeanInjected事件
 register_observer_for!(RabbitmqConnPool, AppEventBeanInjected);
 
-// 常量定义
-const CONN_LIMIT: i32 = 100; // 连接池大小限制
-const CH_LIMIT_FOR_CONN: i32 = 100; // 每个连接的channel限制
-const CH_IDLE_TIMEOUT_MIN: i32 = 5; // channel空闲超时时间（分钟）
-const CONN_IDLE_TIMEOUT_MIN: i32 = 10; // 连接空闲超时时间（分钟）
-
 // 全局连接池实例
 static GLOBAL_POOL: OnceLock<Arc<RabbitmqConnPool>> = OnceLock::new();
 // static INITIALIZED: AtomicBool = AtomicBool::new(false);
@@ -45,8 +51,10 @@ static GLOBAL_POOL: OnceLock<Arc<RabbitmqConnPool>> = OnceLock::new();
 /// rabbitmq连接池
 #[derive(Debug)]
 pub struct RabbitmqConnPool {
-    /// 连接地址（使用内部可变性）
-    conn_str: Arc<Mutex<String>>,
+    /// 连接池配置，初始化时从AppConfig.rabbitmq加载
+    settings: OnceLock<PoolSettings>,
+    /// 下一次连接的节点序号，多节点时轮询
+    next_address: AtomicUsize,
     /// 发布连接
     pub_conns: RwLock<HashMap<String, Arc<RabbitMqConnData>>>,
     /// 发布channel pool
@@ -65,13 +73,63 @@ pub struct RabbitmqConnPool {
     rec_mu: Arc<Mutex<()>>,
     /// 初始化标志
     initialized: AtomicBool,
+    /// 后台任务(发布通道管道填充、空闲连接清理)
+    tasks: std::sync::Mutex<Vec<JoinHandle<()>>>,
+    /// 关闭信号
+    shutdown_tx: watch::Sender<bool>,
+    /// 运行指标
+    metrics: PoolMetrics,
+}
+
+/// 连接池配置
+#[derive(Debug)]
+struct PoolSettings {
+    /// 节点地址，已应用心跳配置
+    addresses: Vec<AMQPUri>,
+    /// 发布连接数量限制
+    conn_limit: i32,
+    /// 每个连接的channel数量限制
+    ch_limit_for_conn: i32,
+    /// channel空闲超时时间 分钟
+    ch_idle_timeout_min: i32,
+    /// 连接空闲超时时间 分钟
+    conn_idle_timeout_min: i32,
+    /// 连接名称
+    connection_name: Option<String>,
+    /// 自定义CA证书链(PEM)
+    tls_cert_chain: Option<String>,
+    /// 客户端证书
+    tls_identity: Option<TlsIdentity>,
+}
+
+/// 客户端证书内容
+#[derive(Debug)]
+enum TlsIdentity {
+    Pkcs12 { der: Vec<u8>, password: String },
+    Pkcs8 { pem: Vec<u8>, key: Vec<u8> },
+}
+
+/// 连接池运行指标
+#[derive(Debug, Default)]
+struct PoolMetrics {
+    /// 获取发布通道次数
+    pipeline_waits: AtomicU64,
+    /// 获取发布通道累计等待时间 微秒
+    pipeline_wait_total_micros: AtomicU64,
+    /// 获取发布通道最大等待时间 微秒
+    pipeline_wait_max_micros: AtomicU64,
+    /// 发布消息数
+    publishes: AtomicU64,
+    /// 发布失败消息数
+    publish_failures: AtomicU64,
 }
 
 impl RabbitmqConnPool {
     pub fn new() -> Self {
         let (tx, rx) = mpsc::channel(1);
         Self {
-            conn_str: Arc::new(Mutex::new(String::new())),
+            settings: OnceLock::new(),
+            next_address: AtomicUsize::new(0),
             pub_conns: RwLock::new(HashMap::new()),
             pub_chs: Mutex::new(Vec::new()),
             pub_lock: Arc::new(AtomicI32::new(0)),
@@ -81,6 +139,9 @@ impl RabbitmqConnPool {
             rec_chs: Mutex::new(Vec::new()),
             rec_mu: Arc::new(Mutex::new(())),
             initialized: AtomicBool::new(false),
+            tasks: std::sync::Mutex::new(Vec::new()),
+            shutdown_tx: watch::channel(false).0,
+            metrics: PoolMetrics::default(),
         }
     }
 
@@ -94,24 +155,34 @@ impl RabbitmqConnPool {
     }
 
     /// 初始化连接池
-    pub async fn init() {
+    /// 配置无效时返回错误，连接池保持未初始化状态
+    pub async fn init() -> anyhow::Result<()> {
         let pool = RabbitmqConnPool::get_instance();
         if pool.initialized.swap(true, Ordering::Relaxed) {
-            return; // 已经初始化过了
+            return Ok(()); // 已经初始化过了
         }
 
         let rudi_context = app::appcontext::rudi_context::instance();
         let ctx = rudi_context.read().await;
         let app_config = ctx.get_ctx().get_single::<app_config::AppConfig>();
-        // 修改 conn_str 字段
-        if let Some(ref rabbitmq_config) = app_config.rabbitmq {
-            let mut conn_str_guard = pool.conn_str.lock().await;
-            *conn_str_guard = rabbitmq_config.address.clone();
-        }
+        let settings = match app_config.rabbitmq {
+            Some(ref rabbitmq_config) => match PoolSettings::from_config(rabbitmq_config) {
+                Ok(settings) => {
+                    codec::set_max_decompressed_size(rabbitmq_config.max_decompressed_size);
+                    settings
+                }
+                Err(err) => {
+                    pool.initialized.store(false, Ordering::Relaxed);
+                    return Err(err.context("invalid rabbitmq config"));
+                }
+            },
+            None => PoolSettings::default(),
+        };
+        let _ = pool.settings.set(settings);
 
         // 启动发布通道管道填充协程
         let pool_clone = pool.clone();
-        tokio::spawn(async move {
+        let fill_task = tokio::spawn(async move {
             loop {
                 pool_clone.push_pub_ch_to_pipe().await;
             }
@@ -119,21 +190,68 @@ impl RabbitmqConnPool {
 
         // 启动定时清理任务
         let pool_clone = pool.clone();
-        tokio::spawn(async move {
+        let clear_task = tokio::spawn(async move {
             loop {
                 tokio::time::sleep(Duration::from_secs(60)).await; // 每分钟执行一次
                 pool_clone.clear_idl_pub_conn().await;
             }
         });
 
+        if let Ok(mut tasks) = pool.tasks.lock() {
+            tasks.push(fill_task);
+            tasks.push(clear_task);
+        }
+
         tracing::info!("RabbitMQ connection pool initialized");
+
+        // 声明注册的拓扑，需在消费者绑定前完成
+        pool.declare_topology().await;
+
+        // 通知订阅者MQ已就绪（绑定消费者等）
+        app::appcontext::publisher::publish_event(AppEventMqReady);
+        Ok(())
+    }
+
+    // 声明注册的拓扑
+    async fn declare_topology(&self) {
+        let ch = match self.get_pub_channel().await {
+            Ok(ch) => ch,
+            Err(err) => {
+                tracing::error!("声明MQ拓扑失败，无可用通道: {:?}", err);
+                return;
+            }
+        };
+
+        match topology::declare_all(&ch.channel).await {
+            Ok(()) => self.release_channel(ch),
+            Err(err) => {
+                tracing::error!("声明MQ拓扑失败: {:?}", err);
+                ch.set_status(ChannelStatus::Close);
+            }
+        }
     }
 
     // 获取发布通道
     pub async fn get_pub_channel(&self) -> Result<Arc<MqChannel>, Box<dyn std::error::Error>> {
-        // 从管道中获取通道
-        let mut rx = self.pub_ch_pipeline_rx.lock().await;
-        match rx.recv().await {
+        if self.is_shutdown() {
+            return Err("连接池已关闭".into());
+        }
+
+        // 从管道中获取通道，连接池关闭时不再等待
+        let start = Instant::now();
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let ch = tokio::select! {
+            ch = async {
+                let mut rx = self.pub_ch_pipeline_rx.lock().await;
+                rx.recv().await
+            } => ch,
+            _ = async { let _ = shutdown_rx.wait_for(|shutdown| *shutdown).await; } => {
+                return Err("连接池已关闭".into());
+            }
+        };
+        self.metrics.record_wait(start.elapsed());
+
+        match ch {
             Some(ch) => match ch {
                 Some(ch) => Ok(ch),
                 None => Err("连接池已满".into()),
@@ -142,13 +260,156 @@ impl RabbitmqConnPool {
         }
     }
 
+    /// 记录发布结果
+    pub fn record_publish(&self, total: usize, failed: usize) {
+        self.metrics
+            .publishes
+            .fetch_add(total as u64, Ordering::Relaxed);
+        self.metrics
+            .publish_failures
+            .fetch_add(failed as u64, Ordering::Relaxed);
+    }
+
+    /// 是否已关闭
+    pub fn is_shutdown(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    /// 获取运行状态快照
+    pub async fn stats(&self) -> PoolStats {
+        let mut pub_channels = ChannelStats::default();
+        for ch in self.pub_chs.lock().await.iter() {
+            pub_channels.add(ch.get_status());
+        }
+        let mut rec_channels = ChannelStats::default();
+        for ch in self.rec_chs.lock().await.iter() {
+            rec_channels.add(ch.get_status());
+        }
+
+        let pipeline_waits = self.metrics.pipeline_waits.load(Ordering::Relaxed);
+        let wait_total_micros = self.metrics.pipeline_wait_total_micros.load(Ordering::Relaxed);
+        PoolStats {
+            pub_connections: self.pub_conns.read().await.len(),
+            rec_connections: self.rec_conns.lock().await.len(),
+            pub_channels,
+            rec_channels,
+            pipeline_waits,
+            pipeline_wait_avg_ms: if pipeline_waits > 0 {
+                wait_total_micros as f64 / pipeline_waits as f64 / 1000.0
+            } else {
+                0.0
+            },
+            pipeline_wait_max_ms: self.metrics.pipeline_wait_max_micros.load(Ordering::Relaxed)
+                as f64
+                / 1000.0,
+            publishes: self.metrics.publishes.load(Ordering::Relaxed),
+            publish_failures: self.metrics.publish_failures.load(Ordering::Relaxed),
+            shutdown: self.is_shutdown(),
+        }
+    }
+
+    /// 关闭连接池
+    /// 先停止消费并在timeout内等待处理中的消息完成，再停止后台任务并关闭所有通道与连接
+    pub async fn shutdown(&self, timeout: Duration) {
+        if self.shutdown_tx.send_replace(true) {
+            return;
+        }
+        tracing::info!("RabbitMQ connection pool shutting down");
+
+        ConsumerBinder::get_instance().shutdown(timeout).await;
+
+        if let Ok(mut tasks) = self.tasks.lock() {
+            for task in tasks.drain(..) {
+                task.abort();
+            }
+        }
+
+        let rec_chs = std::mem::take(&mut *self.rec_chs.lock().await);
+        let pub_chs = std::mem::take(&mut *self.pub_chs.lock().await);
+        for ch in rec_chs.iter().chain(pub_chs.iter()) {
+            ch.set_status(ChannelStatus::Close);
+            if ch.channel.status().connected() {
+                let _ = ch.channel.close(200, "shutdown").await;
+            }
+        }
+
+        let rec_conns = std::mem::take(&mut *self.rec_conns.lock().await);
+        let pub_conns = std::mem::take(&mut *self.pub_conns.write().await);
+        for conn in rec_conns.iter().chain(pub_conns.values()) {
+            if conn.conn.status().connected() {
+                let _ = conn.conn.close(200, "shutdown").await;
+            }
+        }
+
+        tracing::info!("RabbitMQ connection pool closed");
+    }
+
+    // 监听连接事件，连接出错时移除该连接及其通道
+    fn watch_connection(conn: &Connection, guid: String) {
+        let mut event_listener = conn.events_listener();
+        tokio::spawn(async move {
+            while let Some(event) = event_listener.next().await {
+                match event {
+                    lapin::Event::Error(err) => {
+                        tracing::error!("RabbitMQ connection error: {} - {:?}", guid, err);
+                        RabbitmqConnPool::get_instance().evict_conn(&guid).await;
+                        break;
+                    }
+                    lapin::Event::ConnectionBlocked(reason) => {
+                        tracing::warn!("RabbitMQ connection blocked: {} - {}", guid, reason);
+                    }
+                    lapin::Event::ConnectionUnblocked => {
+                        tracing::info!("RabbitMQ connection unblocked: {}", guid);
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    // 移除失效的连接及其通道
+    // 消费通道被移除后，对应的消费循环随消费流终止而重新绑定
+    async fn evict_conn(&self, guid: &str) {
+        let evicted_pub = self.pub_conns.write().await.remove(guid).is_some();
+        if evicted_pub {
+            self.pub_chs.lock().await.retain(|ch| {
+                if ch.conn.guid == guid {
+                    ch.set_status(ChannelStatus::Close);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        let mut rec_conns = self.rec_conns.lock().await;
+        let len = rec_conns.len();
+        rec_conns.retain(|conn| conn.guid != guid);
+        let evicted_rec = rec_conns.len() != len;
+        drop(rec_conns);
+        if evicted_rec {
+            self.rec_chs.lock().await.retain(|ch| {
+                if ch.conn.guid == guid {
+                    ch.set_status(ChannelStatus::Close);
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        if evicted_pub || evicted_rec {
+            tracing::warn!("evicted dead RabbitMQ connection: {}", guid);
+        }
+    }
+
     // 获取消费通道
     pub async fn get_rec_channel(&self) -> Result<Arc<MqChannel>, Box<dyn std::error::Error>> {
         // 尝试从现有通道中获取空闲通道
         let mut rec_chs = self.rec_chs.lock().await;
         for i in 0..rec_chs.len() {
             let ch = &rec_chs[i];
-            if ch.get_status() == ChannelStatus::Idle {
+            if ch.get_status() == ChannelStatus::Idle && ch.channel.status().connected() {
                 // 标记为使用中
                 ch.set_status(ChannelStatus::Busy);
                 ch.update_last_use_mills();
@@ -168,13 +429,15 @@ impl RabbitmqConnPool {
 
     // 获取或创建发布连接
     async fn get_or_create_pub_conn(&self) -> anyhow::Result<Arc<RabbitMqConnData>> {
+        let settings = self.settings();
+        let ch_limit = settings.ch_limit_for_conn;
         let pub_conns = self.pub_conns.read().await;
 
         // 过滤出有效的连接（通道数小于限制且连接未关闭）
         let mut valid_conns = pub_conns
             .iter()
             .filter(|(_, conn)| {
-                conn.live_ch.load(Ordering::Relaxed) < CH_LIMIT_FOR_CONN
+                conn.live_ch.load(Ordering::Relaxed) < ch_limit
                     && conn.conn.status().connected()
             })
             .map(|(_, conn)| conn.clone())
@@ -190,63 +453,105 @@ impl RabbitmqConnPool {
         }
 
         // 检查连接池是否已满
-        if pub_conns.len() as i32 >= CONN_LIMIT {
+        if pub_conns.len() as i32 >= settings.conn_limit {
             anyhow::bail!("连接池已满");
         }
         drop(pub_conns);
 
         // 创建新连接
-        let conn_str = {
-            let conn_str_guard = self.conn_str.lock().await;
-            conn_str_guard.clone()
-        };
-        let conn = Connection::connect(&conn_str, ConnectionProperties::default()).await?;
-        let mut evnet_listener = conn.events_listener();
-        tokio::spawn(async move {
-            while let Some(event) = evnet_listener.next().await {
-                // TODO 监听连接事件
-                match event {
-                    // lapin::Event::Connected => {
-                    //     tracing::info!("RabbitMQ connection connected");
-                    // }
-                    lapin::Event::Error(err) => {
-                        tracing::error!("RabbitMQ connection error: {:?}", err);
-                    }
-                    _ => {}
-                }
-            }
-        });
-
+        let conn = self.connect().await?;
         let conn_data = Arc::new(RabbitMqConnData::new(Arc::new(conn)));
+        Self::watch_connection(&conn_data.conn, conn_data.guid.clone());
 
         let mut pub_conns = self.pub_conns.write().await;
         pub_conns.insert(conn_data.guid.clone(), conn_data.clone());
         Ok(conn_data)
     }
 
+    // 获取连接池配置，未初始化时使用默认配置
+    fn settings(&self) -> &PoolSettings {
+        self.settings.get_or_init(PoolSettings::default)
+    }
+
+    // 建立新连接
+    // 从上次连接的下一个节点开始轮询，节点不可用时依次尝试其余节点
+    async fn connect(&self) -> anyhow::Result<Connection> {
+        let settings = self.settings();
+        let count = settings.addresses.len();
+        if count == 0 {
+            anyhow::bail!("未配置RabbitMQ地址");
+        }
+
+        let start = self.next_address.fetch_add(1, Ordering::Relaxed);
+        let mut last_err = None;
+        for i in 0..count {
+            let uri = &settings.addresses[(start + i) % count];
+            match Connection::connect_uri_with_config(
+                uri.clone(),
+                settings.connection_properties(),
+                settings.tls_config(),
+            )
+            .await
+            {
+                Ok(conn) => return Ok(conn),
+                Err(err) => {
+                    tracing::warn!(
+                        "RabbitMQ节点连接失败: {}:{} - {:?}",
+                        uri.authority.host,
+                        uri.authority.port,
+                        err
+                    );
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        match last_err {
+            Some(err) => Err(err.into()),
+            None => anyhow::bail!("未配置RabbitMQ地址"),
+        }
+    }
+
     // 获取或创建消费连接
     async fn get_or_create_rec_conn(
         &self,
     ) -> Result<Arc<RabbitMqConnData>, Box<dyn std::error::Error>> {
         let mut rec_conns = self.rec_conns.lock().await;
+        // 移除已断开的连接
+        rec_conns.retain(|conn| conn.conn.status().connected());
+        let ch_limit = self.settings().ch_limit_for_conn;
         for conn in rec_conns.iter() {
-            if conn.live_ch.load(Ordering::Relaxed) < CH_LIMIT_FOR_CONN {
+            if conn.live_ch.load(Ordering::Relaxed) < ch_limit {
                 return Ok(conn.clone());
             }
         }
 
         // 创建新连接
-        let conn_str = {
-            let conn_str_guard = self.conn_str.lock().await;
-            conn_str_guard.clone()
-        };
-        let conn = Connection::connect(&conn_str, ConnectionProperties::default()).await?;
+        let conn = self.connect().await?;
         let conn_data = Arc::new(RabbitMqConnData::new(Arc::new(conn)));
+        Self::watch_connection(&conn_data.conn, conn_data.guid.clone());
 
         rec_conns.push(conn_data.clone());
         Ok(conn_data)
     }
 
+    // 丢弃失效的消费通道
+    pub async fn discard_rec_channel(&self, ch: &Arc<MqChannel>) {
+        let mut rec_chs = self.rec_chs.lock().await;
+        let len = rec_chs.len();
+        rec_chs.retain(|c| !Arc::ptr_eq(c, ch));
+        if rec_chs.len() == len {
+            return;
+        }
+        drop(rec_chs);
+
+        ch.set_status(ChannelStatus::Close);
+        ch.conn.dec_chan();
+        if ch.channel.status().connected() {
+            let _ = ch.channel.close(0, "关闭失效通道").await;
+        }
+    }
+
     // 释放通道
     pub fn release_channel(&self, ch: Arc<MqChannel>) {
         ch.set_status(ChannelStatus::Idle);
@@ -267,19 +572,19 @@ impl RabbitmqConnPool {
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
-        // 查找空闲通道
-        let mut idle_ch: Option<Arc<MqChannel>> = None;
-        let mut pub_chs = self.pub_chs.lock().await;
-        for ch in pub_chs.iter() {
-            let status = ch.get_status();
-            if status != ChannelStatus::Busy
-                && status != ChannelStatus::Close
-                && ch.conn.conn.status().connected()
-            {
-                idle_ch = Some(ch.clone());
-                break;
-            }
-        }
+        // 查找空闲通道，推送前释放通道池锁，避免管道阻塞时stats、evict_conn等待
+        let idle_ch = self
+            .pub_chs
+            .lock()
+            .await
+            .iter()
+            .find(|ch| {
+                let status = ch.get_status();
+                status != ChannelStatus::Busy
+                    && status != ChannelStatus::Close
+                    && ch.conn.conn.status().connected()
+            })
+            .cloned();
 
         if let Some(ch) = idle_ch {
             let prev_status = ch.get_status();
@@ -321,9 +626,21 @@ impl RabbitmqConnPool {
         }
 
         let channel = channel_result.unwrap();
+
+        // 发布通道开启confirm模式，发布时等待broker确认
+        if let Err(err) = channel
+            .confirm_select(lapin::options::ConfirmSelectOptions::default())
+            .await
+        {
+            tracing::error!("开启发布确认模式失败: {:?}", err);
+            let _ = channel.close(0, "开启发布确认模式失败").await;
+            self.pub_lock.store(0, Ordering::Relaxed);
+            return;
+        }
+
         let mq_channel = Arc::new(MqChannel::new(conn.clone(), channel));
 
-        pub_chs.push(mq_channel.clone());
+        self.pub_chs.lock().await.push(mq_channel.clone());
         conn.inc_chan();
 
         // 释放锁
@@ -361,6 +678,8 @@ impl RabbitmqConnPool {
         }
 
         // let now = Instant::now();
+        let ch_idle_timeout_min = self.settings().ch_idle_timeout_min;
+        let conn_idle_timeout_min = self.settings().conn_idle_timeout_min;
         let mut pub_chs = self.pub_chs.lock().await;
         let mut pub_conns = self.pub_conns.write().await;
 
@@ -393,7 +712,7 @@ impl RabbitmqConnPool {
                 let ch_last_use = ch.last_use_mills.load(Ordering::Relaxed);
                 let now_mills = Utc::now().timestamp_millis();
                 let idle_millis = now_mills - ch_last_use;
-                if idle_millis >= (CH_IDLE_TIMEOUT_MIN * 60 * 1000) as i64 {
+                if idle_millis >= (ch_idle_timeout_min * 60 * 1000) as i64 {
                     do_clear = true;
                     ch.set_status(ChannelStatus::Timeout);
                 }
@@ -409,7 +728,7 @@ impl RabbitmqConnPool {
                     let conn_last_use = conn.last_use_mills.load(Ordering::Relaxed);
                     let now_mills = Utc::now().timestamp_millis();
                     let conn_idle_millis = now_mills - conn_last_use;
-                    if conn_idle_millis >= (CONN_IDLE_TIMEOUT_MIN * 60 * 1000) as i64 {
+                    if conn_idle_millis >= (conn_idle_timeout_min * 60 * 1000) as i64 {
                         let ch_clone = ch.clone();
 
                         // 从通道池中删除通道
@@ -442,3 +761,103 @@ impl RabbitmqConnPool {
         }
     }
 }
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            addresses: Vec::new(),
+            conn_limit: 100,
+            ch_limit_for_conn: 100,
+            ch_idle_timeout_min: 5,
+            conn_idle_timeout_min: 10,
+            connection_name: None,
+            tls_cert_chain: None,
+            tls_identity: None,
+        }
+    }
+}
+
+impl PoolSettings {
+    // 从配置加载，解析地址并读取证书文件
+    fn from_config(config: &app_config::RabbitMQ) -> anyhow::Result<Self> {
+        let mut addresses = Vec::new();
+        for address in config.addresses() {
+            let mut uri: AMQPUri = address
+                .parse()
+                .map_err(|err| anyhow::anyhow!("invalid rabbitmq address: {}", err))?;
+            if let Some(heartbeat) = config.heartbeat {
+                uri.query.heartbeat = Some(heartbeat);
+            }
+            addresses.push(uri);
+        }
+
+        let mut tls_cert_chain = None;
+        let mut tls_identity = None;
+        if let Some(ref tls) = config.tls {
+            if let Some(ref ca_cert) = tls.ca_cert {
+                tls_cert_chain = Some(std::fs::read_to_string(ca_cert)?);
+            }
+            tls_identity = match (&tls.pkcs12, &tls.client_cert, &tls.client_key) {
+                (Some(pkcs12), _, _) => Some(TlsIdentity::Pkcs12 {
+                    der: std::fs::read(pkcs12)?,
+                    password: tls.pkcs12_password.clone().unwrap_or_default(),
+                }),
+                (None, Some(cert), Some(key)) => Some(TlsIdentity::Pkcs8 {
+                    pem: std::fs::read(cert)?,
+                    key: std::fs::read(key)?,
+                }),
+                (None, None, None) => None,
+                _ => anyhow::bail!("client_cert and client_key must be set together"),
+            };
+        }
+
+        Ok(Self {
+            addresses,
+            conn_limit: config.conn_limit,
+            ch_limit_for_conn: config.ch_limit_for_conn,
+            ch_idle_timeout_min: config.ch_idle_timeout_min,
+            conn_idle_timeout_min: config.conn_idle_timeout_min,
+            connection_name: config.connection_name.clone(),
+            tls_cert_chain,
+            tls_identity,
+        })
+    }
+
+    // 连接属性
+    fn connection_properties(&self) -> ConnectionProperties {
+        let properties = ConnectionProperties::default();
+        match self.connection_name {
+            Some(ref name) => properties.with_connection_name(name.clone().into()),
+            None => properties,
+        }
+    }
+
+    // TLS配置，仅在amqps地址下生效
+    fn tls_config(&self) -> OwnedTLSConfig {
+        OwnedTLSConfig {
+            identity: self.tls_identity.as_ref().map(|identity| match identity {
+                TlsIdentity::Pkcs12 { der, password } => OwnedIdentity::PKCS12 {
+                    der: der.clone(),
+                    password: password.clone(),
+                },
+                TlsIdentity::Pkcs8 { pem, key } => OwnedIdentity::PKCS8 {
+                    pem: pem.clone(),
+                    key: key.clone(),
+                },
+            }),
+            cert_chain: self.tls_cert_chain.clone(),
+        }
+    }
+}
+
+impl PoolMetrics {
+    // 记录获取发布通道的等待时间
+    fn record_wait(&self, elapsed: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.pipeline_waits.fetch_add(1, Ordering::Relaxed);
+        self.pipeline_wait_total_micros
+            .fetch_add(micros, Ordering::Relaxed);
+        self.pipeline_wait_max_micros
+            .fetch_max(micros, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::common::mqutils::consumer::Consumer;
+use inventory;
+use std::sync::Arc;
+
+/// 消费者注册项，用于在静态上下文中存储消费者工厂
+pub struct ConsumerRegistration {
+    pub consumer: fn() -> Arc<Consumer>,
+}
+
+// 为ConsumerRegistration实现inventory的Collect trait
+inventory::collect!(ConsumerRegistration);
+
+/// 分区消费者注册项，每个分区对应一个消费者
+pub struct PartitionedConsumerRegistration {
+    pub consumers: fn() -> Vec<Arc<Consumer>>,
+}
+
+inventory::collect!(PartitionedConsumerRegistration);
+
+/// 从inventory中收集所有声明式注册的消费者
+/// 消费者构造函数会自行将消费者放入消费者容器
+pub fn collect_consumers() -> Vec<Arc<Consumer>> {
+    let mut consumers: Vec<Arc<Consumer>> = inventory::iter::<ConsumerRegistration>()
+        .map(|registration| (registration.consumer)())
+        .collect();
+    for registration in inventory::iter::<PartitionedConsumerRegistration>() {
+        consumers.extend((registration.consumers)());
+    }
+    consumers
+}
@@ -1,21 +1,248 @@
+use crate::common::mqutils::codec::Envelope;
 use crate::common::mqutils::consts::ConsumerType;
-use crate::common::mqutils::models::MqMessage;
+use crate::common::mqutils::consumer::Consumer;
+use crate::common::mqutils::context::MessageContext;
+use crate::common::mqutils::publisher;
+use crate::common::mqutils::transport::Delivery;
+use chrono::Utc;
+use lapin::types::{AMQPValue, FieldTable};
 use tracing;
 
+/// 死信队列后缀
+pub const DLQ_SUFFIX: &str = ".dlq";
+/// 死信消息头: 原始队列
+pub const HEADER_ORIGIN_QUEUE: &str = "x-origin-queue";
+/// 死信消息头: 进入死信的原因
+pub const HEADER_DEAD_REASON: &str = "x-dead-reason";
+
+/// 非持久化延迟队列在无消息后的额外存活时间 毫秒
+const DELAY_QUEUE_EXPIRE_EXTRA_MILLS: u64 = 60 * 1000;
+
+/// 重试退避策略
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code, reason = "Fixed与Schedule由业务代码按需选用")]
+pub enum RetryBackoff {
+    /// 固定间隔 毫秒
+    Fixed(u64),
+    /// 指数退避: 初始间隔 * multiplier^(重试次数-1)，不超过max_ms
+    Exponential {
+        initial_ms: u64,
+        multiplier: u32,
+        max_ms: u64,
+    },
+    /// 自定义间隔表 毫秒，重试次数超出表长度时使用最后一个间隔
+    Schedule(Vec<u64>),
+}
+
+impl Default for RetryBackoff {
+    fn default() -> Self {
+        RetryBackoff::Exponential {
+            initial_ms: 1000,
+            multiplier: 2,
+            max_ms: 5 * 60 * 1000,
+        }
+    }
+}
+
+impl RetryBackoff {
+    /// 获取第retry次重试(从1开始)的延迟 毫秒
+    pub fn delay_ms(&self, retry: u32) -> u64 {
+        let retry = retry.max(1);
+        let delay = match self {
+            RetryBackoff::Fixed(ms) => *ms,
+            RetryBackoff::Exponential {
+                initial_ms,
+                multiplier,
+                max_ms,
+            } => {
+                let factor = (*multiplier as u64).saturating_pow(retry - 1);
+                initial_ms.saturating_mul(factor).min(*max_ms)
+            }
+            RetryBackoff::Schedule(delays) => {
+                let idx = (retry as usize - 1).min(delays.len().saturating_sub(1));
+                delays.get(idx).copied().unwrap_or(1000)
+            }
+        };
+
+        // 延迟为0时消息会立即回到原队列，退化为热循环
+        delay.max(1)
+    }
+}
+
+/// 获取延迟队列名称
+pub fn delay_queue_name(origin_queue: &str, delay_ms: u64) -> String {
+    format!("{}.retry.{}", origin_queue, delay_ms)
+}
+
 // 重试消息
-pub fn retry(meta_msg: &MqMessage, consumer: &crate::common::mqutils::consumer::Consumer) -> bool {
+// 返回true表示消息已转入延迟队列或死信队列，调用方应ack原消息
+// 返回false表示转发失败，调用方应将消息重新入队
+pub async fn retry(
+    mut meta_msg: Envelope,
+    consumer: &Consumer,
+    origin_queue: &str,
+    ctx: &MessageContext,
+) -> bool {
     if meta_msg.current_retry >= consumer.max_retry as i32 {
-        tracing::error!("消息重试次数超过最大值: {:?}", meta_msg);
-        return true;
+        tracing::error!(
+            "消息重试次数超过最大值，转入死信队列: {} - {}",
+            meta_msg.guid,
+            origin_queue
+        );
+        return dead_letter(&meta_msg, consumer, origin_queue, "max retry exceeded", ctx).await;
+    }
+
+    meta_msg.current_retry += 1;
+    let delay_ms = consumer
+        .get_retry_backoff()
+        .delay_ms(meta_msg.current_retry as u32);
+    let delay_queue = delay_queue_name(origin_queue, delay_ms);
+
+    // 延迟队列: 消息过期后通过默认交换器投递回原队列
+    let mut arguments = FieldTable::default();
+    arguments.insert(
+        "x-message-ttl".into(),
+        AMQPValue::LongLongInt(delay_ms as i64),
+    );
+    arguments.insert(
+        "x-dead-letter-exchange".into(),
+        AMQPValue::LongString("".into()),
+    );
+    arguments.insert(
+        "x-dead-letter-routing-key".into(),
+        AMQPValue::LongString(origin_queue.into()),
+    );
+
+    // 广播队列为实例独占的临时队列，延迟队列随之不持久化并在闲置后自动删除
+    let durable = !matches!(consumer.r#type, ConsumerType::Broadcast);
+    if !durable {
+        arguments.insert(
+            "x-expires".into(),
+            AMQPValue::LongLongInt((delay_ms + DELAY_QUEUE_EXPIRE_EXTRA_MILLS) as i64),
+        );
+    }
+
+    let payload = match meta_msg.to_bytes() {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::error!("重试消息序列化失败: {:?}", err);
+            return false;
+        }
+    };
+
+    if let Err(err) = publisher::pub_queue_msg_internal(
+        &delay_queue,
+        durable,
+        arguments,
+        FieldTable::default(),
+        &payload,
+        &ctx.forward_options(),
+    )
+    .await
+    {
+        tracing::error!("发布重试消息失败: {} - {:?}", delay_queue, err);
+        return false;
+    }
+
+    tracing::warn!(
+        "消息处理失败，{}毫秒后第{}次重试: {} - {}",
+        delay_ms,
+        meta_msg.current_retry,
+        meta_msg.guid,
+        origin_queue
+    );
+    true
+}
+
+// 转入死信队列
+pub async fn dead_letter(
+    meta_msg: &Envelope,
+    consumer: &Consumer,
+    origin_queue: &str,
+    reason: &str,
+    ctx: &MessageContext,
+) -> bool {
+    let dlq = consumer.dead_letter_queue();
+
+    let mut headers = FieldTable::default();
+    headers.insert(
+        HEADER_ORIGIN_QUEUE.into(),
+        AMQPValue::LongString(origin_queue.into()),
+    );
+    headers.insert(
+        HEADER_DEAD_REASON.into(),
+        AMQPValue::LongString(reason.into()),
+    );
+
+    let payload = match meta_msg.to_bytes() {
+        Ok(payload) => payload,
+        Err(err) => {
+            tracing::error!("死信消息序列化失败: {:?}", err);
+            return false;
+        }
+    };
+
+    // 死信原因等消息头以本次为准，不沿用原消息中的同名消息头
+    let mut options = ctx.forward_options();
+    options.headers.remove(HEADER_ORIGIN_QUEUE);
+    options.headers.remove(HEADER_DEAD_REASON);
+
+    if let Err(err) = publisher::pub_queue_msg_internal(
+        &dlq,
+        true,
+        FieldTable::default(),
+        headers,
+        &payload,
+        &options,
+    )
+    .await
+    {
+        tracing::error!("发布死信消息失败: {} - {:?}", dlq, err);
+        return false;
+    }
+
+    true
+}
+
+// 无法解码的消息按原样转入死信队列，原消息头与属性保留
+pub async fn dead_letter_undecodable(
+    delivery: &Delivery,
+    consumer: &Consumer,
+    origin_queue: &str,
+    reason: &str,
+) -> bool {
+    let dlq = consumer.dead_letter_queue();
+
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        HEADER_ORIGIN_QUEUE.into(),
+        AMQPValue::LongString(origin_queue.into()),
+    );
+    headers.insert(
+        HEADER_DEAD_REASON.into(),
+        AMQPValue::LongString(reason.into()),
+    );
+    let properties = delivery.properties.clone().with_headers(headers);
+
+    if let Err(err) = publisher::pub_raw_queue_msg_internal(&dlq, &delivery.data, properties).await
+    {
+        tracing::error!("发布死信消息失败: {} - {:?}", dlq, err);
+        return false;
     }
 
-    // 这里可以实现消息重试的逻辑，例如将消息发送到重试队列
-    // 由于我们使用的是RabbitMQ的内置重试机制（通过nack和requeue），这里可以简化处理
-    false
+    true
 }
 
 // 重试成功
-pub fn retry_success(meta_msg: &MqMessage, consumer_type: ConsumerType) {
-    // 这里可以实现重试成功后的逻辑，例如更新重试状态等
-    tracing::info!("消息处理成功: {} - {:?}", meta_msg.guid, consumer_type);
+pub fn retry_success(meta_msg: &Envelope, consumer_type: ConsumerType) {
+    let elapsed_mills = Utc::now()
+        .signed_duration_since(meta_msg.timespan)
+        .num_milliseconds();
+    tracing::info!(
+        "消息重试后处理成功: {} - {:?}, 重试次数: {}, 距首次发布: {}毫秒",
+        meta_msg.guid,
+        consumer_type,
+        meta_msg.current_retry,
+        elapsed_mills
+    );
 }
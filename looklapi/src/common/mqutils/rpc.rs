@@ -0,0 +1,272 @@
+use crate::common::mqutils::codec::{self, Envelope};
+use crate::common::mqutils::context;
+use crate::common::mqutils::handler::ConsumeError;
+use crate::common::mqutils::publisher::{self, PublishError, PublishOptions};
+use crate::common::mqutils::transport::{self, ConsumeSpec, Delivery, QueueSpec, Subscription};
+use futures::StreamExt;
+use lapin::types::AMQPValue;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// 消息头: RPC处理失败原因，存在时表示调用失败
+pub const HEADER_RPC_ERROR: &str = "x-rpc-error";
+
+/// RPC调用错误
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    /// 参数无效
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+    /// 回复队列不可用
+    #[error("reply queue unavailable: {0}")]
+    ReplyQueue(String),
+    /// 请求发布失败
+    #[error("publish request failed: {0}")]
+    Publish(#[from] PublishError),
+    /// 等待回复超时
+    #[error("wait for reply timeout")]
+    Timeout,
+    /// 回复队列已断开，无法收到回复
+    #[error("reply queue disconnected")]
+    Disconnected,
+    /// 服务端处理失败
+    #[error("remote error: {0}")]
+    Remote(String),
+    /// 回复反序列化失败
+    #[error("deserialize reply failed: {0}")]
+    Serialization(String),
+}
+
+type ReplySender = oneshot::Sender<Result<Envelope, RpcError>>;
+
+// 回复队列
+struct ReplyQueue {
+    name: String,
+    closed: AtomicBool,
+}
+
+lazy_static! {
+    // 等待回复的请求，key为correlation_id
+    static ref PENDING: Mutex<HashMap<String, ReplySender>> = Mutex::new(HashMap::new());
+    // 当前进程的回复队列
+    static ref REPLY_QUEUE: tokio::sync::Mutex<Option<Arc<ReplyQueue>>> =
+        tokio::sync::Mutex::new(None);
+}
+
+/// 发起RPC调用并等待回复
+/// 请求发布到route_key对应的工作队列，由Consumer::rpc创建的消费者处理
+#[allow(dead_code, reason = "RPC客户端入口，由业务代码调用")]
+pub async fn call<Req: Serialize, Resp: DeserializeOwned>(
+    route_key: &str,
+    req: Req,
+    timeout: Duration,
+) -> Result<Resp, RpcError> {
+    call_with_options(route_key, req, timeout, PublishOptions::default()).await
+}
+
+/// 发起RPC调用并等待回复(指定发布选项)
+/// 服务端以请求的编码格式回复
+#[allow(dead_code, reason = "RPC客户端入口，由业务代码调用")]
+pub async fn call_with_options<Req: Serialize, Resp: DeserializeOwned>(
+    route_key: &str,
+    req: Req,
+    timeout: Duration,
+    options: PublishOptions,
+) -> Result<Resp, RpcError> {
+    if route_key.is_empty() {
+        return Err(RpcError::InvalidArgument("empty route key".to_string()));
+    }
+
+    let reply_to = reply_queue().await?;
+    let payload = publisher::serialize_message(req, options.codec)?;
+    let correlation_id = uuid::Uuid::new_v4().to_string().replace('-', "");
+
+    let (tx, rx) = oneshot::channel();
+    PENDING.lock().unwrap().insert(correlation_id.clone(), tx);
+    // 返回或调用方放弃等待时移除
+    let _pending = PendingGuard(correlation_id.clone());
+
+    // 请求在超时后过期，避免服务端处理调用方已放弃的请求
+    let options = options
+        .correlation_id(&correlation_id)
+        .reply_to(&reply_to)
+        .expiration(timeout);
+    publisher::pub_work_queue_msg_internal(route_key, &payload, &options).await?;
+
+    let envelope = match tokio::time::timeout(timeout, rx).await {
+        Ok(Ok(result)) => result?,
+        Ok(Err(_)) => return Err(RpcError::Disconnected),
+        Err(_) => return Err(RpcError::Timeout),
+    };
+
+    envelope
+        .decode_content()
+        .map_err(|err| RpcError::Serialization(err.to_string()))
+}
+
+// 等待回复的请求，丢弃时从PENDING中移除
+struct PendingGuard(String);
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        PENDING.lock().unwrap().remove(&self.0);
+    }
+}
+
+// 是否有等待该correlation_id回复的请求
+#[cfg(test)]
+pub(crate) fn is_pending(correlation_id: &str) -> bool {
+    PENDING.lock().unwrap().contains_key(correlation_id)
+}
+
+// 发送RPC回复，reply_to与correlation_id取自当前消息上下文
+// 回复失败时调用方只能等待超时，不再重试请求
+pub(crate) async fn reply<Resp: Serialize>(
+    result: Result<Resp, String>,
+) -> Result<(), ConsumeError> {
+    let ctx = match context::current() {
+        Some(ctx) => ctx,
+        None => return Err(ConsumeError::Discard("no message context".to_string())),
+    };
+    let reply_to = match ctx.reply_to {
+        Some(ref reply_to) => reply_to.clone(),
+        None => {
+            return Err(ConsumeError::Discard(
+                "rpc request without reply_to".to_string(),
+            ));
+        }
+    };
+
+    let mut options = PublishOptions::default().codec(ctx.codec);
+    if let Some(ref correlation_id) = ctx.correlation_id {
+        options = options.correlation_id(correlation_id);
+    }
+
+    let payload = match result {
+        Ok(resp) => match publisher::serialize_message(resp, ctx.codec) {
+            Ok(payload) => payload,
+            Err(err) => {
+                options = options.header(HEADER_RPC_ERROR, &err.to_string());
+                Vec::new()
+            }
+        },
+        Err(err) => {
+            options = options.header(HEADER_RPC_ERROR, &err);
+            Vec::new()
+        }
+    };
+
+    if let Err(err) = publisher::pub_reply_msg_internal(&reply_to, &payload, &options).await {
+        tracing::error!("发送RPC回复失败: {} - {:?}", reply_to, err);
+    }
+    Ok(())
+}
+
+// 获取回复队列名称，回复队列断开后重新创建
+async fn reply_queue() -> Result<String, RpcError> {
+    let mut guard = REPLY_QUEUE.lock().await;
+    if let Some(queue) = guard.as_ref()
+        && !queue.closed.load(Ordering::Relaxed)
+    {
+        return Ok(queue.name.clone());
+    }
+
+    let queue = bind_reply_queue().await?;
+    let name = queue.name.clone();
+    *guard = Some(queue);
+    Ok(name)
+}
+
+// 声明独占的回复队列并启动回复分发循环
+async fn bind_reply_queue() -> Result<Arc<ReplyQueue>, RpcError> {
+    let spec = ConsumeSpec {
+        queue: QueueSpec {
+            name: String::new(),
+            durable: false,
+            auto_delete: true,
+            exclusive: true,
+            ..Default::default()
+        },
+        declare_queue: true,
+        consumer_tag: "rpc_reply".to_string(),
+        no_ack: true,
+        ..Default::default()
+    };
+    let Subscription {
+        queue: name,
+        mut deliveries,
+        control,
+    } = transport::current()
+        .subscribe(spec)
+        .await
+        .map_err(|err| RpcError::ReplyQueue(err.to_string()))?;
+
+    let queue = Arc::new(ReplyQueue {
+        name,
+        closed: AtomicBool::new(false),
+    });
+
+    let reply_queue = queue.clone();
+    tokio::spawn(async move {
+        while let Some(delivery) = deliveries.next().await {
+            match delivery {
+                Ok(delivery) => dispatch_reply(delivery),
+                Err(err) => {
+                    tracing::error!("RPC回复队列消费失败: {:?}", err);
+                    break;
+                }
+            }
+        }
+
+        // 回复队列随通道删除，等待中的请求无法再收到回复
+        tracing::warn!("RPC回复队列已断开: {}", reply_queue.name);
+        reply_queue.closed.store(true, Ordering::Relaxed);
+        control.close().await;
+        PENDING.lock().unwrap().clear();
+    });
+
+    Ok(queue)
+}
+
+// 按correlation_id将回复交给等待中的请求
+fn dispatch_reply(delivery: Delivery) {
+    let correlation_id = match delivery.properties.correlation_id() {
+        Some(correlation_id) => correlation_id.to_string(),
+        None => {
+            tracing::warn!("RPC回复缺少correlation_id");
+            return;
+        }
+    };
+
+    let sender = match PENDING.lock().unwrap().remove(&correlation_id) {
+        Some(sender) => sender,
+        None => {
+            // 请求已超时
+            tracing::warn!("RPC回复无对应请求: {}", correlation_id);
+            return;
+        }
+    };
+
+    let remote_error = delivery.properties.headers().as_ref().and_then(|headers| {
+        headers
+            .inner()
+            .get(HEADER_RPC_ERROR)
+            .map(|value| match value {
+                AMQPValue::LongString(v) => v.to_string(),
+                other => format!("{:?}", other),
+            })
+    });
+
+    let result = match remote_error {
+        Some(err) => Err(RpcError::Remote(err)),
+        None => codec::decode_delivery(&delivery)
+            .map_err(|err| RpcError::Serialization(err.to_string())),
+    };
+    let _ = sender.send(result);
+}
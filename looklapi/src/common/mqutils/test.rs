@@ -0,0 +1,859 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use futures::StreamExt;
+use lapin::types::FieldTable;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::time::timeout;
+
+use crate::common::mqutils::admin::{self, AdminError};
+use crate::common::mqutils::codec::{self, Codec, CodecError, Envelope};
+use crate::common::mqutils::consumer::{Consumer, ConsumerBinder};
+use crate::common::mqutils::dedupe::{DedupeOptions, MemoryDedupeStore};
+use crate::common::mqutils::delayed::{DelayedScheduler, MemoryDelayedStore};
+use crate::common::mqutils::handler::ConsumeError;
+use crate::common::mqutils::partition;
+use crate::common::mqutils::publisher;
+use crate::common::mqutils::retry::{self, RetryBackoff};
+use crate::common::mqutils::rpc;
+use crate::common::mqutils::transport::memory::{MemoryBroker, topic_matches};
+use crate::common::mqutils::transport::{ConsumeSpec, Declare, Publishing, QueueSpec, Transport};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Order {
+    id: u32,
+    name: String,
+}
+
+fn order(id: u32) -> Order {
+    Order {
+        id,
+        name: format!("order-{}", id),
+    }
+}
+
+/// 生成唯一名称，避免测试间共享内存broker时相互影响
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+/// 等待接收消息
+async fn recv<T>(rx: &mut mpsc::UnboundedReceiver<T>) -> T {
+    timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("wait for message timeout")
+        .expect("channel closed")
+}
+
+/// 测试gzip解压大小限制
+#[test]
+fn test_decompress_limit() {
+    let data = vec![b'a'; 4096];
+    let (compressed, content_encoding) = codec::compress(data.clone(), Some(0)).unwrap();
+    assert_eq!(
+        codec::decompress_with_limit(&compressed, content_encoding, 4096).unwrap(),
+        data
+    );
+    assert!(matches!(
+        codec::decompress_with_limit(&compressed, content_encoding, 4095),
+        Err(CodecError::TooLarge(4095))
+    ));
+}
+
+/// 测试同步与异步入口处理消息
+#[test]
+fn test_on_received() {
+    let route_key = unique("received");
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 1, |msg: Order| async move {
+        if msg.id == 0 {
+            return Err(ConsumeError::Retry("invalid order".to_string()));
+        }
+        Ok(())
+    });
+    let ok = Envelope::new(Codec::Json, &order(1))
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let failed = Envelope::new(Codec::Json, &order(0))
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    assert!(consumer.on_received(std::str::from_utf8(&ok).unwrap()));
+    assert!(!consumer.on_received(std::str::from_utf8(&failed).unwrap()));
+
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        assert!(consumer.on_received(std::str::from_utf8(&ok).unwrap()));
+        assert!(
+            consumer
+                .on_received_async(std::str::from_utf8(&ok).unwrap())
+                .await
+        );
+    });
+}
+
+/// 测试单线程运行时内同步处理消息时返回false而不是panic
+#[tokio::test]
+async fn test_on_received_current_thread() {
+    let route_key = unique("received_current");
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 1, |_: Order| async move {
+        Ok::<(), ConsumeError>(())
+    });
+    let msg = Envelope::new(Codec::Json, &order(1))
+        .unwrap()
+        .to_bytes()
+        .unwrap();
+    let msg = std::str::from_utf8(&msg).unwrap();
+    assert!(!consumer.on_received(msg));
+    assert!(consumer.on_received_async(msg).await);
+}
+
+/// 测试topic路由键匹配
+#[test]
+fn test_topic_matches() {
+    assert!(topic_matches("order.*.created", "order.eu.created"));
+    assert!(!topic_matches("order.*.created", "order.eu.west.created"));
+    assert!(topic_matches("order.#", "order"));
+    assert!(topic_matches("order.#", "order.eu.west.created"));
+    assert!(topic_matches("#.created", "order.eu.created"));
+    assert!(topic_matches("#", "anything.at.all"));
+    assert!(!topic_matches("order.*", "order"));
+    assert!(!topic_matches("order.created", "order.paid"));
+}
+
+/// 测试工作队列发布与消费
+#[tokio::test]
+async fn test_memory_work_queue() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("orders");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let consumer = Consumer::work_queue(&route_key, 1, 10, false, 3, move |msg: Order| {
+        let tx = tx.clone();
+        async move {
+            tx.send(msg).unwrap();
+            Ok::<(), ConsumeError>(())
+        }
+    });
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    publisher::pub_work_queue_msg(&route_key, order(1))
+        .await
+        .unwrap();
+    publisher::pub_work_queue_msg(&route_key, order(2))
+        .await
+        .unwrap();
+
+    assert_eq!(recv(&mut rx).await, order(1));
+    assert_eq!(recv(&mut rx).await, order(2));
+    assert_eq!(broker.published_to("", &route_key).len(), 2);
+
+    // 确认后队列中不再有消息
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(broker.queue_len(&route_key), 0);
+    assert_eq!(broker.unacked_len(&route_key), 0);
+}
+
+/// 测试广播消息投递到所有消费者
+#[tokio::test]
+async fn test_memory_broadcast() {
+    MemoryBroker::install();
+    let exchange = unique("notice");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for idx in 0..2 {
+        let tx = tx.clone();
+        let consumer = Consumer::broadcast(&exchange, 1, move |msg: Order| {
+            let tx = tx.clone();
+            async move {
+                tx.send((idx, msg)).unwrap();
+                Ok::<(), ConsumeError>(())
+            }
+        });
+        assert!(
+            ConsumerBinder::get_instance()
+                .bind_broadcast_consumer(consumer)
+                .await
+        );
+    }
+
+    publisher::pub_broadcast_msg(&exchange, order(7))
+        .await
+        .unwrap();
+
+    let mut received = vec![recv(&mut rx).await, recv(&mut rx).await];
+    received.sort_by_key(|(idx, _)| *idx);
+    assert_eq!(received, vec![(0, order(7)), (1, order(7))]);
+}
+
+/// 测试广播消息处理失败后经实例的延迟队列重试
+#[tokio::test]
+async fn test_memory_broadcast_retry() {
+    let broker = MemoryBroker::install();
+    let exchange = unique("retry_notice");
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let counter = attempts.clone();
+    let consumer = Consumer::broadcast(&exchange, 3, move |msg: Order| {
+        let tx = tx.clone();
+        let counter = counter.clone();
+        async move {
+            if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                return Err(ConsumeError::Retry("first attempt".to_string()));
+            }
+            tx.send(msg).unwrap();
+            Ok(())
+        }
+    });
+    consumer.set_retry_backoff(RetryBackoff::Fixed(20));
+
+    // amq.前缀的队列不允许客户端声明
+    let queue = consumer.broadcast_queue_name();
+    let delay_queue = retry::delay_queue_name(&queue, 20);
+    assert!(!queue.starts_with("amq."));
+    assert!(!delay_queue.starts_with("amq."));
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_broadcast_consumer(consumer)
+            .await
+    );
+    assert!(broker.queue_exists(&queue));
+
+    publisher::pub_broadcast_msg(&exchange, order(8))
+        .await
+        .unwrap();
+
+    assert_eq!(recv(&mut rx).await, order(8));
+    assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    assert_eq!(broker.published_to("", &delay_queue).len(), 1);
+}
+
+/// 测试topic消息按模式路由
+#[tokio::test]
+async fn test_memory_topic() {
+    MemoryBroker::install();
+    let exchange = unique("events");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let consumer = Consumer::topic(&exchange, "order.*.created", 1, move |msg: Order| {
+        let tx = tx.clone();
+        async move {
+            tx.send(msg).unwrap();
+            Ok::<(), ConsumeError>(())
+        }
+    });
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_topic_consumer(consumer)
+            .await
+    );
+
+    publisher::pub_topic_msg(&exchange, "order.eu.paid", order(1))
+        .await
+        .unwrap();
+    publisher::pub_topic_msg(&exchange, "order.eu.created", order(2))
+        .await
+        .unwrap();
+
+    assert_eq!(recv(&mut rx).await, order(2));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(rx.try_recv().is_err());
+}
+
+/// 测试prefetch限制与nack重新入队
+#[tokio::test]
+async fn test_memory_prefetch_and_requeue() {
+    let broker = MemoryBroker::install();
+    let queue = unique("prefetch");
+
+    let mut subscription = broker
+        .subscribe(ConsumeSpec {
+            queue: QueueSpec {
+                name: queue.clone(),
+                durable: true,
+                ..Default::default()
+            },
+            declare_queue: true,
+            prefetch_count: 1,
+            consumer_tag: "test".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    publisher::pub_work_queue_msg(&queue, order(1))
+        .await
+        .unwrap();
+    publisher::pub_work_queue_msg(&queue, order(2))
+        .await
+        .unwrap();
+
+    // prefetch为1时第二条消息等待第一条确认
+    let first = subscription.deliveries.next().await.unwrap().unwrap();
+    assert_eq!(broker.unacked_len(&queue), 1);
+    assert_eq!(broker.queue_len(&queue), 1);
+    assert!(!first.redelivered);
+
+    // 重新入队后再次投递，并标记为重新投递
+    first.nack(true).await.unwrap();
+    let again = subscription.deliveries.next().await.unwrap().unwrap();
+    assert!(again.redelivered);
+    let envelope = Envelope::from_bytes(Codec::Json, &again.data).unwrap();
+    assert_eq!(envelope.decode_content::<Order>().unwrap(), order(1));
+
+    again.ack().await.unwrap();
+    let second = subscription.deliveries.next().await.unwrap().unwrap();
+    let envelope = Envelope::from_bytes(Codec::Json, &second.data).unwrap();
+    assert_eq!(envelope.decode_content::<Order>().unwrap(), order(2));
+    second.ack().await.unwrap();
+
+    assert_eq!(broker.queue_len(&queue), 0);
+    assert_eq!(broker.unacked_len(&queue), 0);
+    subscription.control.close().await;
+}
+
+/// 测试处理失败后经延迟队列重试
+#[tokio::test]
+async fn test_memory_retry() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("retry");
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let counter = attempts.clone();
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 3, move |msg: Order| {
+        let tx = tx.clone();
+        let counter = counter.clone();
+        async move {
+            if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                return Err(ConsumeError::Retry("first attempt".to_string()));
+            }
+            tx.send(msg).unwrap();
+            Ok(())
+        }
+    });
+    consumer.set_retry_backoff(RetryBackoff::Fixed(20));
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    publisher::pub_work_queue_msg(&route_key, order(3))
+        .await
+        .unwrap();
+
+    assert_eq!(recv(&mut rx).await, order(3));
+    assert_eq!(attempts.load(Ordering::Relaxed), 2);
+    assert_eq!(
+        broker
+            .published_to("", &format!("{}.retry.20", route_key))
+            .len(),
+        1
+    );
+}
+
+/// 测试超过最大重试次数后转入死信队列
+#[tokio::test]
+async fn test_memory_dead_letter() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("dead");
+
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 1, move |_: Order| async move {
+        Err::<(), _>(ConsumeError::DeadLetter("invalid order".to_string()))
+    });
+    let dlq = consumer.dead_letter_queue();
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    publisher::pub_work_queue_msg(&route_key, order(4))
+        .await
+        .unwrap();
+
+    timeout(Duration::from_secs(5), async {
+        while broker.queue_len(&dlq) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("message not dead lettered");
+
+    let dead = broker.get(&dlq).unwrap();
+    let envelope = Envelope::from_bytes(Codec::Json, &dead.data).unwrap();
+    assert_eq!(envelope.decode_content::<Order>().unwrap(), order(4));
+}
+
+/// 测试无法解码的消息按原样转入死信队列
+#[tokio::test]
+async fn test_memory_dead_letter_undecodable() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("undecodable");
+
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 1, move |_: Order| async move {
+        Ok::<(), ConsumeError>(())
+    });
+    let dlq = consumer.dead_letter_queue();
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    let properties = lapin::BasicProperties::default().with_content_type("application/json".into());
+    publisher::pub_raw_queue_msg_internal(&route_key, b"not an envelope", properties)
+        .await
+        .unwrap();
+
+    timeout(Duration::from_secs(5), async {
+        while broker.queue_len(&dlq) == 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("message not dead lettered");
+
+    let dead_letters = admin::list_dead_letters(&dlq, 0, 10).await.unwrap();
+    assert_eq!(dead_letters.len(), 1);
+    assert!(dead_letters[0].guid.is_empty());
+    assert_eq!(
+        dead_letters[0].origin_queue.as_deref(),
+        Some(route_key.as_str())
+    );
+    assert!(
+        dead_letters[0]
+            .reason
+            .as_deref()
+            .unwrap()
+            .starts_with("decode failed")
+    );
+    let dead = broker.get(&dlq).unwrap();
+    assert_eq!(dead.data, b"not an envelope");
+}
+
+/// 测试RPC调用
+#[tokio::test]
+async fn test_memory_rpc() {
+    MemoryBroker::install();
+    let route_key = unique("rpc");
+
+    let consumer = Consumer::rpc(&route_key, 1, 1, false, |req: Order| async move {
+        if req.id == 0 {
+            return Err("invalid id".to_string());
+        }
+        Ok(Order {
+            id: req.id * 10,
+            name: req.name.to_uppercase(),
+        })
+    });
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    let resp: Order = rpc::call(&route_key, order(5), Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(
+        resp,
+        Order {
+            id: 50,
+            name: "ORDER-5".to_string(),
+        }
+    );
+
+    let err = rpc::call::<_, Order>(&route_key, order(0), Duration::from_secs(5))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, rpc::RpcError::Remote(ref reason) if reason == "invalid id"));
+}
+
+/// 测试调用方放弃等待后移除等待中的请求
+#[tokio::test]
+async fn test_memory_rpc_cancelled() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("rpc_cancelled");
+
+    // 没有消费者处理请求，在外层超时后丢弃调用
+    let call = rpc::call::<_, Order>(&route_key, order(1), Duration::from_secs(60));
+    assert!(timeout(Duration::from_millis(50), call).await.is_err());
+
+    let requests = broker.published_to("", &route_key);
+    assert_eq!(requests.len(), 1);
+    let correlation_id = requests[0].properties.correlation_id().clone().unwrap();
+    assert!(!rpc::is_pending(correlation_id.as_str()));
+}
+
+/// 测试mandatory消息无法路由
+#[tokio::test]
+async fn test_memory_unroutable() {
+    let broker = MemoryBroker::install();
+    broker.declare_queue(&unique("unused"), false, &FieldTable::default());
+
+    let options = publisher::PublishOptions::default().mandatory(true);
+    let exchange = unique("topic");
+    let err =
+        publisher::pub_topic_msg_with_options(&exchange, "nobody.listens", order(6), &options)
+            .await
+            .unwrap_err();
+    assert!(matches!(
+        err,
+        publisher::PublishError::Unroutable {
+            reply_code: 312,
+            ..
+        }
+    ));
+}
+
+/// 测试重复消息去重
+#[tokio::test]
+async fn test_memory_dedupe() {
+    let broker = MemoryBroker::install();
+    let store = MemoryDedupeStore::install();
+    let route_key = unique("dedupe");
+
+    let attempts = Arc::new(AtomicU32::new(0));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let counter = attempts.clone();
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 3, move |msg: Order| {
+        let tx = tx.clone();
+        let counter = counter.clone();
+        async move {
+            // 首次处理失败，释放租约后重试的消息应再次处理
+            if counter.fetch_add(1, Ordering::Relaxed) == 0 {
+                return Err(ConsumeError::Retry("first attempt".to_string()));
+            }
+            tx.send(msg).unwrap();
+            Ok::<(), ConsumeError>(())
+        }
+    });
+    consumer.set_retry_backoff(RetryBackoff::Fixed(20));
+    consumer.set_dedupe(DedupeOptions::default().scope(&route_key));
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    // 同一guid的消息投递两次
+    let envelope = Envelope::new(Codec::Json, &order(8)).unwrap();
+    let data = envelope.to_bytes().unwrap();
+    let properties =
+        lapin::BasicProperties::default().with_content_type(Codec::Json.content_type().into());
+    broker
+        .publish(Publishing {
+            exchange: "",
+            route_key: &route_key,
+            declare: Declare::Nothing,
+            messages: vec![(data.clone(), properties.clone())],
+            mandatory: false,
+            confirm_timeout: Duration::from_secs(5),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(recv(&mut rx).await, order(8));
+    let key = DedupeOptions::default()
+        .scope(&route_key)
+        .key("", &envelope.guid);
+    assert!(store.is_done(&key));
+    assert_eq!(key.db(), 0);
+    assert_eq!(
+        DedupeOptions::default().db(3).key("", &envelope.guid).db(),
+        3
+    );
+
+    broker
+        .publish(Publishing {
+            exchange: "",
+            route_key: &route_key,
+            declare: Declare::Nothing,
+            messages: vec![(data, properties)],
+            mandatory: false,
+            confirm_timeout: Duration::from_secs(5),
+        })
+        .await
+        .unwrap();
+
+    // 重复消息被确认但不再处理
+    timeout(Duration::from_secs(5), async {
+        while broker.queue_len(&route_key) > 0 || broker.unacked_len(&route_key) > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("duplicate not acked");
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(rx.try_recv().is_err());
+    assert_eq!(attempts.load(Ordering::Relaxed), 2);
+}
+
+/// 测试广播消费者默认按实例去重，每个实例都处理同一消息
+#[tokio::test]
+async fn test_memory_broadcast_dedupe() {
+    MemoryDedupeStore::install();
+    MemoryBroker::install();
+    let exchange = unique("dedupe_notice");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for idx in 0..2 {
+        let tx = tx.clone();
+        let consumer = Consumer::broadcast(&exchange, 1, move |msg: Order| {
+            let tx = tx.clone();
+            async move {
+                tx.send((idx, msg)).unwrap();
+                Ok::<(), ConsumeError>(())
+            }
+        });
+        consumer.set_dedupe(DedupeOptions::default());
+        assert!(
+            ConsumerBinder::get_instance()
+                .bind_broadcast_consumer(consumer)
+                .await
+        );
+    }
+
+    publisher::pub_broadcast_msg(&exchange, order(9))
+        .await
+        .unwrap();
+
+    let mut received = vec![recv(&mut rx).await, recv(&mut rx).await];
+    received.sort_by_key(|(idx, _)| *idx);
+    assert_eq!(received, vec![(0, order(9)), (1, order(9))]);
+}
+
+/// 测试分区键哈希
+#[test]
+fn test_partition_of() {
+    assert_eq!(partition::fnv1a(""), 0x811c9dc5);
+    assert_eq!(partition::fnv1a("a"), 0xe40c292c);
+    assert_eq!(partition::fnv1a("foobar"), 0xbf9cf968);
+    assert_eq!(partition::partition_of("order-1", 1), 0);
+    assert!(partition::partition_of("order-1", 8) < 8);
+    assert_eq!(
+        partition::partition_of("order-1", 8),
+        partition::partition_of("order-1", 8)
+    );
+}
+
+/// 测试分区消费: 同一分区键顺序处理，失败时原地重试
+#[tokio::test]
+async fn test_memory_partitioned() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("partitioned");
+    let partitions = 4;
+
+    let failed = Arc::new(AtomicU32::new(0));
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let counter = failed.clone();
+    let consumers = Consumer::partitioned(&route_key, partitions, 3, move |msg: Order| {
+        let tx = tx.clone();
+        let counter = counter.clone();
+        async move {
+            // 前两次处理失败，原地重试期间同一分区的后续消息等待
+            if counter.fetch_add(1, Ordering::Relaxed) < 2 {
+                return Err(ConsumeError::Retry("first attempt".to_string()));
+            }
+            tx.send(msg).unwrap();
+            Ok::<(), ConsumeError>(())
+        }
+    });
+    assert_eq!(consumers.len(), partitions as usize);
+    for consumer in consumers {
+        consumer.set_retry_backoff(RetryBackoff::Fixed(20));
+        // 每个分区绑定两次，单活队列中第二个消费者作为备用
+        for _ in 0..2 {
+            assert!(
+                ConsumerBinder::get_instance()
+                    .bind_work_queue_consumer(consumer.clone())
+                    .await
+            );
+        }
+    }
+
+    let keys = ["customer-a", "customer-b"];
+    for seq in 0..5 {
+        for (idx, key) in keys.iter().enumerate() {
+            let id = (idx as u32 + 1) * 100 + seq * 10;
+            publisher::pub_partitioned_msg(&route_key, partitions, key, order(id))
+                .await
+                .unwrap();
+        }
+    }
+
+    let mut received: Vec<Vec<u32>> = vec![Vec::new(); keys.len()];
+    for _ in 0..10 {
+        let msg = recv(&mut rx).await;
+        received[(msg.id / 100 - 1) as usize].push(msg.id);
+    }
+    for (idx, ids) in received.iter().enumerate() {
+        let expected: Vec<u32> = (0..5)
+            .map(|seq| (idx as u32 + 1) * 100 + seq * 10)
+            .collect();
+        assert_eq!(ids, &expected);
+    }
+
+    // 原地重试不经过延迟队列
+    let queue = partition::partition_queue(
+        &route_key,
+        partition::partition_of("customer-a", partitions),
+    );
+    assert_eq!(broker.published_to("", &queue).len(), 5);
+    assert!(
+        broker
+            .published_to("", &format!("{}.retry.20", queue))
+            .is_empty()
+    );
+}
+
+/// 测试延迟消息的投递与取消
+#[tokio::test]
+async fn test_memory_delayed() {
+    MemoryBroker::install();
+    let store = MemoryDelayedStore::install();
+    let route_key = unique("delayed");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 3, move |msg: Order| {
+        let tx = tx.clone();
+        async move {
+            tx.send(msg).unwrap();
+            Ok::<(), ConsumeError>(())
+        }
+    });
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    let due = publisher::pub_delayed_msg(&route_key, order(9), Duration::from_millis(50))
+        .await
+        .unwrap();
+    let later = publisher::pub_delayed_msg(&route_key, order(10), Duration::from_secs(3600))
+        .await
+        .unwrap();
+    let cancelled = publisher::pub_delayed_msg(&route_key, order(11), Duration::ZERO)
+        .await
+        .unwrap();
+    assert!(publisher::cancel_delayed_msg(&cancelled).await.unwrap());
+    assert!(!publisher::cancel_delayed_msg(&cancelled).await.unwrap());
+
+    // 未到期时不投递
+    DelayedScheduler::get_instance()
+        .dispatch_due()
+        .await
+        .unwrap();
+    assert!(store.contains(&due));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    DelayedScheduler::get_instance()
+        .dispatch_due()
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut rx).await, order(9));
+    assert!(!store.contains(&due));
+    assert!(store.contains(&later));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(rx.try_recv().is_err());
+    assert!(publisher::cancel_delayed_msg(&later).await.unwrap());
+}
+
+/// 测试死信消息的浏览、编辑后重新发布与删除
+#[tokio::test]
+async fn test_memory_dead_letter_admin() {
+    let broker = MemoryBroker::install();
+    let route_key = unique("admin");
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let consumer = Consumer::work_queue(&route_key, 1, 1, false, 1, move |msg: Order| {
+        let tx = tx.clone();
+        async move {
+            if msg.id == 0 {
+                return Err(ConsumeError::DeadLetter("invalid order".to_string()));
+            }
+            tx.send(msg).unwrap();
+            Ok(())
+        }
+    });
+    let dlq = consumer.dead_letter_queue();
+    assert!(
+        ConsumerBinder::get_instance()
+            .bind_work_queue_consumer(consumer)
+            .await
+    );
+
+    for _ in 0..3 {
+        publisher::pub_work_queue_msg(&route_key, order(0))
+            .await
+            .unwrap();
+    }
+    timeout(Duration::from_secs(5), async {
+        while broker.queue_len(&dlq) < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("messages not dead lettered");
+
+    // 浏览后消息仍留在死信队列中
+    let all = admin::list_dead_letters(&dlq, 0, 10).await.unwrap();
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].origin_queue.as_deref(), Some(route_key.as_str()));
+    assert_eq!(all[0].reason.as_deref(), Some("invalid order"));
+    assert_eq!(
+        all[0].content,
+        Some(serde_json::to_value(order(0)).unwrap())
+    );
+    let page = admin::list_dead_letters(&dlq, 1, 10).await.unwrap();
+    assert_eq!(
+        page.iter().map(|d| d.guid.clone()).collect::<Vec<_>>(),
+        all[1..].iter().map(|d| d.guid.clone()).collect::<Vec<_>>()
+    );
+    assert_eq!(broker.queue_len(&dlq), 3);
+    assert_eq!(broker.unacked_len(&dlq), 0);
+
+    // 编辑后重新发布到原始队列
+    let edited = serde_json::to_value(order(12)).unwrap();
+    admin::replay_dead_letter(&dlq, &all[1].guid, Some(edited))
+        .await
+        .unwrap();
+    assert_eq!(recv(&mut rx).await, order(12));
+    assert_eq!(broker.queue_len(&dlq), 2);
+
+    // 删除单条与清空
+    assert_eq!(
+        admin::purge_dead_letters(&dlq, Some(&all[0].guid))
+            .await
+            .unwrap(),
+        1
+    );
+    let rest = admin::list_dead_letters(&dlq, 0, 10).await.unwrap();
+    assert_eq!(rest.len(), 1);
+    assert_eq!(rest[0].guid, all[2].guid);
+    assert_eq!(admin::purge_dead_letters(&dlq, None).await.unwrap(), 1);
+    assert_eq!(broker.queue_len(&dlq), 0);
+
+    assert!(matches!(
+        admin::replay_dead_letter(&dlq, &all[2].guid, None).await,
+        Err(AdminError::NotFound(_))
+    ));
+    assert!(matches!(
+        admin::list_dead_letters(&route_key, 0, 10).await,
+        Err(AdminError::InvalidArgument(_))
+    ));
+}
@@ -0,0 +1,296 @@
+use inventory;
+use lapin::types::{AMQPValue, FieldTable};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 交换器定义
+#[derive(Debug, Clone)]
+pub struct ExchangeDef {
+    pub name: String,
+    pub kind: lapin::ExchangeKind,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub arguments: FieldTable,
+}
+
+#[allow(dead_code, reason = "拓扑定义的构造方法，由业务代码注册时调用")]
+impl ExchangeDef {
+    /// 新建持久化交换器定义
+    pub fn new(name: &str, kind: lapin::ExchangeKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            durable: true,
+            auto_delete: false,
+            arguments: FieldTable::default(),
+        }
+    }
+
+    /// 设置是否持久化
+    pub fn durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    /// 设置无绑定时是否自动删除
+    pub fn auto_delete(mut self, auto_delete: bool) -> Self {
+        self.auto_delete = auto_delete;
+        self
+    }
+
+    /// 设置备用交换器，无法路由的消息转发到该交换器
+    pub fn alternate_exchange(self, exchange: &str) -> Self {
+        self.argument("alternate-exchange", AMQPValue::LongString(exchange.into()))
+    }
+
+    /// 设置自定义参数
+    pub fn argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+}
+
+/// 队列定义
+#[derive(Debug, Clone)]
+pub struct QueueDef {
+    pub name: String,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub arguments: FieldTable,
+}
+
+#[allow(dead_code, reason = "拓扑定义的构造方法，由业务代码注册时调用")]
+impl QueueDef {
+    /// 新建持久化队列定义
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            durable: true,
+            auto_delete: false,
+            arguments: FieldTable::default(),
+        }
+    }
+
+    /// 设置是否持久化
+    pub fn durable(mut self, durable: bool) -> Self {
+        self.durable = durable;
+        self
+    }
+
+    /// 设置无消费者时是否自动删除
+    pub fn auto_delete(mut self, auto_delete: bool) -> Self {
+        self.auto_delete = auto_delete;
+        self
+    }
+
+    /// 设置消息存活时间 毫秒
+    pub fn message_ttl(self, ttl_ms: u64) -> Self {
+        self.argument("x-message-ttl", AMQPValue::LongLongInt(ttl_ms as i64))
+    }
+
+    /// 设置队列最大消息数
+    pub fn max_length(self, max_length: u64) -> Self {
+        self.argument("x-max-length", AMQPValue::LongLongInt(max_length as i64))
+    }
+
+    /// 设置队列最大字节数
+    pub fn max_length_bytes(self, max_bytes: u64) -> Self {
+        self.argument(
+            "x-max-length-bytes",
+            AMQPValue::LongLongInt(max_bytes as i64),
+        )
+    }
+
+    /// 使用仲裁队列，仲裁队列必须持久化
+    pub fn quorum(mut self) -> Self {
+        self.durable = true;
+        self.argument("x-queue-type", AMQPValue::LongString("quorum".into()))
+    }
+
+    /// 设置死信交换器
+    pub fn dead_letter_exchange(self, exchange: &str) -> Self {
+        self.argument(
+            "x-dead-letter-exchange",
+            AMQPValue::LongString(exchange.into()),
+        )
+    }
+
+    /// 设置死信路由键
+    pub fn dead_letter_routing_key(self, route_key: &str) -> Self {
+        self.argument(
+            "x-dead-letter-routing-key",
+            AMQPValue::LongString(route_key.into()),
+        )
+    }
+
+    /// 设置自定义参数
+    pub fn argument(mut self, key: &str, value: AMQPValue) -> Self {
+        self.arguments.insert(key.into(), value);
+        self
+    }
+}
+
+/// 绑定定义
+#[derive(Debug, Clone)]
+pub struct BindingDef {
+    pub queue: String,
+    pub exchange: String,
+    pub route_key: String,
+}
+
+/// 拓扑定义
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    exchanges: Vec<ExchangeDef>,
+    queues: Vec<QueueDef>,
+    bindings: Vec<BindingDef>,
+}
+
+#[allow(dead_code, reason = "拓扑的构造方法，由业务代码注册时调用")]
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加交换器
+    pub fn exchange(mut self, exchange: ExchangeDef) -> Self {
+        self.exchanges.push(exchange);
+        self
+    }
+
+    /// 添加队列
+    pub fn queue(mut self, queue: QueueDef) -> Self {
+        self.queues.push(queue);
+        self
+    }
+
+    /// 添加绑定
+    pub fn bind(mut self, queue: &str, exchange: &str, route_key: &str) -> Self {
+        self.bindings.push(BindingDef {
+            queue: queue.to_string(),
+            exchange: exchange.to_string(),
+            route_key: route_key.to_string(),
+        });
+        self
+    }
+}
+
+/// 拓扑注册项
+pub struct TopologyRegistration {
+    pub topology: fn() -> Topology,
+}
+
+inventory::collect!(TopologyRegistration);
+
+// 合并后的拓扑
+struct TopologyRegistry {
+    exchanges: HashMap<String, ExchangeDef>,
+    queues: HashMap<String, QueueDef>,
+    bindings: Vec<BindingDef>,
+}
+
+static REGISTRY: OnceLock<TopologyRegistry> = OnceLock::new();
+
+// 收集所有注册的拓扑，同名定义以后注册的为准
+fn registry() -> &'static TopologyRegistry {
+    REGISTRY.get_or_init(|| {
+        let mut registry = TopologyRegistry {
+            exchanges: HashMap::new(),
+            queues: HashMap::new(),
+            bindings: Vec::new(),
+        };
+        for registration in inventory::iter::<TopologyRegistration>() {
+            let topology = (registration.topology)();
+            for exchange in topology.exchanges {
+                registry.exchanges.insert(exchange.name.clone(), exchange);
+            }
+            for queue in topology.queues {
+                registry.queues.insert(queue.name.clone(), queue);
+            }
+            registry.bindings.extend(topology.bindings);
+        }
+        registry
+    })
+}
+
+/// 获取已注册的交换器定义
+pub fn exchange(name: &str) -> Option<&'static ExchangeDef> {
+    registry().exchanges.get(name)
+}
+
+/// 获取已注册的队列定义
+pub fn queue(name: &str) -> Option<&'static QueueDef> {
+    registry().queues.get(name)
+}
+
+// 获取所有已注册的拓扑定义，供内存broker建立拓扑
+#[cfg(test)]
+pub(crate) fn definitions() -> (
+    Vec<&'static ExchangeDef>,
+    Vec<&'static QueueDef>,
+    &'static [BindingDef],
+) {
+    let registry = registry();
+    (
+        registry.exchanges.values().collect(),
+        registry.queues.values().collect(),
+        &registry.bindings,
+    )
+}
+
+// 声明所有已注册的拓扑，重复声明相同定义是幂等的
+// 已存在的同名队列或交换器参数不一致时broker会关闭通道
+pub(crate) async fn declare_all(channel: &lapin::Channel) -> Result<(), lapin::Error> {
+    let registry = registry();
+
+    for exchange in registry.exchanges.values() {
+        channel
+            .exchange_declare(
+                &exchange.name,
+                exchange.kind.clone(),
+                lapin::options::ExchangeDeclareOptions {
+                    durable: exchange.durable,
+                    auto_delete: exchange.auto_delete,
+                    internal: false,
+                    ..Default::default()
+                },
+                exchange.arguments.clone(),
+            )
+            .await?;
+    }
+
+    for queue in registry.queues.values() {
+        channel
+            .queue_declare(
+                &queue.name,
+                lapin::options::QueueDeclareOptions {
+                    durable: queue.durable,
+                    auto_delete: queue.auto_delete,
+                    exclusive: false,
+                    ..Default::default()
+                },
+                queue.arguments.clone(),
+            )
+            .await?;
+    }
+
+    for binding in registry.bindings.iter() {
+        channel
+            .queue_bind(
+                &binding.queue,
+                &binding.exchange,
+                &binding.route_key,
+                lapin::options::QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+    }
+
+    tracing::info!(
+        "mq topology declared, {} exchanges, {} queues, {} bindings",
+        registry.exchanges.len(),
+        registry.queues.len(),
+        registry.bindings.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,858 @@
+use crate::common::mqutils::publisher::PublishError;
+use crate::common::mqutils::topology;
+use crate::common::mqutils::transport::{
+    self, Acker, ConsumeSpec, Declare, Delivery, Publishing, QueueBrowser, Subscription,
+    SubscriptionControl, Transport, TransportError,
+};
+use crate::common::testutil;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use lapin::types::{AMQPValue, FieldTable};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// 进程内的内存broker，用于在没有RabbitMQ的环境下测试发布与消费
+/// 支持默认交换器、direct/fanout/topic交换器、ack/nack/重新入队、prefetch、
+/// 消息过期(x-message-ttl、expiration)、死信转发(x-dead-letter-exchange)与单活消费者(x-single-active-consumer)
+pub struct MemoryBroker {
+    state: Arc<Mutex<BrokerState>>,
+}
+
+/// 已发布的消息记录
+#[derive(Debug, Clone)]
+pub struct PublishedMessage {
+    pub exchange: String,
+    pub route_key: String,
+    pub properties: lapin::BasicProperties,
+    pub data: Vec<u8>,
+}
+
+// broker状态，所有操作在锁内同步完成
+#[derive(Default)]
+struct BrokerState {
+    exchanges: HashMap<String, MemExchange>,
+    queues: HashMap<String, MemQueue>,
+    /// 已投递未确认的消息，key为delivery tag
+    unacked: BTreeMap<u64, Unacked>,
+    /// 已发布的消息
+    published: Vec<PublishedMessage>,
+    next_tag: u64,
+    next_consumer_id: u64,
+}
+
+struct MemExchange {
+    kind: lapin::ExchangeKind,
+    auto_delete: bool,
+    /// 绑定 (队列, 路由键)
+    bindings: Vec<(String, String)>,
+}
+
+struct MemQueue {
+    auto_delete: bool,
+    exclusive: bool,
+    message_ttl: Option<Duration>,
+    dead_letter_exchange: Option<String>,
+    dead_letter_routing_key: Option<String>,
+    messages: VecDeque<Message>,
+    consumers: Vec<MemConsumer>,
+    /// 单活消费者(x-single-active-consumer)，仅向最早订阅的消费者投递
+    single_active: bool,
+    /// 轮询投递的下一个消费者
+    next_consumer: usize,
+}
+
+#[derive(Clone)]
+struct Message {
+    exchange: String,
+    routing_key: String,
+    properties: lapin::BasicProperties,
+    data: Vec<u8>,
+    redelivered: bool,
+    expires_at: Option<Instant>,
+}
+
+struct MemConsumer {
+    id: u64,
+    prefetch_count: u16,
+    no_ack: bool,
+    /// 未确认的消息数
+    unacked: usize,
+    tx: mpsc::UnboundedSender<Result<Delivery, TransportError>>,
+}
+
+struct Unacked {
+    queue: String,
+    consumer_id: u64,
+    message: Message,
+}
+
+impl MemoryBroker {
+    /// 新建内存broker，已注册的拓扑会预先声明
+    pub fn new() -> Arc<Self> {
+        let broker = Arc::new(MemoryBroker {
+            state: Arc::new(Mutex::new(BrokerState::default())),
+        });
+
+        let (exchanges, queues, bindings) = topology::definitions();
+        for exchange in exchanges {
+            broker.declare_exchange(&exchange.name, exchange.kind.clone(), exchange.auto_delete);
+        }
+        for queue in queues {
+            broker.declare_queue(&queue.name, queue.auto_delete, &queue.arguments);
+        }
+        for binding in bindings {
+            let _ = broker.bind(&binding.queue, &binding.exchange, &binding.route_key);
+        }
+        broker
+    }
+
+    /// 安装进程内共享的内存broker作为传输层，重复调用返回同一实例
+    pub fn install() -> Arc<Self> {
+        let broker = testutil::shared(MemoryBroker::new);
+        transport::TRANSPORT.install(broker.clone());
+        broker
+    }
+
+    /// 声明交换器，已存在时不修改
+    pub fn declare_exchange(&self, name: &str, kind: lapin::ExchangeKind, auto_delete: bool) {
+        self.lock().declare_exchange(name, kind, auto_delete);
+    }
+
+    /// 声明队列，已存在时不修改
+    pub fn declare_queue(&self, name: &str, auto_delete: bool, arguments: &FieldTable) {
+        self.lock()
+            .declare_queue(name, auto_delete, false, arguments);
+    }
+
+    /// 绑定队列到交换器
+    pub fn bind(&self, queue: &str, exchange: &str, route_key: &str) -> Result<(), TransportError> {
+        self.lock().bind(queue, exchange, route_key)
+    }
+
+    /// 发布到指定交换器与路由键的消息
+    pub fn published_to(&self, exchange: &str, route_key: &str) -> Vec<PublishedMessage> {
+        self.lock()
+            .published
+            .iter()
+            .filter(|msg| msg.exchange == exchange && msg.route_key == route_key)
+            .cloned()
+            .collect()
+    }
+
+    /// 队列中待投递的消息数
+    pub fn queue_len(&self, queue: &str) -> usize {
+        let state = self.lock();
+        state
+            .queues
+            .get(queue)
+            .map(|q| q.messages.len())
+            .unwrap_or(0)
+    }
+
+    /// 队列中已投递未确认的消息数
+    pub fn unacked_len(&self, queue: &str) -> usize {
+        self.lock()
+            .unacked
+            .values()
+            .filter(|unacked| unacked.queue == queue)
+            .count()
+    }
+
+    /// 队列是否存在
+    pub fn queue_exists(&self, queue: &str) -> bool {
+        self.lock().queues.contains_key(queue)
+    }
+
+    /// 从队列中取出一条待投递的消息，不经过消费者
+    pub fn get(&self, queue: &str) -> Option<PublishedMessage> {
+        let mut state = self.lock();
+        state.expire(queue);
+        let message = state.queues.get_mut(queue)?.messages.pop_front()?;
+        Some(PublishedMessage {
+            exchange: message.exchange,
+            route_key: message.routing_key,
+            properties: message.properties,
+            data: message.data,
+        })
+    }
+
+    /// 清空队列，返回清除的消息数
+    pub fn purge(&self, queue: &str) -> usize {
+        let mut state = self.lock();
+        match state.queues.get_mut(queue) {
+            Some(q) => {
+                let count = q.messages.len();
+                q.messages.clear();
+                count
+            }
+            None => 0,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BrokerState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Transport for MemoryBroker {
+    fn publish<'a>(
+        &'a self,
+        publishing: Publishing<'a>,
+    ) -> BoxFuture<'a, Result<Vec<Result<(), PublishError>>, PublishError>> {
+        Box::pin(async move {
+            let mut state = self.lock();
+            match publishing.declare {
+                Declare::Queue {
+                    name, arguments, ..
+                } => state.declare_queue(name, false, false, &arguments),
+                Declare::Exchange {
+                    name,
+                    kind,
+                    auto_delete,
+                    ..
+                } => state.declare_exchange(name, kind, auto_delete),
+                Declare::Nothing => {}
+            }
+
+            if !publishing.exchange.is_empty() && !state.exchanges.contains_key(publishing.exchange)
+            {
+                return Err(PublishError::Transport(TransportError::NotFound(format!(
+                    "exchange '{}'",
+                    publishing.exchange
+                ))));
+            }
+
+            let mut results = Vec::with_capacity(publishing.messages.len());
+            let mut expiring = Vec::new();
+            for (data, properties) in publishing.messages {
+                state.published.push(PublishedMessage {
+                    exchange: publishing.exchange.to_string(),
+                    route_key: publishing.route_key.to_string(),
+                    properties: properties.clone(),
+                    data: data.clone(),
+                });
+
+                let message = Message {
+                    exchange: publishing.exchange.to_string(),
+                    routing_key: publishing.route_key.to_string(),
+                    properties,
+                    data,
+                    redelivered: false,
+                    expires_at: None,
+                };
+                let routed = state.route(message, &mut expiring);
+                if routed == 0 && publishing.mandatory {
+                    results.push(Err(PublishError::Unroutable {
+                        reply_code: 312,
+                        reply_text: "NO_ROUTE".to_string(),
+                    }));
+                } else {
+                    results.push(Ok(()));
+                }
+            }
+            state.dispatch_all(&self.state);
+            drop(state);
+
+            self.schedule_expire(expiring);
+            Ok(results)
+        })
+    }
+
+    fn subscribe(&self, spec: ConsumeSpec) -> BoxFuture<'_, Result<Subscription, TransportError>> {
+        Box::pin(async move {
+            let mut state = self.lock();
+            if let Some(ref exchange) = spec.exchange {
+                state.declare_exchange(&exchange.name, exchange.kind.clone(), exchange.auto_delete);
+            }
+
+            let queue = if spec.declare_queue {
+                let name = if spec.queue.name.is_empty() {
+                    format!("amq.gen-{}", uuid::Uuid::new_v4().simple())
+                } else {
+                    spec.queue.name.clone()
+                };
+                state.declare_queue(
+                    &name,
+                    spec.queue.auto_delete,
+                    spec.queue.exclusive,
+                    &spec.queue.arguments,
+                );
+                name
+            } else if state.queues.contains_key(&spec.queue.name) {
+                spec.queue.name.clone()
+            } else {
+                return Err(TransportError::NotFound(format!(
+                    "queue '{}'",
+                    spec.queue.name
+                )));
+            };
+
+            if let Some((ref exchange, ref route_key)) = spec.binding {
+                state.bind(&queue, exchange, route_key)?;
+            }
+
+            state.next_consumer_id += 1;
+            let consumer_id = state.next_consumer_id;
+            let (tx, rx) = mpsc::unbounded_channel();
+            if let Some(q) = state.queues.get_mut(&queue) {
+                q.consumers.push(MemConsumer {
+                    id: consumer_id,
+                    prefetch_count: spec.prefetch_count,
+                    no_ack: spec.no_ack,
+                    unacked: 0,
+                    tx,
+                });
+            }
+            state.dispatch(&queue, &self.state);
+            drop(state);
+
+            let deliveries = futures::stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|delivery| (delivery, rx))
+            })
+            .boxed();
+            Ok(Subscription {
+                queue: queue.clone(),
+                deliveries,
+                control: Box::new(MemorySubscription {
+                    state: Arc::downgrade(&self.state),
+                    queue,
+                    consumer_id,
+                }),
+            })
+        })
+    }
+
+    fn browse<'a>(
+        &'a self,
+        queue: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn QueueBrowser>, TransportError>> {
+        Box::pin(async move {
+            let mut state = self.lock();
+            if !state.queues.contains_key(queue) {
+                return Err(TransportError::NotFound(format!("queue '{}'", queue)));
+            }
+            state.next_consumer_id += 1;
+            Ok(Box::new(MemoryBrowser {
+                state: Arc::downgrade(&self.state),
+                queue: queue.to_string(),
+                browser_id: state.next_consumer_id,
+            }) as Box<dyn QueueBrowser>)
+        })
+    }
+
+    fn purge_queue<'a>(&'a self, queue: &'a str) -> BoxFuture<'a, Result<u32, TransportError>> {
+        Box::pin(async move {
+            if !self.queue_exists(queue) {
+                return Err(TransportError::NotFound(format!("queue '{}'", queue)));
+            }
+            Ok(self.purge(queue) as u32)
+        })
+    }
+}
+
+impl MemoryBroker {
+    // 消息过期时转入死信或丢弃
+    fn schedule_expire(&self, expiring: Vec<(String, Instant)>) {
+        if expiring.is_empty() || tokio::runtime::Handle::try_current().is_err() {
+            return;
+        }
+        for (queue, expires_at) in expiring {
+            schedule_expire(Arc::downgrade(&self.state), queue, expires_at);
+        }
+    }
+}
+
+// 到期后检查队列中的过期消息
+fn schedule_expire(state: Weak<Mutex<BrokerState>>, queue: String, expires_at: Instant) {
+    tokio::spawn(async move {
+        tokio::time::sleep_until(expires_at).await;
+        let Some(state_ref) = state.upgrade() else {
+            return;
+        };
+        let mut guard = state_ref.lock().unwrap();
+        let expiring = guard.expire(&queue);
+        guard.dispatch_all(&state_ref);
+        drop(guard);
+        for (queue, expires_at) in expiring {
+            schedule_expire(state.clone(), queue, expires_at);
+        }
+    });
+}
+
+impl BrokerState {
+    fn declare_exchange(&mut self, name: &str, kind: lapin::ExchangeKind, auto_delete: bool) {
+        self.exchanges
+            .entry(name.to_string())
+            .or_insert_with(|| MemExchange {
+                kind,
+                auto_delete,
+                bindings: Vec::new(),
+            });
+    }
+
+    fn declare_queue(
+        &mut self,
+        name: &str,
+        auto_delete: bool,
+        exclusive: bool,
+        arguments: &FieldTable,
+    ) {
+        self.queues
+            .entry(name.to_string())
+            .or_insert_with(|| MemQueue {
+                auto_delete,
+                exclusive,
+                message_ttl: arg_u64(arguments, "x-message-ttl").map(Duration::from_millis),
+                dead_letter_exchange: arg_string(arguments, "x-dead-letter-exchange"),
+                dead_letter_routing_key: arg_string(arguments, "x-dead-letter-routing-key"),
+                messages: VecDeque::new(),
+                consumers: Vec::new(),
+                single_active: arg_bool(arguments, "x-single-active-consumer"),
+                next_consumer: 0,
+            });
+    }
+
+    fn bind(&mut self, queue: &str, exchange: &str, route_key: &str) -> Result<(), TransportError> {
+        if !self.queues.contains_key(queue) {
+            return Err(TransportError::NotFound(format!("queue '{}'", queue)));
+        }
+        let exchange = self
+            .exchanges
+            .get_mut(exchange)
+            .ok_or_else(|| TransportError::NotFound(format!("exchange '{}'", exchange)))?;
+        let binding = (queue.to_string(), route_key.to_string());
+        if !exchange.bindings.contains(&binding) {
+            exchange.bindings.push(binding);
+        }
+        Ok(())
+    }
+
+    // 路由消息到匹配的队列，返回路由到的队列数
+    // 带过期时间的消息记录到expiring中，由调用方安排过期检查
+    fn route(&mut self, message: Message, expiring: &mut Vec<(String, Instant)>) -> usize {
+        let queues: Vec<String> = if message.exchange.is_empty() {
+            vec![message.routing_key.clone()]
+        } else {
+            match self.exchanges.get(&message.exchange) {
+                Some(exchange) => {
+                    let mut queues: Vec<String> = exchange
+                        .bindings
+                        .iter()
+                        .filter(|(_, pattern)| match exchange.kind {
+                            lapin::ExchangeKind::Fanout => true,
+                            lapin::ExchangeKind::Direct => *pattern == message.routing_key,
+                            lapin::ExchangeKind::Topic => {
+                                topic_matches(pattern, &message.routing_key)
+                            }
+                            _ => false,
+                        })
+                        .map(|(queue, _)| queue.clone())
+                        .collect();
+                    queues.dedup();
+                    queues
+                }
+                None => Vec::new(),
+            }
+        };
+
+        let now = Instant::now();
+        let message_expiration = message
+            .properties
+            .expiration()
+            .as_ref()
+            .and_then(|v| v.as_str().parse::<u64>().ok())
+            .map(Duration::from_millis);
+
+        let mut routed = 0;
+        for name in queues {
+            let Some(queue) = self.queues.get_mut(&name) else {
+                continue;
+            };
+            let ttl = match (queue.message_ttl, message_expiration) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+            let mut message = message.clone();
+            message.expires_at = ttl.map(|ttl| now + ttl);
+            if let Some(expires_at) = message.expires_at {
+                expiring.push((name.clone(), expires_at));
+            }
+            queue.messages.push_back(message);
+            routed += 1;
+        }
+        routed
+    }
+
+    // 移除队列中的过期消息，配置了死信交换器时转发
+    fn expire(&mut self, queue: &str) -> Vec<(String, Instant)> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        if let Some(q) = self.queues.get_mut(queue) {
+            let mut remaining = VecDeque::with_capacity(q.messages.len());
+            for message in q.messages.drain(..) {
+                match message.expires_at {
+                    Some(expires_at) if expires_at <= now => expired.push(message),
+                    _ => remaining.push_back(message),
+                }
+            }
+            q.messages = remaining;
+        }
+
+        let mut expiring = Vec::new();
+        for message in expired {
+            self.dead_letter(queue, message, &mut expiring);
+        }
+        expiring
+    }
+
+    // 转发到队列的死信交换器，未配置时丢弃
+    fn dead_letter(
+        &mut self,
+        queue: &str,
+        mut message: Message,
+        expiring: &mut Vec<(String, Instant)>,
+    ) {
+        let Some(q) = self.queues.get(queue) else {
+            return;
+        };
+        let Some(exchange) = q.dead_letter_exchange.clone() else {
+            return;
+        };
+        if let Some(ref route_key) = q.dead_letter_routing_key {
+            message.routing_key = route_key.clone();
+        }
+        message.exchange = exchange;
+        message.redelivered = false;
+        message.expires_at = None;
+        message.properties = message.properties.with_expiration("".into());
+        self.route(message, expiring);
+    }
+
+    // 向消费者投递消息，按顺序轮询有余量的消费者
+    // 单活队列只向最早订阅的消费者投递，该消费者断开后由下一个消费者接替
+    fn dispatch(&mut self, queue: &str, state_ref: &Arc<Mutex<BrokerState>>) {
+        loop {
+            let tag = self.next_tag + 1;
+            let Some(q) = self.queues.get_mut(queue) else {
+                return;
+            };
+            if q.messages.is_empty() || q.consumers.is_empty() {
+                return;
+            }
+
+            let count = q.consumers.len();
+            let (start, candidates) = if q.single_active {
+                (0, 1)
+            } else {
+                (q.next_consumer, count)
+            };
+            let idx = (0..candidates)
+                .map(|i| (start + i) % count)
+                .find(|&i| {
+                    let consumer = &q.consumers[i];
+                    consumer.no_ack
+                        || consumer.prefetch_count == 0
+                        || consumer.unacked < consumer.prefetch_count as usize
+                });
+            let Some(idx) = idx else {
+                return;
+            };
+            q.next_consumer = (idx + 1) % count;
+
+            let Some(message) = q.messages.pop_front() else {
+                return;
+            };
+            let consumer = &mut q.consumers[idx];
+            let delivery = Delivery {
+                exchange: message.exchange.clone(),
+                routing_key: message.routing_key.clone(),
+                redelivered: message.redelivered,
+                properties: message.properties.clone(),
+                data: message.data.clone(),
+                acker: Acker::Memory(MemoryAcker {
+                    state: Arc::downgrade(state_ref),
+                    tag,
+                    no_ack: consumer.no_ack,
+                }),
+            };
+
+            if consumer.tx.send(Ok(delivery)).is_err() {
+                // 消费者已断开，消息放回队首
+                let consumer_id = consumer.id;
+                q.messages.push_front(message);
+                self.remove_consumer(queue, consumer_id, false);
+                continue;
+            }
+
+            self.next_tag = tag;
+            if !consumer.no_ack {
+                consumer.unacked += 1;
+                let consumer_id = consumer.id;
+                self.unacked.insert(
+                    tag,
+                    Unacked {
+                        queue: queue.to_string(),
+                        consumer_id,
+                        message,
+                    },
+                );
+            }
+        }
+    }
+
+    fn dispatch_all(&mut self, state_ref: &Arc<Mutex<BrokerState>>) {
+        let queues: Vec<String> = self.queues.keys().cloned().collect();
+        for queue in queues {
+            self.dispatch(&queue, state_ref);
+        }
+    }
+
+    // 确认或拒绝消息，返回消息所在队列
+    fn settle(&mut self, tag: u64, requeue: Option<bool>) -> Vec<(String, Instant)> {
+        let mut expiring = Vec::new();
+        let Some(unacked) = self.unacked.remove(&tag) else {
+            return expiring;
+        };
+        if let Some(q) = self.queues.get_mut(&unacked.queue) {
+            if let Some(consumer) = q.consumers.iter_mut().find(|c| c.id == unacked.consumer_id) {
+                consumer.unacked = consumer.unacked.saturating_sub(1);
+            }
+            match requeue {
+                None => {}
+                Some(true) => {
+                    let mut message = unacked.message;
+                    message.redelivered = true;
+                    q.messages.push_front(message);
+                }
+                Some(false) => self.dead_letter(&unacked.queue, unacked.message, &mut expiring),
+            }
+        }
+        expiring
+    }
+
+    // 移除消费者，requeue为true时未确认的消息重新入队
+    // 自动删除的队列在最后一个消费者移除后删除
+    fn remove_consumer(&mut self, queue: &str, consumer_id: u64, requeue: bool) {
+        if requeue {
+            self.requeue_unacked(queue, consumer_id);
+        }
+
+        let delete = match self.queues.get_mut(queue) {
+            Some(q) => {
+                q.consumers.retain(|c| c.id != consumer_id);
+                q.next_consumer = 0;
+                (q.auto_delete || q.exclusive) && q.consumers.is_empty()
+            }
+            None => false,
+        };
+        if delete {
+            self.delete_queue(queue);
+        }
+    }
+
+    // 消费者未确认的消息按原顺序重新入队
+    fn requeue_unacked(&mut self, queue: &str, consumer_id: u64) {
+        let tags: Vec<u64> = self
+            .unacked
+            .iter()
+            .filter(|(_, unacked)| unacked.queue == queue && unacked.consumer_id == consumer_id)
+            .map(|(tag, _)| *tag)
+            .collect();
+        for tag in tags.into_iter().rev() {
+            if let Some(unacked) = self.unacked.remove(&tag)
+                && let Some(q) = self.queues.get_mut(queue)
+            {
+                let mut message = unacked.message;
+                message.redelivered = true;
+                q.messages.push_front(message);
+            }
+        }
+    }
+
+    // 删除队列及其绑定，无绑定的自动删除交换器一并删除
+    fn delete_queue(&mut self, queue: &str) {
+        self.queues.remove(queue);
+        self.exchanges.retain(|_, exchange| {
+            let len = exchange.bindings.len();
+            exchange.bindings.retain(|(q, _)| q != queue);
+            !(exchange.auto_delete && len > 0 && exchange.bindings.is_empty())
+        });
+    }
+}
+
+// 内存broker的消息确认
+pub(super) struct MemoryAcker {
+    state: Weak<Mutex<BrokerState>>,
+    tag: u64,
+    no_ack: bool,
+}
+
+impl MemoryAcker {
+    pub(super) fn ack(&self) {
+        self.settle(None);
+    }
+
+    pub(super) fn nack(&self, requeue: bool) {
+        self.settle(Some(requeue));
+    }
+
+    fn settle(&self, requeue: Option<bool>) {
+        if self.no_ack {
+            return;
+        }
+        let Some(state_ref) = self.state.upgrade() else {
+            return;
+        };
+        let mut state = state_ref.lock().unwrap();
+        let expiring = state.settle(self.tag, requeue);
+        state.dispatch_all(&state_ref);
+        drop(state);
+
+        if tokio::runtime::Handle::try_current().is_ok() {
+            for (queue, expires_at) in expiring {
+                schedule_expire(self.state.clone(), queue, expires_at);
+            }
+        }
+    }
+}
+
+// 内存broker的队列浏览，拉取的消息以浏览id记为未确认
+struct MemoryBrowser {
+    state: Weak<Mutex<BrokerState>>,
+    queue: String,
+    browser_id: u64,
+}
+
+impl QueueBrowser for MemoryBrowser {
+    fn next(&mut self) -> BoxFuture<'_, Result<Option<Delivery>, TransportError>> {
+        Box::pin(async move {
+            let Some(state_ref) = self.state.upgrade() else {
+                return Ok(None);
+            };
+            let mut state = state_ref.lock().unwrap();
+            state.expire(&self.queue);
+            let Some(message) = state
+                .queues
+                .get_mut(&self.queue)
+                .and_then(|q| q.messages.pop_front())
+            else {
+                return Ok(None);
+            };
+
+            state.next_tag += 1;
+            let tag = state.next_tag;
+            let delivery = Delivery {
+                exchange: message.exchange.clone(),
+                routing_key: message.routing_key.clone(),
+                redelivered: message.redelivered,
+                properties: message.properties.clone(),
+                data: message.data.clone(),
+                acker: Acker::Memory(MemoryAcker {
+                    state: self.state.clone(),
+                    tag,
+                    no_ack: false,
+                }),
+            };
+            state.unacked.insert(
+                tag,
+                Unacked {
+                    queue: self.queue.clone(),
+                    consumer_id: self.browser_id,
+                    message,
+                },
+            );
+            Ok(Some(delivery))
+        })
+    }
+
+    fn close(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            if let Some(state_ref) = self.state.upgrade() {
+                let mut state = state_ref.lock().unwrap();
+                state.requeue_unacked(&self.queue, self.browser_id);
+                state.dispatch(&self.queue, &state_ref);
+            }
+        })
+    }
+}
+
+// 内存broker的订阅
+struct MemorySubscription {
+    state: Weak<Mutex<BrokerState>>,
+    queue: String,
+    consumer_id: u64,
+}
+
+impl SubscriptionControl for MemorySubscription {
+    fn cancel(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(state) = self.state.upgrade() {
+                state
+                    .lock()
+                    .unwrap()
+                    .remove_consumer(&self.queue, self.consumer_id, false);
+            }
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(state_ref) = self.state.upgrade() {
+                let mut state = state_ref.lock().unwrap();
+                state.remove_consumer(&self.queue, self.consumer_id, true);
+                state.dispatch_all(&state_ref);
+            }
+        })
+    }
+}
+
+/// topic路由键匹配，*匹配一个单词，#匹配零个或多个单词
+pub fn topic_matches(pattern: &str, route_key: &str) -> bool {
+    fn matches(pattern: &[&str], words: &[&str]) -> bool {
+        match pattern.split_first() {
+            None => words.is_empty(),
+            Some((&"#", rest)) => (0..=words.len()).any(|i| matches(rest, &words[i..])),
+            Some((&"*", rest)) => !words.is_empty() && matches(rest, &words[1..]),
+            Some((word, rest)) => words.first() == Some(word) && matches(rest, &words[1..]),
+        }
+    }
+
+    let pattern: Vec<&str> = pattern.split('.').collect();
+    let words: Vec<&str> = if route_key.is_empty() {
+        Vec::new()
+    } else {
+        route_key.split('.').collect()
+    };
+    matches(&pattern, &words)
+}
+
+// 读取整数类型的参数
+fn arg_u64(arguments: &FieldTable, key: &str) -> Option<u64> {
+    match arguments.inner().get(key)? {
+        AMQPValue::ShortShortInt(v) => u64::try_from(*v).ok(),
+        AMQPValue::ShortShortUInt(v) => Some(*v as u64),
+        AMQPValue::ShortInt(v) => u64::try_from(*v).ok(),
+        AMQPValue::ShortUInt(v) => Some(*v as u64),
+        AMQPValue::LongInt(v) => u64::try_from(*v).ok(),
+        AMQPValue::LongUInt(v) => Some(*v as u64),
+        AMQPValue::LongLongInt(v) => u64::try_from(*v).ok(),
+        _ => None,
+    }
+}
+
+// 读取布尔类型的参数
+fn arg_bool(arguments: &FieldTable, key: &str) -> bool {
+    matches!(arguments.inner().get(key), Some(AMQPValue::Boolean(true)))
+}
+
+// 读取字符串类型的参数
+fn arg_string(arguments: &FieldTable, key: &str) -> Option<String> {
+    match arguments.inner().get(key)? {
+        AMQPValue::LongString(v) => Some(v.to_string()),
+        AMQPValue::ShortString(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
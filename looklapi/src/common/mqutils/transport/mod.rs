@@ -0,0 +1,263 @@
+#[cfg(test)]
+pub mod memory;
+pub mod rabbitmq;
+
+use crate::common::mqutils::publisher::PublishError;
+use crate::common::mqutils::topology;
+use crate::common::overridable::Overridable;
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use lapin::types::FieldTable;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 消息传输层错误
+#[derive(Debug, thiserror::Error)]
+#[allow(dead_code, reason = "NotFound与ResourceLocked由RabbitMQ的错误码转换而来")]
+pub enum TransportError {
+    /// 无可用的通道
+    #[error("channel unavailable: {0}")]
+    NoChannel(String),
+    /// 队列或交换器不存在
+    #[error("not found: {0}")]
+    NotFound(String),
+    /// 队列被其他连接独占
+    #[error("resource locked: {0}")]
+    ResourceLocked(String),
+    /// broker返回错误
+    #[error("broker error: {0}")]
+    Broker(#[from] lapin::Error),
+}
+
+/// 发布前需要声明的拓扑
+pub enum Declare<'a> {
+    /// 声明队列
+    Queue {
+        name: &'a str,
+        durable: bool,
+        arguments: FieldTable,
+    },
+    /// 声明交换器
+    Exchange {
+        name: &'a str,
+        kind: lapin::ExchangeKind,
+        durable: bool,
+        auto_delete: bool,
+    },
+    /// 不声明，用于发布到其他连接独占的队列(如RPC回复队列)
+    Nothing,
+}
+
+impl Declare<'_> {
+    // 是否已在拓扑中注册，已注册的在连接池初始化时声明过
+    pub(crate) fn registered(&self) -> bool {
+        match self {
+            Declare::Queue { name, .. } => topology::queue(name).is_some(),
+            Declare::Exchange { name, .. } => topology::exchange(name).is_some(),
+            Declare::Nothing => false,
+        }
+    }
+
+    // 声明缓存键，None表示不缓存
+    // 会被broker自动删除的队列与交换器(x-expires、auto_delete)每次都重新声明
+    pub(crate) fn cache_key(&self) -> Option<String> {
+        match self {
+            Declare::Queue {
+                name, arguments, ..
+            } => {
+                if arguments.inner().contains_key("x-expires") {
+                    None
+                } else {
+                    Some(format!("queue:{}", name))
+                }
+            }
+            Declare::Exchange {
+                name, auto_delete, ..
+            } => {
+                if *auto_delete {
+                    None
+                } else {
+                    Some(format!("exchange:{}", name))
+                }
+            }
+            Declare::Nothing => None,
+        }
+    }
+}
+
+/// 待发布的一批消息，发布到同一交换器与路由键
+pub struct Publishing<'a> {
+    pub exchange: &'a str,
+    pub route_key: &'a str,
+    pub declare: Declare<'a>,
+    /// 消息体与消息属性
+    pub messages: Vec<(Vec<u8>, lapin::BasicProperties)>,
+    /// 无法路由时退回
+    pub mandatory: bool,
+    /// 等待broker确认的超时时间
+    pub confirm_timeout: Duration,
+}
+
+/// 订阅时声明的队列
+#[derive(Debug, Clone, Default)]
+pub struct QueueSpec {
+    /// 队列名称，为空时由broker生成临时队列
+    pub name: String,
+    pub durable: bool,
+    pub auto_delete: bool,
+    pub exclusive: bool,
+    pub arguments: FieldTable,
+}
+
+/// 订阅时声明的交换器
+#[derive(Debug, Clone)]
+pub struct ExchangeSpec {
+    pub name: String,
+    pub kind: lapin::ExchangeKind,
+    pub durable: bool,
+    pub auto_delete: bool,
+}
+
+/// 订阅参数
+#[derive(Debug, Clone, Default)]
+pub struct ConsumeSpec {
+    /// 消费的队列
+    pub queue: QueueSpec,
+    /// 是否声明队列，已在拓扑中注册的队列不再声明
+    pub declare_queue: bool,
+    /// 需要声明的交换器
+    pub exchange: Option<ExchangeSpec>,
+    /// 绑定到交换器的路由键，(交换器, 路由键)
+    pub binding: Option<(String, String)>,
+    /// 未确认消息数上限，0表示不限制
+    pub prefetch_count: u16,
+    pub consumer_tag: String,
+    /// 自动确认
+    pub no_ack: bool,
+}
+
+/// 投递的消息
+#[allow(dead_code, reason = "exchange与routing_key供处理器排查消息来源")]
+pub struct Delivery {
+    pub exchange: String,
+    pub routing_key: String,
+    pub redelivered: bool,
+    pub properties: lapin::BasicProperties,
+    pub data: Vec<u8>,
+    acker: Acker,
+}
+
+// 消息确认方式
+enum Acker {
+    Rabbitmq(lapin::acker::Acker),
+    #[cfg(test)]
+    Memory(memory::MemoryAcker),
+}
+
+impl Delivery {
+    /// 确认消息
+    pub async fn ack(&self) -> Result<(), TransportError> {
+        match self.acker {
+            Acker::Rabbitmq(ref acker) => {
+                acker
+                    .ack(lapin::options::BasicAckOptions::default())
+                    .await?;
+            }
+            #[cfg(test)]
+            Acker::Memory(ref acker) => acker.ack(),
+        }
+        Ok(())
+    }
+
+    /// 拒绝消息，requeue为true时重新入队
+    pub async fn nack(&self, requeue: bool) -> Result<(), TransportError> {
+        match self.acker {
+            Acker::Rabbitmq(ref acker) => {
+                acker
+                    .nack(lapin::options::BasicNackOptions {
+                        requeue,
+                        ..Default::default()
+                    })
+                    .await?;
+            }
+            #[cfg(test)]
+            Acker::Memory(ref acker) => acker.nack(requeue),
+        }
+        Ok(())
+    }
+}
+
+impl From<lapin::message::Delivery> for Delivery {
+    fn from(delivery: lapin::message::Delivery) -> Self {
+        Delivery {
+            exchange: delivery.exchange.to_string(),
+            routing_key: delivery.routing_key.to_string(),
+            redelivered: delivery.redelivered,
+            properties: delivery.properties,
+            data: delivery.data,
+            acker: Acker::Rabbitmq(delivery.acker),
+        }
+    }
+}
+
+/// 投递消息流，订阅取消或通道断开时结束
+pub type DeliveryStream = BoxStream<'static, Result<Delivery, TransportError>>;
+
+/// 订阅控制
+pub trait SubscriptionControl: Send + Sync {
+    /// 取消订阅，不再接收新消息，已接收未确认的消息仍可确认
+    fn cancel(&self) -> BoxFuture<'_, ()>;
+
+    /// 释放订阅占用的资源
+    fn close(&self) -> BoxFuture<'_, ()>;
+}
+
+/// 订阅
+pub struct Subscription {
+    /// 实际消费的队列名称
+    pub queue: String,
+    pub deliveries: DeliveryStream,
+    pub control: Box<dyn SubscriptionControl>,
+}
+
+/// 队列浏览，逐条拉取消息而不订阅
+/// 结束浏览时未确认的消息按原顺序重新入队
+pub trait QueueBrowser: Send {
+    /// 拉取下一条消息，队列为空时返回None
+    fn next(&mut self) -> BoxFuture<'_, Result<Option<Delivery>, TransportError>>;
+
+    /// 结束浏览
+    fn close(self: Box<Self>) -> BoxFuture<'static, ()>;
+}
+
+/// 消息传输层
+/// 发布与消费均经由当前安装的传输层，默认为RabbitMQ连接池
+pub trait Transport: Send + Sync {
+    /// 发布一批消息并等待确认
+    /// 外层错误表示未能发布(无通道、声明或发布失败)，内层为每条消息的确认结果
+    fn publish<'a>(
+        &'a self,
+        publishing: Publishing<'a>,
+    ) -> BoxFuture<'a, Result<Vec<Result<(), PublishError>>, PublishError>>;
+
+    /// 声明队列、交换器与绑定后订阅队列
+    fn subscribe(&self, spec: ConsumeSpec) -> BoxFuture<'_, Result<Subscription, TransportError>>;
+
+    /// 浏览已存在的队列
+    fn browse<'a>(
+        &'a self,
+        queue: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn QueueBrowser>, TransportError>>;
+
+    /// 清空已存在的队列中待投递的消息，返回清除的消息数
+    fn purge_queue<'a>(&'a self, queue: &'a str) -> BoxFuture<'a, Result<u32, TransportError>>;
+}
+
+// 当前传输层，默认使用RabbitMQ
+static TRANSPORT: Overridable<dyn Transport> =
+    Overridable::new(|| Arc::new(rabbitmq::RabbitmqTransport));
+
+/// 获取当前传输层，发布与订阅均经由该传输层
+pub fn current() -> Arc<dyn Transport> {
+    TRANSPORT.current()
+}
@@ -0,0 +1,406 @@
+use crate::common::mqutils::models::{ChannelStatus, MqChannel};
+use crate::common::mqutils::publisher::PublishError;
+use crate::common::mqutils::rabbitmq_pool::RabbitmqConnPool;
+use crate::common::mqutils::transport::{
+    ConsumeSpec, Declare, Delivery, Publishing, QueueBrowser, Subscription, SubscriptionControl,
+    Transport, TransportError,
+};
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use lapin::publisher_confirm::Confirmation;
+use lapin::types::FieldTable;
+use std::sync::Arc;
+
+/// 基于RabbitMQ连接池的传输层
+pub struct RabbitmqTransport;
+
+impl Transport for RabbitmqTransport {
+    fn publish<'a>(
+        &'a self,
+        publishing: Publishing<'a>,
+    ) -> BoxFuture<'a, Result<Vec<Result<(), PublishError>>, PublishError>> {
+        Box::pin(publish(publishing))
+    }
+
+    fn subscribe(&self, spec: ConsumeSpec) -> BoxFuture<'_, Result<Subscription, TransportError>> {
+        Box::pin(subscribe(spec))
+    }
+
+    fn browse<'a>(
+        &'a self,
+        queue: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn QueueBrowser>, TransportError>> {
+        Box::pin(async move {
+            let rec_chan = RabbitmqConnPool::get_instance()
+                .get_rec_channel()
+                .await
+                .map_err(|err| TransportError::NoChannel(err.to_string()))?;
+            Ok(Box::new(RabbitmqBrowser {
+                rec_chan,
+                queue: queue.to_string(),
+            }) as Box<dyn QueueBrowser>)
+        })
+    }
+
+    fn purge_queue<'a>(&'a self, queue: &'a str) -> BoxFuture<'a, Result<u32, TransportError>> {
+        Box::pin(async move {
+            let pool = RabbitmqConnPool::get_instance();
+            let rec_chan = pool
+                .get_rec_channel()
+                .await
+                .map_err(|err| TransportError::NoChannel(err.to_string()))?;
+            let result = rec_chan
+                .channel
+                .queue_purge(queue, lapin::options::QueuePurgeOptions::default())
+                .await;
+            pool.discard_rec_channel(&rec_chan).await;
+            Ok(result?)
+        })
+    }
+}
+
+// 通过同一发布通道发布多条消息，统一等待broker确认
+// 发布通道均处于confirm模式，通道出错时标记为关闭，由定时任务清理
+async fn publish(
+    publishing: Publishing<'_>,
+) -> Result<Vec<Result<(), PublishError>>, PublishError> {
+    let Publishing {
+        exchange,
+        route_key,
+        declare,
+        messages,
+        mandatory,
+        confirm_timeout,
+    } = publishing;
+    let total = messages.len();
+
+    let pool = RabbitmqConnPool::get_instance();
+    let pub_chan = pool
+        .get_pub_channel()
+        .await
+        .map_err(|err| PublishError::NoChannel(err.to_string()))?;
+
+    let declare = if declare.registered() {
+        Declare::Nothing
+    } else {
+        declare
+    };
+    let declare_key = declare.cache_key();
+
+    let result = async {
+        declare_once(&pub_chan, declare).await?;
+
+        let mut confirms = Vec::with_capacity(messages.len());
+        for (payload, properties) in messages {
+            let confirm = pub_chan
+                .channel
+                .basic_publish(
+                    exchange,
+                    route_key,
+                    lapin::options::BasicPublishOptions {
+                        mandatory,
+                        ..Default::default()
+                    },
+                    &payload,
+                    properties,
+                )
+                .await?;
+            confirms.push(confirm);
+        }
+
+        let confirmations =
+            tokio::time::timeout(confirm_timeout, futures::future::join_all(confirms))
+                .await
+                .map_err(|_| PublishError::ConfirmTimeout)?;
+        Ok(confirmations)
+    }
+    .await;
+
+    match result {
+        Ok(confirmations) => {
+            pool.release_channel(pub_chan);
+            let results: Vec<Result<(), PublishError>> = confirmations
+                .into_iter()
+                .map(|confirmation| match confirmation {
+                    Ok(confirmation) => check_confirmation(exchange, route_key, confirmation),
+                    Err(err) => Err(err.into()),
+                })
+                .collect();
+            let failed = results.iter().filter(|result| result.is_err()).count();
+            pool.record_publish(results.len(), failed);
+            Ok(results)
+        }
+        Err(err) => {
+            pool.record_publish(total, total);
+            if matches!(err, PublishError::Broker(_)) {
+                // 声明缓存可能已失效(如自动删除的交换器已被删除)，下次重新声明
+                if let Some(ref key) = declare_key {
+                    pub_chan.conn.forget_declared(key);
+                }
+                pub_chan.set_status(ChannelStatus::Close);
+            } else {
+                pool.release_channel(pub_chan);
+            }
+            Err(err)
+        }
+    }
+}
+
+// 声明队列或交换器，同一连接内已声明过的不再重复声明
+async fn declare_once(pub_chan: &MqChannel, declare: Declare<'_>) -> Result<(), lapin::Error> {
+    let key = declare.cache_key();
+    if let Some(ref key) = key
+        && pub_chan.conn.is_declared(key)
+    {
+        return Ok(());
+    }
+
+    match declare {
+        Declare::Queue {
+            name,
+            durable,
+            arguments,
+        } => {
+            pub_chan
+                .channel
+                .queue_declare(
+                    name,
+                    lapin::options::QueueDeclareOptions {
+                        durable,
+                        auto_delete: false,
+                        exclusive: false,
+                        ..Default::default()
+                    },
+                    arguments,
+                )
+                .await?;
+        }
+        Declare::Exchange {
+            name,
+            kind,
+            durable,
+            auto_delete,
+        } => {
+            pub_chan
+                .channel
+                .exchange_declare(
+                    name,
+                    kind,
+                    lapin::options::ExchangeDeclareOptions {
+                        durable,
+                        auto_delete,
+                        internal: false,
+                        ..Default::default()
+                    },
+                    FieldTable::default(),
+                )
+                .await?;
+        }
+        Declare::Nothing => {}
+    }
+
+    if let Some(key) = key {
+        pub_chan.conn.mark_declared(key);
+    }
+    Ok(())
+}
+
+// 检查broker确认结果
+fn check_confirmation(
+    exchange: &str,
+    route_key: &str,
+    confirmation: Confirmation,
+) -> Result<(), PublishError> {
+    match confirmation {
+        Confirmation::Ack(Some(returned)) => {
+            tracing::warn!(
+                "消息无法路由被退回: exchange={}, route_key={}, reply_code={}, reply_text={}",
+                exchange,
+                route_key,
+                returned.reply_code,
+                returned.reply_text
+            );
+            Err(PublishError::Unroutable {
+                reply_code: returned.reply_code,
+                reply_text: returned.reply_text.to_string(),
+            })
+        }
+        Confirmation::Ack(None) | Confirmation::NotRequested => Ok(()),
+        Confirmation::Nack(_) => {
+            tracing::warn!(
+                "消息被broker拒绝: exchange={}, route_key={}",
+                exchange,
+                route_key
+            );
+            Err(PublishError::Nack)
+        }
+    }
+}
+
+// 获取消费通道并执行声明与订阅，失败时丢弃该通道
+async fn subscribe(spec: ConsumeSpec) -> Result<Subscription, TransportError> {
+    let pool = RabbitmqConnPool::get_instance();
+    let rec_chan = pool
+        .get_rec_channel()
+        .await
+        .map_err(|err| TransportError::NoChannel(err.to_string()))?;
+
+    match setup(&rec_chan, &spec).await {
+        Ok((queue, consumer)) => {
+            let deliveries = consumer
+                .map(|delivery| delivery.map(Delivery::from).map_err(TransportError::from))
+                .boxed();
+            Ok(Subscription {
+                queue,
+                deliveries,
+                control: Box::new(RabbitmqSubscription {
+                    rec_chan,
+                    consumer_tag: spec.consumer_tag,
+                }),
+            })
+        }
+        Err(err) => {
+            pool.discard_rec_channel(&rec_chan).await;
+            Err(err.into())
+        }
+    }
+}
+
+// 声明交换器、队列与绑定，设置QoS后订阅，返回实际的队列名称
+async fn setup(
+    rec_chan: &MqChannel,
+    spec: &ConsumeSpec,
+) -> Result<(String, lapin::Consumer), lapin::Error> {
+    if let Some(ref exchange) = spec.exchange {
+        rec_chan
+            .channel
+            .exchange_declare(
+                &exchange.name,
+                exchange.kind.clone(),
+                lapin::options::ExchangeDeclareOptions {
+                    durable: exchange.durable,
+                    auto_delete: exchange.auto_delete,
+                    internal: false,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await?;
+    }
+
+    let queue_name = if spec.declare_queue {
+        let queue = rec_chan
+            .channel
+            .queue_declare(
+                &spec.queue.name,
+                lapin::options::QueueDeclareOptions {
+                    durable: spec.queue.durable,
+                    auto_delete: spec.queue.auto_delete,
+                    exclusive: spec.queue.exclusive,
+                    ..Default::default()
+                },
+                spec.queue.arguments.clone(),
+            )
+            .await?;
+        queue.name().to_string()
+    } else {
+        spec.queue.name.clone()
+    };
+
+    if let Some((ref exchange, ref route_key)) = spec.binding {
+        rec_chan
+            .channel
+            .queue_bind(
+                &queue_name,
+                exchange,
+                route_key,
+                lapin::options::QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+    }
+
+    if spec.prefetch_count > 0 {
+        rec_chan
+            .channel
+            .basic_qos(
+                spec.prefetch_count,
+                lapin::options::BasicQosOptions::default(),
+            )
+            .await?;
+    }
+
+    let consumer = rec_chan
+        .channel
+        .basic_consume(
+            &queue_name,
+            &spec.consumer_tag,
+            lapin::options::BasicConsumeOptions {
+                no_ack: spec.no_ack,
+                exclusive: false,
+                no_local: false,
+                ..Default::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+
+    Ok((queue_name, consumer))
+}
+
+// RabbitMQ订阅，占用一个消费通道
+struct RabbitmqSubscription {
+    rec_chan: Arc<MqChannel>,
+    consumer_tag: String,
+}
+
+impl SubscriptionControl for RabbitmqSubscription {
+    fn cancel(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            let _ = self
+                .rec_chan
+                .channel
+                .basic_cancel(
+                    &self.consumer_tag,
+                    lapin::options::BasicCancelOptions::default(),
+                )
+                .await;
+        })
+    }
+
+    fn close(&self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            RabbitmqConnPool::get_instance()
+                .discard_rec_channel(&self.rec_chan)
+                .await;
+        })
+    }
+}
+
+// RabbitMQ队列浏览，独占一个消费通道
+// 通道关闭时broker将未确认的消息重新入队
+struct RabbitmqBrowser {
+    rec_chan: Arc<MqChannel>,
+    queue: String,
+}
+
+impl QueueBrowser for RabbitmqBrowser {
+    fn next(&mut self) -> BoxFuture<'_, Result<Option<Delivery>, TransportError>> {
+        Box::pin(async move {
+            let message = self
+                .rec_chan
+                .channel
+                .basic_get(&self.queue, lapin::options::BasicGetOptions { no_ack: false })
+                .await?;
+            Ok(message.map(|message| Delivery::from(message.delivery)))
+        })
+    }
+
+    fn close(self: Box<Self>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            RabbitmqConnPool::get_instance()
+                .discard_rec_channel(&self.rec_chan)
+                .await;
+        })
+    }
+}
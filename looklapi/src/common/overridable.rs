@@ -0,0 +1,41 @@
+use std::sync::{Arc, OnceLock};
+#[cfg(test)]
+use std::sync::RwLock;
+
+/// 可替换的全局实现，用于传输层与各类存储
+/// 默认实现在首次使用时创建并一直复用，测试中可安装内存实现替代
+pub struct Overridable<T: ?Sized> {
+    // 默认实现的构造函数
+    init: fn() -> Arc<T>,
+    // 默认实现
+    default: OnceLock<Arc<T>>,
+    // 测试安装的替代实现
+    #[cfg(test)]
+    installed: RwLock<Option<Arc<T>>>,
+}
+
+impl<T: ?Sized> Overridable<T> {
+    pub const fn new(init: fn() -> Arc<T>) -> Self {
+        Self {
+            init,
+            default: OnceLock::new(),
+            #[cfg(test)]
+            installed: RwLock::new(None),
+        }
+    }
+
+    /// 获取当前实现
+    pub fn current(&self) -> Arc<T> {
+        #[cfg(test)]
+        if let Some(ref installed) = *self.installed.read().unwrap() {
+            return installed.clone();
+        }
+        self.default.get_or_init(self.init).clone()
+    }
+
+    /// 安装替代实现，之后current均返回该实现
+    #[cfg(test)]
+    pub fn install(&self, value: Arc<T>) {
+        *self.installed.write().unwrap() = Some(value);
+    }
+}
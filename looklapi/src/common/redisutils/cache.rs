@@ -0,0 +1,681 @@
+use super::key::RedisKey;
+use super::locker::{LockOptions, RedisLock};
+use super::operator::{json_to_obj, obj_to_json};
+use super::redipool::get_conn;
+use crate::common::mqutils::consumer::{self, Consumer};
+use crate::common::mqutils::handler::ConsumeError;
+use crate::common::mqutils::publisher;
+use crate::common::overridable::Overridable;
+use futures::future::BoxFuture;
+use lazy_static::lazy_static;
+use redis::{AsyncCommands, RedisResult};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Once, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 缓存失效广播的交换器
+pub const CACHE_INVALIDATE_EXCHANGE: &str = "cache_invalidate";
+
+/// 空值标记，不是合法的json，不会与缓存的值冲突
+const NULL_MARKER: &str = "__cache_null__";
+/// 加载锁key后缀
+const LOCK_SUFFIX: &str = "_cache_lock";
+/// 等待其他实例加载时的轮询间隔
+const WAIT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 默认空值缓存时间
+const DEFAULT_NEGATIVE_TTL: Duration = Duration::from_secs(60);
+/// 默认过期时间抖动比例
+const DEFAULT_JITTER: f64 = 0.1;
+/// 默认加载锁过期时间
+const DEFAULT_LOCK_TTL: Duration = Duration::from_secs(5);
+/// 默认等待其他实例加载的时间
+const DEFAULT_LOCK_WAIT: Duration = Duration::from_secs(3);
+/// 默认本地缓存容量
+const DEFAULT_LOCAL_CAPACITY: usize = 10000;
+/// 默认本地缓存过期时间
+const DEFAULT_LOCAL_TTL: Duration = Duration::from_secs(60);
+
+/// 读穿缓存配置
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    /// 缓存过期时间
+    pub ttl: Duration,
+    /// 过期时间抖动比例，实际过期时间在ttl*(1±jitter)内随机，避免同时过期
+    pub jitter: f64,
+    /// 加载结果为空时的缓存时间，为0时不缓存空值
+    pub negative_ttl: Duration,
+    /// 加载锁过期时间，应大于加载耗时
+    pub lock_ttl: Duration,
+    /// 其他实例正在加载时的最长等待时间，超时后自行加载
+    pub lock_wait: Duration,
+    /// 是否使用进程内的本地缓存，需在redis配置中开启local_cache，未开启时不生效
+    pub local: bool,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(300),
+            jitter: DEFAULT_JITTER,
+            negative_ttl: DEFAULT_NEGATIVE_TTL,
+            lock_ttl: DEFAULT_LOCK_TTL,
+            lock_wait: DEFAULT_LOCK_WAIT,
+            local: false,
+        }
+    }
+}
+
+#[allow(dead_code, reason = "缓存选项的构造方法，由业务代码按需调用")]
+impl CacheOptions {
+    /// 设置缓存过期时间
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// 设置过期时间抖动比例
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// 设置空值缓存时间
+    pub fn negative_ttl(mut self, negative_ttl: Duration) -> Self {
+        self.negative_ttl = negative_ttl;
+        self
+    }
+
+    /// 设置加载锁过期时间与等待时间
+    pub fn lock(mut self, lock_ttl: Duration, lock_wait: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self.lock_wait = lock_wait;
+        self
+    }
+
+    /// 开启或关闭本地缓存
+    pub fn local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+
+    // 加抖动后的过期时间
+    fn jittered(&self, ttl: Duration) -> Duration {
+        let ms = ttl.as_millis() as u64;
+        let spread = (ms as f64 * self.jitter) as u64;
+        if spread == 0 {
+            return ttl;
+        }
+        let offset = (uuid::Uuid::new_v4().as_u128() as u64) % (spread * 2 + 1);
+        Duration::from_millis((ms + offset).saturating_sub(spread).max(1))
+    }
+}
+
+/// 缓存存储
+pub trait CacheStore: Send + Sync {
+    /// 获取缓存值
+    fn get<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<Option<String>>>;
+
+    /// 写入缓存值
+    fn set<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        value: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<()>>;
+
+    /// 删除缓存值
+    fn del<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<()>>;
+
+    /// 获取加载锁，获取成功时返回的guard被丢弃时释放锁
+    fn lock<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<Option<Box<dyn Any + Send>>>>;
+}
+
+// 当前缓存存储，默认使用redis
+static STORE: Overridable<dyn CacheStore> = Overridable::new(|| Arc::new(RedisCacheStore));
+
+lazy_static! {
+    // 进程内正在加载的key
+    static ref FLIGHTS: Mutex<HashMap<RedisKey, Arc<tokio::sync::Mutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 获取当前缓存存储
+pub fn current() -> Arc<dyn CacheStore> {
+    STORE.current()
+}
+
+/// 基于redis的缓存存储
+pub struct RedisCacheStore;
+
+impl CacheStore for RedisCacheStore {
+    fn get<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<Option<String>>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            conn.get(key).await
+        })
+    }
+
+    fn set<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        value: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            conn.pset_ex(key, value, ttl.as_millis().max(1) as u64)
+                .await
+        })
+    }
+
+    fn del<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<()>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            conn.del(key).await
+        })
+    }
+
+    fn lock<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<Option<Box<dyn Any + Send>>>> {
+        Box::pin(async move {
+            let lock = RedisLock::acquire(key, &LockOptions::default().ttl(ttl)).await?;
+            Ok(lock.map(|lock| Box::new(lock) as Box<dyn Any + Send>))
+        })
+    }
+}
+
+/// 本地缓存，容量与过期时间有上限
+/// 本地缓存的值在其他实例更新后可能短暂不一致，失效时通过广播通知所有实例
+pub(crate) struct LocalCache {
+    capacity: usize,
+    ttl: Duration,
+    /// key -> 缓存项
+    entries: HashMap<RedisKey, LocalEntry>,
+    /// 按过期时间排序的索引，(过期时间, 序号) -> key，用于淘汰
+    expiries: BTreeMap<(Instant, u64), RedisKey>,
+    /// 下一个缓存项的序号，区分过期时间相同的缓存项
+    next_seq: u64,
+}
+
+struct LocalEntry {
+    json: String,
+    expires_at: Instant,
+    seq: u64,
+}
+
+impl LocalCache {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            expiries: BTreeMap::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &RedisKey) -> Option<String> {
+        match self.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.json.clone()),
+            Some(_) => {
+                self.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    // 已满时淘汰最早过期的一项，已过期的项总是最先被淘汰
+    pub(crate) fn insert(&mut self, key: &RedisKey, json: &str, ttl: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(key);
+        if self.entries.len() >= self.capacity
+            && let Some((_, oldest)) = self.expiries.pop_first()
+        {
+            self.entries.remove(&oldest);
+        }
+
+        let expires_at = Instant::now() + ttl.min(self.ttl);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.expiries.insert((expires_at, seq), key.clone());
+        self.entries.insert(
+            key.clone(),
+            LocalEntry {
+                json: json.to_string(),
+                expires_at,
+                seq,
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, key: &RedisKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.expiries.remove(&(entry.expires_at, entry.seq));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.expiries.clear();
+    }
+}
+
+lazy_static! {
+    static ref LOCAL: Mutex<LocalCache> =
+        Mutex::new(LocalCache::new(DEFAULT_LOCAL_CAPACITY, DEFAULT_LOCAL_TTL));
+}
+
+/// 设置本地缓存的容量与最长过期时间，会清空已有的本地缓存
+#[allow(dead_code, reason = "由业务代码在启动时按需调整本地缓存")]
+pub fn configure_local(capacity: usize, ttl: Duration) {
+    let mut local = LOCAL.lock().unwrap();
+    local.capacity = capacity;
+    local.ttl = ttl;
+    local.clear();
+}
+
+/// 缓存失效通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheInvalidation {
+    /// 失效的key，包含所在的db
+    pub keys: Vec<RedisKey>,
+}
+
+// 是否允许使用本地缓存
+static LOCAL_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// 开启或关闭本地缓存
+/// 开启后CacheOptions::local生效，失效时广播通知所有实例，各实例的配置应一致
+pub fn set_local_enabled(enabled: bool) {
+    LOCAL_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否允许使用本地缓存
+pub fn local_enabled() -> bool {
+    LOCAL_ENABLED.load(Ordering::Relaxed)
+}
+
+// 是否使用本地缓存，首次使用时创建接收失效通知的消费者
+// 未使用本地缓存的服务不创建消费者
+fn use_local(options: &CacheOptions) -> bool {
+    static INVALIDATE_CONSUMER: OnceLock<Arc<Consumer>> = OnceLock::new();
+    static DISABLED_WARNING: Once = Once::new();
+
+    if !options.local {
+        return false;
+    }
+    if !local_enabled() {
+        DISABLED_WARNING.call_once(|| {
+            tracing::warn!("本地缓存未开启，需在redis配置中设置local_cache = true");
+        });
+        return false;
+    }
+
+    // 各实例接收失效通知，清除本地缓存
+    INVALIDATE_CONSUMER.get_or_init(|| {
+        consumer::add_consumer(|| {
+            Consumer::broadcast(
+                CACHE_INVALIDATE_EXCHANGE,
+                1,
+                |msg: CacheInvalidation| async move {
+                    invalidate_local(&msg.keys);
+                    Ok::<(), ConsumeError>(())
+                },
+            )
+        })
+    });
+    true
+}
+
+/// 读穿缓存，使用默认配置
+#[allow(dead_code, reason = "读穿缓存接口，由业务代码与缓存宏调用")]
+pub async fn get_or_load<T, F, Fut, E>(
+    key: impl Into<RedisKey>,
+    ttl: Duration,
+    loader: F,
+) -> Result<Option<T>, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Option<T>, E>>,
+{
+    get_or_load_with_options(key, &CacheOptions::default().ttl(ttl), loader).await
+}
+
+/// 读穿缓存
+/// 依次读取本地缓存、redis，都未命中时调用loader加载并写入缓存
+/// 同一进程内同一key同时只有一个加载，跨实例通过加载锁避免同时加载
+/// 缓存不可用时直接调用loader，只有loader的错误会返回
+#[allow(dead_code, reason = "读穿缓存接口，由业务代码与缓存宏调用")]
+pub async fn get_or_load_with_options<T, F, Fut, E>(
+    key: impl Into<RedisKey>,
+    options: &CacheOptions,
+    loader: F,
+) -> Result<Option<T>, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<Option<T>, E>>,
+{
+    let key: &RedisKey = &key.into();
+    if let Some(value) = lookup(key, options).await {
+        return Ok(value);
+    }
+
+    // 同一进程内等待正在进行的加载，完成后重新读取缓存
+    let _flight = Flight::enter(key).await;
+    if let Some(value) = lookup(key, options).await {
+        return Ok(value);
+    }
+
+    let store = current();
+    let lock_key = key.sibling(format!("{}{}", key, LOCK_SUFFIX));
+    let _lock = match store.lock(&lock_key, options.lock_ttl).await {
+        Ok(Some(lock)) => {
+            // 其他实例可能在上次读取后完成加载并释放了锁
+            if let Some(value) = lookup(key, options).await {
+                return Ok(value);
+            }
+            Some(lock)
+        }
+        Ok(None) => {
+            // 其他实例正在加载，等待其写入缓存
+            let deadline = Instant::now() + options.lock_wait;
+            while Instant::now() < deadline {
+                tokio::time::sleep(WAIT_INTERVAL).await;
+                if let Some(value) = lookup(key, options).await {
+                    return Ok(value);
+                }
+            }
+            None
+        }
+        Err(err) => {
+            tracing::warn!("缓存加载锁获取失败: {} - {}", key, err);
+            None
+        }
+    };
+
+    let value = loader().await?;
+    fill(key, options, value.as_ref()).await;
+    Ok(value)
+}
+
+/// 删除缓存，开启本地缓存时通知所有实例清除本地缓存
+#[allow(dead_code, reason = "缓存失效接口，由业务代码与缓存宏调用")]
+pub async fn invalidate<K: Clone + Into<RedisKey>>(keys: &[K]) -> RedisResult<()> {
+    let keys: Vec<RedisKey> = keys.iter().map(|key| key.clone().into()).collect();
+    let store = current();
+    for key in &keys {
+        store.del(key).await?;
+    }
+
+    invalidate_local(&keys);
+    if !local_enabled() {
+        return Ok(());
+    }
+    let msg = CacheInvalidation { keys };
+    if let Err(err) = publisher::pub_broadcast_msg(CACHE_INVALIDATE_EXCHANGE, &msg).await {
+        tracing::warn!("缓存失效通知发送失败: {:?} - {}", msg.keys, err);
+    }
+    Ok(())
+}
+
+/// 清除本实例的本地缓存
+pub fn invalidate_local<K: Clone + Into<RedisKey>>(keys: &[K]) {
+    let mut local = LOCAL.lock().unwrap();
+    for key in keys {
+        local.remove(&key.clone().into());
+    }
+}
+
+/// 读取缓存值，未命中、缓存不可用或无法解析时返回None，用于#[cached]
+#[allow(dead_code, reason = "供缓存宏展开后调用")]
+pub async fn get<T: DeserializeOwned>(key: &RedisKey) -> Option<T> {
+    let json = match current().get(key).await {
+        Ok(json) => json?,
+        Err(err) => {
+            tracing::warn!("缓存读取失败: {} - {}", key, err);
+            return None;
+        }
+    };
+    decode(key, &json).flatten()
+}
+
+/// 写入缓存值，失败时只记录日志，用于#[cached]
+#[allow(dead_code, reason = "供缓存宏展开后调用")]
+pub async fn set<T: Serialize>(key: &RedisKey, value: &T, ttl: Duration) {
+    let json = match obj_to_json(value) {
+        Ok(json) => json,
+        Err(err) => {
+            tracing::warn!("缓存序列化失败: {} - {}", key, err);
+            return;
+        }
+    };
+    if let Err(err) = current().set(key, &json, ttl).await {
+        tracing::warn!("缓存写入失败: {} - {}", key, err);
+    }
+}
+
+/// 删除缓存值，不通知其他实例清除本地缓存，失败时只记录日志，用于#[cache_evict]
+#[allow(dead_code, reason = "供缓存宏展开后调用")]
+pub async fn del(key: &RedisKey) {
+    if let Err(err) = current().del(key).await {
+        tracing::warn!("缓存删除失败: {} - {}", key, err);
+    }
+}
+
+// 读取缓存，命中时返回Some，命中空值时返回Some(None)
+async fn lookup<T: DeserializeOwned>(key: &RedisKey, options: &CacheOptions) -> Option<Option<T>> {
+    let local = use_local(options);
+    if local && let Some(json) = LOCAL.lock().unwrap().get(key) {
+        return decode(key, &json);
+    }
+
+    let json = match current().get(key).await {
+        Ok(json) => json?,
+        Err(err) => {
+            tracing::warn!("缓存读取失败: {} - {}", key, err);
+            return None;
+        }
+    };
+    let value = decode(key, &json)?;
+    if local {
+        let ttl = if value.is_some() {
+            options.ttl
+        } else {
+            options.negative_ttl
+        };
+        LOCAL.lock().unwrap().insert(key, &json, ttl);
+    }
+    Some(value)
+}
+
+// 写入加载结果
+async fn fill<T: Serialize>(key: &RedisKey, options: &CacheOptions, value: Option<&T>) {
+    let (json, ttl) = match value {
+        Some(value) => match obj_to_json(value) {
+            Ok(json) => (json, options.jittered(options.ttl)),
+            Err(err) => {
+                tracing::warn!("缓存序列化失败: {} - {}", key, err);
+                return;
+            }
+        },
+        None if options.negative_ttl.is_zero() => return,
+        None => (
+            NULL_MARKER.to_string(),
+            options.jittered(options.negative_ttl),
+        ),
+    };
+
+    if let Err(err) = current().set(key, &json, ttl).await {
+        tracing::warn!("缓存写入失败: {} - {}", key, err);
+    }
+    if use_local(options) {
+        LOCAL.lock().unwrap().insert(key, &json, ttl);
+    }
+}
+
+// 解析缓存值，无法解析时视为未命中
+fn decode<T: DeserializeOwned>(key: &RedisKey, json: &str) -> Option<Option<T>> {
+    if json == NULL_MARKER {
+        return Some(None);
+    }
+    match json_to_obj(json) {
+        Ok(value) => Some(Some(value)),
+        Err(err) => {
+            tracing::warn!("缓存反序列化失败: {} - {}", key, err);
+            None
+        }
+    }
+}
+
+// 进程内的单次加载，同一key的加载依次进行
+struct Flight {
+    key: RedisKey,
+    entry: Arc<tokio::sync::Mutex<()>>,
+    _guard: tokio::sync::OwnedMutexGuard<()>,
+}
+
+impl Flight {
+    async fn enter(key: &RedisKey) -> Self {
+        let entry = FLIGHTS
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let guard = entry.clone().lock_owned().await;
+        Flight {
+            key: key.clone(),
+            entry,
+            _guard: guard,
+        }
+    }
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        // 没有其他等待者时移除，map、当前加载与持有的锁共三个引用
+        let mut flights = FLIGHTS.lock().unwrap();
+        if Arc::strong_count(&self.entry) <= 3 {
+            flights.remove(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryCacheStore;
+
+// 测试用的内存实现
+#[cfg(test)]
+mod memory {
+    use super::{CacheStore, STORE};
+    use crate::common::redisutils::key::RedisKey;
+    use crate::common::testutil;
+    use futures::future::BoxFuture;
+    use redis::RedisResult;
+    use std::any::Any;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    /// 进程内的内存缓存存储，用于在没有redis的环境下测试
+    #[derive(Default)]
+    pub struct MemoryCacheStore {
+        /// key -> (值, 过期时间)
+        entries: Arc<Mutex<HashMap<RedisKey, (String, Instant)>>>,
+    }
+
+    impl MemoryCacheStore {
+        /// 安装进程内共享的内存缓存存储，重复调用返回同一实例
+        pub fn install() -> Arc<Self> {
+            let store = testutil::shared(Arc::<Self>::default);
+            STORE.install(store.clone());
+            store
+        }
+
+        // 获取未过期的值
+        fn value(&self, key: &RedisKey) -> Option<String> {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .get(key)
+                .filter(|(_, expires_at)| *expires_at > Instant::now())
+                .map(|(value, _)| value.clone())
+        }
+    }
+
+    // 内存加载锁，丢弃时删除
+    struct MemoryLockGuard {
+        key: RedisKey,
+        entries: Arc<Mutex<HashMap<RedisKey, (String, Instant)>>>,
+    }
+
+    impl Drop for MemoryLockGuard {
+        fn drop(&mut self) {
+            self.entries.lock().unwrap().remove(&self.key);
+        }
+    }
+
+    impl CacheStore for MemoryCacheStore {
+        fn get<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<Option<String>>> {
+            Box::pin(async move { Ok(self.value(key)) })
+        }
+
+        fn set<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            value: &'a str,
+            ttl: Duration,
+        ) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                entries.insert(key.clone(), (value.to_string(), Instant::now() + ttl));
+                Ok(())
+            })
+        }
+
+        fn del<'a>(&'a self, key: &'a RedisKey) -> BoxFuture<'a, RedisResult<()>> {
+            Box::pin(async move {
+                self.entries.lock().unwrap().remove(key);
+                Ok(())
+            })
+        }
+
+        fn lock<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            ttl: Duration,
+        ) -> BoxFuture<'a, RedisResult<Option<Box<dyn Any + Send>>>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let now = Instant::now();
+                if entries
+                    .get(key)
+                    .is_some_and(|(_, expires_at)| *expires_at > now)
+                {
+                    return Ok(None);
+                }
+                entries.insert(key.clone(), (String::new(), now + ttl));
+                Ok(Some(Box::new(MemoryLockGuard {
+                    key: key.clone(),
+                    entries: self.entries.clone(),
+                }) as Box<dyn Any + Send>))
+            })
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use redis::{RedisWrite, ToRedisArgs, ToSingleRedisArg};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 最大db索引
+pub const MAX_DB_INDEX: u8 = 15;
+
+// 是否按旧的"N_"前缀解析未指定db的key
+static LEGACY_DB_PREFIX: AtomicBool = AtomicBool::new(false);
+
+/// 开启或关闭旧的db前缀解析
+/// 开启后未指定db的key按"N_"前缀(N不大于MAX_DB_INDEX)选择db，前缀保留在key中
+/// 关闭时未指定db的key都使用db 0，只影响之后构造的key
+pub fn set_legacy_db_prefix(enabled: bool) {
+    LEGACY_DB_PREFIX.store(enabled, Ordering::Relaxed);
+}
+
+/// 是否开启了旧的db前缀解析
+pub fn legacy_db_prefix() -> bool {
+    LEGACY_DB_PREFIX.load(Ordering::Relaxed)
+}
+
+/// 解析"N_"前缀中的db索引，没有前缀或超出范围时返回None
+/// 例如：5_op_shop_stock 为db 5，12_foo 为db 12，13800000000 没有前缀
+pub fn parse_db_prefix(key: &str) -> Option<u8> {
+    let (prefix, _) = key.split_once('_')?;
+    if prefix.is_empty() || prefix.len() > 2 || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    prefix.parse().ok().filter(|db| *db <= MAX_DB_INDEX)
+}
+
+/// redis key及其所在的db
+/// 由字符串转换时在构造时确定db: 使用db 0，开启旧的db前缀解析后按前缀选择db
+/// db与key字符串都相同时视为同一个key
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RedisKey {
+    db: u8,
+    key: String,
+}
+
+#[allow(dead_code, reason = "key的构造与访问方法，由业务代码按需调用")]
+impl RedisKey {
+    /// 指定db的key
+    pub fn new(db: u8, key: impl Into<String>) -> Self {
+        Self {
+            db,
+            key: key.into(),
+        }
+    }
+
+    /// 按"N_"前缀选择db的key，不受旧的db前缀解析开关影响
+    pub fn with_db_prefix(key: impl Into<String>) -> Self {
+        let key = key.into();
+        Self {
+            db: parse_db_prefix(&key).unwrap_or(0),
+            key,
+        }
+    }
+
+    /// key所在的db
+    pub fn db(&self) -> u8 {
+        self.db
+    }
+
+    /// key字符串
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.key.is_empty()
+    }
+
+    /// 同一db下的另一个key
+    pub fn sibling(&self, key: impl Into<String>) -> Self {
+        Self::new(self.db, key)
+    }
+}
+
+impl From<&str> for RedisKey {
+    fn from(key: &str) -> Self {
+        Self::from(key.to_string())
+    }
+}
+
+impl From<String> for RedisKey {
+    fn from(key: String) -> Self {
+        let db = if legacy_db_prefix() {
+            parse_db_prefix(&key).unwrap_or(0)
+        } else {
+            0
+        };
+        Self { db, key }
+    }
+}
+
+impl From<&String> for RedisKey {
+    fn from(key: &String) -> Self {
+        Self::from(key.as_str())
+    }
+}
+
+impl From<&RedisKey> for RedisKey {
+    fn from(key: &RedisKey) -> Self {
+        key.clone()
+    }
+}
+
+impl fmt::Display for RedisKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key)
+    }
+}
+
+impl ToRedisArgs for RedisKey {
+    fn write_redis_args<W>(&self, out: &mut W)
+    where
+        W: ?Sized + RedisWrite,
+    {
+        self.key.write_redis_args(out)
+    }
+}
+
+impl ToSingleRedisArg for RedisKey {}
+
+/// 构造RedisKey
+/// redis_key!(db = 5, "user:{}", id) 指定db，redis_key!("user:{}", id) 未指定db
+#[macro_export]
+macro_rules! redis_key {
+    (db = $db:expr, $($fmt:tt)+) => {
+        $crate::common::redisutils::key::RedisKey::new($db, ::std::format!($($fmt)+))
+    };
+    ($($fmt:tt)+) => {
+        $crate::common::redisutils::key::RedisKey::from(::std::format!($($fmt)+))
+    };
+}
@@ -1,10 +1,12 @@
+use super::key::RedisKey;
 use super::operator::{json_to_obj, obj_to_json};
 use super::redipool::get_conn;
-use redis::{Commands, RedisError, RedisResult};
+use redis::{AsyncCommands, RedisError, RedisResult};
 use serde::{Serialize, de::DeserializeOwned};
 
 // 向列表头(左端)push数据
-pub async fn lpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize> {
+pub async fn lpush<T: Serialize>(key: impl Into<RedisKey>, values: &[T]) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -24,7 +26,7 @@ pub async fn lpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize>
         pipe.lpush(key, val_json);
     }
 
-    let results: Vec<usize> = pipe.query(&mut conn)?;
+    let results: Vec<usize> = pipe.query_async(&mut conn).await?;
     results
         .last()
         .copied()
@@ -32,7 +34,8 @@ pub async fn lpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize>
 }
 
 // 向列表尾(右端)push数据
-pub async fn rpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize> {
+pub async fn rpush<T: Serialize>(key: impl Into<RedisKey>, values: &[T]) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -52,7 +55,7 @@ pub async fn rpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize>
         pipe.rpush(key, val_json);
     }
 
-    let results: Vec<usize> = pipe.query(&mut conn)?;
+    let results: Vec<usize> = pipe.query_async(&mut conn).await?;
     results
         .last()
         .copied()
@@ -60,7 +63,8 @@ pub async fn rpush<T: Serialize>(key: &str, values: &[T]) -> RedisResult<usize>
 }
 
 // 移除并返回表头(左端)数据
-pub async fn lpop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
+pub async fn lpop<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -69,12 +73,13 @@ pub async fn lpop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
     }
 
     let mut conn = get_conn(key).await?;
-    let val_json: String = conn.lpop(key, None)?;
+    let val_json: String = conn.lpop(key, None).await?;
     json_to_obj(&val_json).map_err(|e| RedisError::from(e))
 }
 
 // 移除并返回表尾(右端)数据
-pub async fn rpop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
+pub async fn rpop<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -83,29 +88,39 @@ pub async fn rpop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
     }
 
     let mut conn = get_conn(key).await?;
-    let val_json: String = conn.rpop(key, None)?;
+    let val_json: String = conn.rpop(key, None).await?;
     json_to_obj(&val_json).map_err(|e| RedisError::from(e))
 }
 
 // 从一个列表尾弹出数据并push到另一个列表头
+// 两个列表需在同一db
 pub async fn rpoplpush<T: DeserializeOwned>(
-    source_key: &str,
-    destination_key: &str,
+    source_key: impl Into<RedisKey>,
+    destination_key: impl Into<RedisKey>,
 ) -> RedisResult<T> {
+    let source_key: &RedisKey = &source_key.into();
+    let destination_key: &RedisKey = &destination_key.into();
     if source_key.is_empty() || destination_key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
             "invalid key",
         )));
     }
+    if source_key.db() != destination_key.db() {
+        return Err(RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "keys must be in the same db",
+        )));
+    }
 
     let mut conn = get_conn(source_key).await?;
-    let val_json: String = conn.rpoplpush(source_key, destination_key)?;
+    let val_json: String = conn.rpoplpush(source_key, destination_key).await?;
     json_to_obj(&val_json).map_err(|e| RedisError::from(e))
 }
 
 // 移除列表中与value相等的元素
-pub async fn lremove(key: &str, count: isize, value: &str) -> RedisResult<usize> {
+pub async fn lremove(key: impl Into<RedisKey>, count: isize, value: &str) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -114,11 +129,12 @@ pub async fn lremove(key: &str, count: isize, value: &str) -> RedisResult<usize>
     }
 
     let mut conn = get_conn(key).await?;
-    conn.lrem(key, count, value)
+    conn.lrem(key, count, value).await
 }
 
 // 获取列表长度
-pub async fn llen(key: &str) -> RedisResult<usize> {
+pub async fn llen(key: impl Into<RedisKey>) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -127,11 +143,12 @@ pub async fn llen(key: &str) -> RedisResult<usize> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.llen(key)
+    conn.llen(key).await
 }
 
 // 获取列表指定索引的元素
-pub async fn lindex<T: DeserializeOwned>(key: &str, index: isize) -> RedisResult<T> {
+pub async fn lindex<T: DeserializeOwned>(key: impl Into<RedisKey>, index: isize) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -140,12 +157,17 @@ pub async fn lindex<T: DeserializeOwned>(key: &str, index: isize) -> RedisResult
     }
 
     let mut conn = get_conn(key).await?;
-    let val_json: String = conn.lindex(key, index)?;
+    let val_json: String = conn.lindex(key, index).await?;
     json_to_obj(&val_json).map_err(|e| RedisError::from(e))
 }
 
 // 设置列表指定索引的元素
-pub async fn lset<T: Serialize>(key: &str, index: isize, value: &T) -> RedisResult<()> {
+pub async fn lset<T: Serialize>(
+    key: impl Into<RedisKey>,
+    index: isize,
+    value: &T,
+) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -155,15 +177,16 @@ pub async fn lset<T: Serialize>(key: &str, index: isize, value: &T) -> RedisResu
 
     let val_json = obj_to_json(value)?;
     let mut conn = get_conn(key).await?;
-    conn.lset(key, index, val_json)
+    conn.lset(key, index, val_json).await
 }
 
 // 获取列表指定范围的元素
 pub async fn lrange<T: DeserializeOwned>(
-    key: &str,
+    key: impl Into<RedisKey>,
     start: isize,
     end: isize,
 ) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -172,7 +195,7 @@ pub async fn lrange<T: DeserializeOwned>(
     }
 
     let mut conn = get_conn(key).await?;
-    let values: Vec<String> = conn.lrange(key, start, end)?;
+    let values: Vec<String> = conn.lrange(key, start, end).await?;
     values
         .into_iter()
         .map(|v| json_to_obj(&v).map_err(|e| RedisError::from(e)))
@@ -180,7 +203,8 @@ pub async fn lrange<T: DeserializeOwned>(
 }
 
 // 修剪列表，保留指定范围的元素
-pub async fn ltrim(key: &str, start: isize, end: isize) -> RedisResult<()> {
+pub async fn ltrim(key: impl Into<RedisKey>, start: isize, end: isize) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -189,5 +213,5 @@ pub async fn ltrim(key: &str, start: isize, end: isize) -> RedisResult<()> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.ltrim(key, start, end)
+    conn.ltrim(key, start, end).await
 }
@@ -1,43 +1,463 @@
+use super::key::RedisKey;
 use super::redipool::get_conn;
-use redis::{Commands, RedisError, RedisResult};
-use std::time::{Duration, Instant};
+use crate::common::overridable::Overridable;
+use futures::future::BoxFuture;
+use redis::{RedisError, RedisResult};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
-// 获取分布式锁
-pub async fn lock(key: &str, expire_secs: u64) -> RedisResult<bool> {
-    if key.is_empty() || expire_secs == 0 {
-        return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid arguments")));
+/// 锁默认过期时间
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+/// 等待锁时的默认最小退避间隔
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_millis(20);
+/// 等待锁时的默认最大退避间隔
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_millis(1000);
+
+// 值与token一致时删除
+const UNLOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// 值与token一致时重置过期时间
+const EXTEND_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return 0
+"#;
+
+/// 分布式锁配置
+#[derive(Debug, Clone)]
+pub struct LockOptions {
+    /// 锁过期时间，持有者异常退出时锁在过期后自动释放
+    pub ttl: Duration,
+    /// 是否开启看门狗，开启后持有期间每隔ttl/3续期一次
+    pub watchdog: bool,
+    /// 获取失败时的最长等待时间，为0时只尝试一次
+    pub wait: Duration,
+    /// 等待时的最小退避间隔
+    pub min_backoff: Duration,
+    /// 等待时的最大退避间隔
+    pub max_backoff: Duration,
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_TTL,
+            watchdog: false,
+            wait: Duration::ZERO,
+            min_backoff: DEFAULT_MIN_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
     }
+}
 
-    let mut conn = get_conn(key).await?;
-    let value = format!("{}", Instant::now().duration_since(Instant::now()).as_nanos());
-    
-    // 使用SET命令的NX选项获取锁
-    let result: Option<String> = redis::cmd("SET").arg(key).arg(&value).arg("NX").arg("EX").arg(expire_secs as i64).query(&mut conn)?;
-    match result {
-        Some(_) => Ok(true),
-        None => Ok(false),
+#[allow(dead_code, reason = "锁选项的构造方法，由业务代码按需调用")]
+impl LockOptions {
+    /// 设置锁过期时间
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// 开启或关闭看门狗
+    pub fn watchdog(mut self, watchdog: bool) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// 设置最长等待时间
+    pub fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// 设置等待时的退避间隔范围
+    pub fn backoff(mut self, min_backoff: Duration, max_backoff: Duration) -> Self {
+        self.min_backoff = min_backoff;
+        self.max_backoff = max_backoff.max(min_backoff);
+        self
+    }
+
+    // 第attempt次(从1开始)获取失败后的退避间隔
+    // 指数增长的上限内随机取值，避免等待者同时重试，先等待的不会因固定间隔总是落后
+    pub(crate) fn backoff_delay(&self, attempt: u32) -> Duration {
+        let min = self.min_backoff.as_millis().max(1) as u64;
+        let max = (self.max_backoff.as_millis() as u64).max(min);
+        let cap = min
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(max);
+        let jitter = (uuid::Uuid::new_v4().as_u128() as u64) % (cap - min + 1);
+        Duration::from_millis(min + jitter)
     }
 }
 
-// 释放分布式锁
-pub async fn unlock(key: &str) -> RedisResult<()> {
-    if key.is_empty() {
-        return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
+/// 分布式锁
+/// 以随机token标识持有者，只有持有者能续期和释放，guard被丢弃时自动释放
+pub struct RedisLock {
+    key: RedisKey,
+    token: String,
+    ttl: Duration,
+    /// 看门狗续期失败，锁已被释放或被其他持有者获取
+    lost: Arc<AtomicBool>,
+    watchdog: Option<JoinHandle<()>>,
+    released: bool,
+}
+
+#[allow(dead_code, reason = "分布式锁接口，由业务代码调用")]
+impl RedisLock {
+    /// 获取分布式锁，超过等待时间仍未获取到时返回None
+    pub async fn acquire(
+        key: impl Into<RedisKey>,
+        options: &LockOptions,
+    ) -> RedisResult<Option<RedisLock>> {
+        let key = key.into();
+        if key.is_empty() || options.ttl.as_millis() == 0 {
+            return Err(RedisError::from((
+                redis::ErrorKind::InvalidClientConfig,
+                "invalid arguments",
+            )));
+        }
+
+        let token = uuid::Uuid::new_v4().simple().to_string();
+        let deadline = Instant::now() + options.wait;
+        let mut attempt = 0;
+        loop {
+            if current().set_nx(&key, &token, options.ttl).await? {
+                break;
+            }
+
+            attempt += 1;
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let delay = options.backoff_delay(attempt).min(deadline - now);
+            tokio::time::sleep(delay).await;
+        }
+
+        let mut lock = RedisLock {
+            key,
+            token,
+            ttl: options.ttl,
+            lost: Arc::new(AtomicBool::new(false)),
+            watchdog: None,
+            released: false,
+        };
+        if options.watchdog {
+            lock.watchdog = Some(tokio::spawn(watchdog(
+                lock.key.clone(),
+                lock.token.clone(),
+                lock.ttl,
+                lock.lost.clone(),
+            )));
+        }
+        Ok(Some(lock))
+    }
+
+    /// 锁的key
+    pub fn key(&self) -> &RedisKey {
+        &self.key
+    }
+
+    /// 持有者token
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// 看门狗续期时发现锁已不再由当前持有者持有
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// 手动续期，返回false表示锁已不再由当前持有者持有
+    pub async fn extend(&self) -> RedisResult<bool> {
+        let extended = current().extend(&self.key, &self.token, self.ttl).await?;
+        if !extended {
+            self.lost.store(true, Ordering::Relaxed);
+        }
+        Ok(extended)
     }
 
-    let mut conn = get_conn(key).await?;
-    let _: usize = conn.del(key)?;
-    Ok(())
+    /// 释放锁，返回false表示锁已过期或已被其他持有者获取
+    pub async fn unlock(mut self) -> RedisResult<bool> {
+        self.stop_watchdog();
+        self.released = true;
+        current().unlock(&self.key, &self.token).await
+    }
+
+    // 停止看门狗
+    fn stop_watchdog(&mut self) {
+        if let Some(watchdog) = self.watchdog.take() {
+            watchdog.abort();
+        }
+    }
 }
 
-// 尝试获取分布式锁，如果获取失败则等待重试
-pub async fn try_lock(key: &str, expire_secs: u64, max_retry: usize, retry_interval_ms: u64) -> RedisResult<bool> {
-    for _ in 0..max_retry {
-        if lock(key, expire_secs).await? {
-            return Ok(true);
+impl Drop for RedisLock {
+    fn drop(&mut self) {
+        self.stop_watchdog();
+        if self.released {
+            return;
+        }
+
+        // drop中无法等待，交给运行时异步释放，不在运行时中时等待锁过期
+        let key = std::mem::take(&mut self.key);
+        let token = std::mem::take(&mut self.token);
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(err) = current().unlock(&key, &token).await {
+                        tracing::warn!("分布式锁释放失败: {} - {}", key, err);
+                    }
+                });
+            }
+            Err(_) => {
+                tracing::warn!("分布式锁未释放，将在过期后自动释放: {}", key);
+            }
         }
-        // 等待一段时间后重试
-        tokio::time::sleep(Duration::from_millis(retry_interval_ms)).await;
     }
-    Ok(false)
-}
\ No newline at end of file
+}
+
+// 获取分布式锁，只尝试一次，未获取到时返回None
+#[allow(dead_code, reason = "分布式锁接口，由业务代码调用")]
+pub async fn lock(key: impl Into<RedisKey>, ttl: Duration) -> RedisResult<Option<RedisLock>> {
+    RedisLock::acquire(key, &LockOptions::default().ttl(ttl)).await
+}
+
+// 尝试获取分布式锁，如果获取失败则退避等待，最多等待wait
+#[allow(dead_code, reason = "分布式锁接口，由业务代码调用")]
+pub async fn try_lock(
+    key: impl Into<RedisKey>,
+    ttl: Duration,
+    wait: Duration,
+) -> RedisResult<Option<RedisLock>> {
+    RedisLock::acquire(key, &LockOptions::default().ttl(ttl).wait(wait)).await
+}
+
+/// 分布式锁存储
+pub trait LockStore: Send + Sync {
+    /// key不存在时写入token，返回是否获取成功
+    fn set_nx<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<bool>>;
+
+    /// 仅在token一致时重置过期时间，返回是否续期成功
+    fn extend<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<bool>>;
+
+    /// 仅在token一致时删除，返回是否删除成功
+    fn unlock<'a>(&'a self, key: &'a RedisKey, token: &'a str) -> BoxFuture<'a, RedisResult<bool>>;
+}
+
+// 当前锁存储，默认使用redis
+static STORE: Overridable<dyn LockStore> = Overridable::new(|| Arc::new(RedisLockStore));
+
+/// 获取当前锁存储
+pub fn current() -> Arc<dyn LockStore> {
+    STORE.current()
+}
+
+/// 基于redis的锁存储
+pub struct RedisLockStore;
+
+impl LockStore for RedisLockStore {
+    // 使用SET命令的NX选项获取锁
+    fn set_nx<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<bool>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            let result: Option<String> = redis::cmd("SET")
+                .arg(key)
+                .arg(token)
+                .arg("NX")
+                .arg("PX")
+                .arg(ttl.as_millis() as u64)
+                .query_async(&mut conn)
+                .await?;
+            Ok(result.is_some())
+        })
+    }
+
+    fn extend<'a>(
+        &'a self,
+        key: &'a RedisKey,
+        token: &'a str,
+        ttl: Duration,
+    ) -> BoxFuture<'a, RedisResult<bool>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            let extended: i64 = redis::Script::new(EXTEND_SCRIPT)
+                .key(key)
+                .arg(token)
+                .arg(ttl.as_millis() as u64)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(extended == 1)
+        })
+    }
+
+    fn unlock<'a>(&'a self, key: &'a RedisKey, token: &'a str) -> BoxFuture<'a, RedisResult<bool>> {
+        Box::pin(async move {
+            let mut conn = get_conn(key).await?;
+            let deleted: i64 = redis::Script::new(UNLOCK_SCRIPT)
+                .key(key)
+                .arg(token)
+                .invoke_async(&mut conn)
+                .await?;
+            Ok(deleted == 1)
+        })
+    }
+}
+
+// 看门狗，持有期间定期续期，续期发现锁已丢失时退出
+async fn watchdog(key: RedisKey, token: String, ttl: Duration, lost: Arc<AtomicBool>) {
+    let interval = (ttl / 3).max(Duration::from_millis(1));
+    loop {
+        tokio::time::sleep(interval).await;
+        match current().extend(&key, &token, ttl).await {
+            Ok(true) => {}
+            Ok(false) => {
+                lost.store(true, Ordering::Relaxed);
+                tracing::warn!("分布式锁已丢失，停止续期: {}", key);
+                return;
+            }
+            // 续期失败时锁仍在有效期内，下次继续尝试
+            Err(err) => tracing::warn!("分布式锁续期失败: {} - {}", key, err),
+        }
+    }
+}
+
+#[cfg(test)]
+pub use memory::MemoryLockStore;
+
+// 测试用的内存实现
+#[cfg(test)]
+mod memory {
+    use super::{LockStore, STORE};
+    use crate::common::redisutils::key::RedisKey;
+    use crate::common::testutil;
+    use futures::future::BoxFuture;
+    use redis::RedisResult;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    /// 进程内的内存锁存储，用于在没有redis的环境下测试
+    #[derive(Default)]
+    pub struct MemoryLockStore {
+        /// key -> (token, 过期时间)
+        entries: Mutex<HashMap<RedisKey, (String, Instant)>>,
+    }
+
+    impl MemoryLockStore {
+        /// 安装进程内共享的内存锁存储，重复调用返回同一实例
+        pub fn install() -> Arc<Self> {
+            let store = testutil::shared(Arc::<Self>::default);
+            STORE.install(store.clone());
+            store
+        }
+
+        /// 当前持有锁的token
+        pub fn holder(&self, key: &RedisKey) -> Option<String> {
+            let entries = self.entries.lock().unwrap();
+            entries
+                .get(key)
+                .filter(|(_, expires_at)| *expires_at > Instant::now())
+                .map(|(token, _)| token.clone())
+        }
+
+        /// 模拟锁过期后被其他持有者获取
+        pub fn steal(&self, key: &RedisKey, token: &str) {
+            let mut entries = self.entries.lock().unwrap();
+            entries.insert(
+                key.clone(),
+                (token.to_string(), Instant::now() + Duration::from_secs(60)),
+            );
+        }
+    }
+
+    // 锁未过期且由token持有
+    fn held_by(
+        entries: &HashMap<RedisKey, (String, Instant)>,
+        key: &RedisKey,
+        token: &str,
+        now: Instant,
+    ) -> bool {
+        entries
+            .get(key)
+            .is_some_and(|(value, expires_at)| value == token && *expires_at > now)
+    }
+
+    impl LockStore for MemoryLockStore {
+        fn set_nx<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            token: &'a str,
+            ttl: Duration,
+        ) -> BoxFuture<'a, RedisResult<bool>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let now = Instant::now();
+                if entries
+                    .get(key)
+                    .is_some_and(|(_, expires_at)| *expires_at > now)
+                {
+                    return Ok(false);
+                }
+                entries.insert(key.clone(), (token.to_string(), now + ttl));
+                Ok(true)
+            })
+        }
+
+        fn extend<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            token: &'a str,
+            ttl: Duration,
+        ) -> BoxFuture<'a, RedisResult<bool>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                let now = Instant::now();
+                if !held_by(&entries, key, token, now) {
+                    return Ok(false);
+                }
+                entries.insert(key.clone(), (token.to_string(), now + ttl));
+                Ok(true)
+            })
+        }
+
+        fn unlock<'a>(
+            &'a self,
+            key: &'a RedisKey,
+            token: &'a str,
+        ) -> BoxFuture<'a, RedisResult<bool>> {
+            Box::pin(async move {
+                let mut entries = self.entries.lock().unwrap();
+                if !held_by(&entries, key, token, Instant::now()) {
+                    return Ok(false);
+                }
+                entries.remove(key);
+                Ok(true)
+            })
+        }
+    }
+}
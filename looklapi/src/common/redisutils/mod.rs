@@ -1,8 +1,23 @@
 pub mod consts;
+pub mod key;
 pub mod operator;
 pub mod list;
 pub mod set;
 pub mod zset;
 pub mod locker;
+pub mod cache;
 pub mod multi_cmds;
-pub mod redipool;
\ No newline at end of file
+pub mod redipool;
+#[cfg(test)]
+mod test;
+
+use crate::app::app_config::AppConfig;
+
+/// 按redis配置设置db前缀解析与本地缓存
+/// 未指定db的key在构造时确定db，需在应用启动时、使用redis前调用
+pub fn init(app_config: &AppConfig) {
+    if let Some(ref redis) = app_config.redis {
+        key::set_legacy_db_prefix(redis.legacy_db_prefix);
+        cache::set_local_enabled(redis.local_cache);
+    }
+}
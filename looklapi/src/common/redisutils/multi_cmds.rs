@@ -1,86 +1,542 @@
-use super::redipool::get_conn;
-use redis::{Commands, Pipeline, RedisError, RedisResult};
+use super::key::RedisKey;
+use super::operator::obj_to_json;
+use super::redipool::{get_conn0, get_dedicated_conn};
+use futures::future::BoxFuture;
+use lazy_static::lazy_static;
+use redis::aio::MultiplexedConnection;
+use redis::{
+    Cmd, FromRedisValue, ParsingError, Pipeline, RedisError, RedisResult, Script, ToRedisArgs,
+    Value,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
-// 批量执行redis命令
-pub async fn multi_exec(key: &str, commands: &[(String, Vec<&str>)]) -> RedisResult<()> {
-    if commands.is_empty() {
-        return Ok(());
+/// json格式存储的值，作为批量执行结果的类型时自动反序列化
+/// 值不存在时使用Option<Json<T>>
+#[derive(Debug, Clone, PartialEq)]
+#[allow(dead_code, reason = "作为批量执行结果的类型由业务代码使用")]
+pub struct Json<T>(pub T);
+
+impl<T: DeserializeOwned> FromRedisValue for Json<T> {
+    fn from_redis_value(v: Value) -> Result<Self, ParsingError> {
+        let json = String::from_redis_value(v)?;
+        serde_json::from_str(&json)
+            .map(Json)
+            .map_err(|e| ParsingError::from(e.to_string()))
     }
+}
 
-    let mut conn = get_conn(key).await?;
-    let mut pipe = Pipeline::new();
+/// 批量执行的redis命令
+/// 所有命令在同一个db中执行，key指定的db与批次不一致时执行失败
+/// 执行结果按命令顺序返回，可以是元组或Vec，ignore的命令不计入结果
+pub struct Batch {
+    db_index: u8,
+    pipe: Pipeline,
+    /// 添加命令时的错误，执行时返回
+    error: Option<RedisError>,
+}
 
-    for (cmd_name, args) in commands {
-        match cmd_name.as_str() {
-            "SET" => {
-                if args.len() >= 2 {
-                    pipe.cmd("SET").arg(&args[0]).arg(&args[1]);
-                }
-            }
-            "GET" => {
-                if !args.is_empty() {
-                    pipe.cmd("GET").arg(&args[0]);
-                }
-            }
-            "DEL" => {
-                if !args.is_empty() {
-                    pipe.cmd("DEL").arg(&args[0]);
-                }
-            }
-            "HSET" => {
-                if args.len() >= 3 {
-                    pipe.cmd("HSET").arg(&args[0]).arg(&args[1]).arg(&args[2]);
-                }
-            }
-            "HGET" => {
-                if args.len() >= 2 {
-                    pipe.cmd("HGET").arg(&args[0]).arg(&args[1]);
-                }
-            }
-            "HMSET" => {
-                if !args.is_empty() {
-                    let mut cmd = pipe.cmd("HMSET").arg(&args[0]);
-                    for arg in &args[1..] {
-                        cmd.arg(arg);
-                    }
-                }
+#[allow(dead_code, reason = "批量命令接口，由业务代码调用")]
+impl Batch {
+    /// 创建指定db的批次
+    pub fn new(db_index: u8) -> Self {
+        Self {
+            db_index,
+            pipe: Pipeline::new(),
+            error: None,
+        }
+    }
+
+    /// 创建key所在db的批次
+    pub fn for_key(key: impl Into<RedisKey>) -> Self {
+        Self::new(key.into().db())
+    }
+
+    /// 使用MULTI/EXEC包裹，批次内的命令原子执行
+    /// 集群模式下批次内的key需使用相同的hash tag
+    pub fn atomic(&mut self) -> &mut Self {
+        self.pipe.atomic();
+        self
+    }
+
+    /// 忽略上一条命令的结果
+    pub fn ignore(&mut self) -> &mut Self {
+        self.pipe.ignore();
+        self
+    }
+
+    /// 批次中的命令数
+    pub fn len(&self) -> usize {
+        self.pipe.len()
+    }
+
+    /// 批次是否为空
+    pub fn is_empty(&self) -> bool {
+        self.pipe.is_empty()
+    }
+
+    /// 添加任意命令
+    pub fn cmd(&mut self, cmd: Cmd) -> &mut Self {
+        self.pipe.add_command(cmd);
+        self
+    }
+
+    /// 设置键值对
+    pub fn set<T: Serialize>(&mut self, key: impl Into<RedisKey>, val: &T) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, val) {
+            self.pipe.cmd("SET").arg(key).arg(json);
+        }
+        self
+    }
+
+    /// 设置带过期时间的键值对
+    pub fn set_ex<T: Serialize>(
+        &mut self,
+        key: impl Into<RedisKey>,
+        val: &T,
+        secs: u64,
+    ) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, val) {
+            self.pipe.cmd("SET").arg(key).arg(json).arg("EX").arg(secs);
+        }
+        self
+    }
+
+    /// 获取键值，结果类型为Option<Json<T>>
+    pub fn get(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("GET", key)
+    }
+
+    /// 删除键
+    pub fn del(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("DEL", key)
+    }
+
+    /// 增减值
+    pub fn incr(&mut self, key: impl Into<RedisKey>, incr_val: i64) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("INCRBY", key).pipe.arg(incr_val);
+        self
+    }
+
+    /// 键是否存在
+    pub fn exist(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("EXISTS", key)
+    }
+
+    /// 设置过期时间(秒)
+    pub fn expire(&mut self, key: impl Into<RedisKey>, secs: u64) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("EXPIRE", key).pipe.arg(secs);
+        self
+    }
+
+    /// 设置过期时间(毫秒)
+    pub fn pexpire(&mut self, key: impl Into<RedisKey>, millisecs: u64) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("PEXPIRE", key).pipe.arg(millisecs);
+        self
+    }
+
+    /// 移除过期时间
+    pub fn persist(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("PERSIST", key)
+    }
+
+    /// 设置哈希字段
+    pub fn hash_set<T: Serialize>(
+        &mut self,
+        key: impl Into<RedisKey>,
+        field: &str,
+        val: &T,
+    ) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, val) {
+            self.pipe.cmd("HSET").arg(key).arg(field).arg(json);
+        }
+        self
+    }
+
+    /// 获取哈希字段，结果类型为Option<Json<T>>
+    pub fn hash_get(&mut self, key: impl Into<RedisKey>, field: &str) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("HGET", key).pipe.arg(field);
+        self
+    }
+
+    /// 删除哈希字段
+    pub fn hash_del(&mut self, key: impl Into<RedisKey>, fields: &[&str]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("HDEL", key).pipe.arg(fields);
+        self
+    }
+
+    /// 哈希字段增减值
+    pub fn hash_incr(&mut self, key: impl Into<RedisKey>, field: &str, incr_val: i64) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("HINCRBY", key).pipe.arg(field).arg(incr_val);
+        self
+    }
+
+    /// 获取所有哈希字段，结果类型为HashMap<String, Json<T>>
+    pub fn hash_get_all(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("HGETALL", key)
+    }
+
+    /// 从列表头部插入
+    pub fn lpush<T: Serialize>(&mut self, key: impl Into<RedisKey>, values: &[T]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(values) = self.encode_list(key, values) {
+            self.pipe.cmd("LPUSH").arg(key).arg(values);
+        }
+        self
+    }
+
+    /// 从列表尾部插入
+    pub fn rpush<T: Serialize>(&mut self, key: impl Into<RedisKey>, values: &[T]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(values) = self.encode_list(key, values) {
+            self.pipe.cmd("RPUSH").arg(key).arg(values);
+        }
+        self
+    }
+
+    /// 从列表头部弹出，结果类型为Option<Json<T>>
+    pub fn lpop(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("LPOP", key)
+    }
+
+    /// 从列表尾部弹出，结果类型为Option<Json<T>>
+    pub fn rpop(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("RPOP", key)
+    }
+
+    /// 获取列表指定范围的元素，结果类型为Vec<Json<T>>
+    pub fn lrange(&mut self, key: impl Into<RedisKey>, start: isize, end: isize) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("LRANGE", key).pipe.arg(start).arg(end);
+        self
+    }
+
+    /// 获取列表长度
+    pub fn llen(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("LLEN", key)
+    }
+
+    /// 向集合添加成员
+    pub fn sadd<T: Serialize>(&mut self, key: impl Into<RedisKey>, members: &[T]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(members) = self.encode_list(key, members) {
+            self.pipe.cmd("SADD").arg(key).arg(members);
+        }
+        self
+    }
+
+    /// 移除集合成员
+    pub fn srem<T: Serialize>(&mut self, key: impl Into<RedisKey>, members: &[T]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(members) = self.encode_list(key, members) {
+            self.pipe.cmd("SREM").arg(key).arg(members);
+        }
+        self
+    }
+
+    /// 是否为集合成员
+    pub fn sismember<T: Serialize>(&mut self, key: impl Into<RedisKey>, member: &T) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, member) {
+            self.pipe.cmd("SISMEMBER").arg(key).arg(json);
+        }
+        self
+    }
+
+    /// 获取集合的所有成员，结果类型为Vec<Json<T>>
+    pub fn smembers(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("SMEMBERS", key)
+    }
+
+    /// 获取集合的大小
+    pub fn scard(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("SCARD", key)
+    }
+
+    /// 向有序集合添加成员
+    pub fn zadd<T: Serialize>(
+        &mut self,
+        key: impl Into<RedisKey>,
+        score: f64,
+        member: &T,
+    ) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, member) {
+            self.pipe.cmd("ZADD").arg(key).arg(score).arg(json);
+        }
+        self
+    }
+
+    /// 移除有序集合成员
+    pub fn zrem<T: Serialize>(&mut self, key: impl Into<RedisKey>, members: &[T]) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(members) = self.encode_list(key, members) {
+            self.pipe.cmd("ZREM").arg(key).arg(members);
+        }
+        self
+    }
+
+    /// 获取有序集合成员的分数
+    pub fn zscore<T: Serialize>(&mut self, key: impl Into<RedisKey>, member: &T) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, member) {
+            self.pipe.cmd("ZSCORE").arg(key).arg(json);
+        }
+        self
+    }
+
+    /// 有序集合成员分数增减
+    pub fn zincrby<T: Serialize>(
+        &mut self,
+        key: impl Into<RedisKey>,
+        increment: f64,
+        member: &T,
+    ) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        if let Some(json) = self.encode(key, member) {
+            self.pipe.cmd("ZINCRBY").arg(key).arg(increment).arg(json);
+        }
+        self
+    }
+
+    /// 获取有序集合指定排名范围的成员，结果类型为Vec<Json<T>>
+    pub fn zrange(&mut self, key: impl Into<RedisKey>, start: isize, stop: isize) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("ZRANGE", key).pipe.arg(start).arg(stop);
+        self
+    }
+
+    /// 获取有序集合的大小
+    pub fn zcard(&mut self, key: impl Into<RedisKey>) -> &mut Self {
+        let key: &RedisKey = &key.into();
+        self.key_cmd("ZCARD", key)
+    }
+
+    /// 执行批次，返回按命令顺序排列的结果
+    pub async fn exec<T: FromRedisValue>(&self) -> RedisResult<T> {
+        if let Some(ref err) = self.error {
+            return Err(err.clone());
+        }
+        let mut conn = get_conn0(self.db_index).await?;
+        self.pipe.query_async(&mut conn).await
+    }
+
+    // 添加只有一个key参数的命令
+    fn key_cmd(&mut self, name: &str, key: &RedisKey) -> &mut Self {
+        self.check_key(key);
+        self.pipe.cmd(name).arg(key);
+        self
+    }
+
+    // 检查key是否属于批次所在的db，记录第一个错误
+    fn check_key(&mut self, key: &RedisKey) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        if key.is_empty() {
+            self.error = Some(invalid_argument("invalid key"));
+        } else if key.db() != self.db_index {
+            self.error = Some(invalid_argument("key not in batch db"));
+        }
+        self.error.is_none()
+    }
+
+    // 检查key并序列化值
+    fn encode<T: Serialize>(&mut self, key: &RedisKey, val: &T) -> Option<String> {
+        if !self.check_key(key) {
+            return None;
+        }
+        match obj_to_json(val) {
+            Ok(json) => Some(json),
+            Err(err) => {
+                self.error = Some(RedisError::from(err));
+                None
             }
-            "LPUSH" => {
-                if !args.is_empty() {
-                    let mut cmd = pipe.cmd("LPUSH").arg(&args[0]);
-                    for arg in &args[1..] {
-                        cmd.arg(arg);
-                    }
-                }
+        }
+    }
+
+    // 检查key并序列化多个值
+    fn encode_list<T: Serialize>(&mut self, key: &RedisKey, values: &[T]) -> Option<Vec<String>> {
+        if !self.check_key(key) {
+            return None;
+        }
+        if values.is_empty() {
+            self.error = Some(invalid_argument("empty values"));
+            return None;
+        }
+        match values.iter().map(obj_to_json).collect() {
+            Ok(values) => Some(values),
+            Err(err) => {
+                self.error = Some(RedisError::from(err));
+                None
             }
-            "RPUSH" => {
-                if !args.is_empty() {
-                    let mut cmd = pipe.cmd("RPUSH").arg(&args[0]);
-                    for arg in &args[1..] {
-                        cmd.arg(arg);
-                    }
-                }
+        }
+    }
+}
+
+/// 乐观事务
+/// WATCH指定的key后调用build读取数据并构建批次，批次以MULTI/EXEC执行
+/// watch的key在执行前被修改时重新执行build，超过max_retry次仍冲突时返回None
+/// build使用的连接已WATCH，读取watch的key需使用该连接
+#[allow(dead_code, reason = "事务与脚本接口，由业务代码调用")]
+pub async fn transaction<T, F>(
+    db_index: u8,
+    watch_keys: &[RedisKey],
+    max_retry: usize,
+    mut build: F,
+) -> RedisResult<Option<T>>
+where
+    T: FromRedisValue,
+    F: for<'c> FnMut(&'c mut MultiplexedConnection) -> BoxFuture<'c, RedisResult<Batch>>,
+{
+    if watch_keys.is_empty() {
+        return Err(invalid_argument("invalid watch keys"));
+    }
+    if watch_keys
+        .iter()
+        .any(|key| key.is_empty() || key.db() != db_index)
+    {
+        return Err(invalid_argument("watch key not in transaction db"));
+    }
+
+    // WATCH的状态属于连接，不能使用共享连接
+    let mut conn = get_dedicated_conn(db_index).await?;
+    for _ in 0..=max_retry {
+        redis::cmd("WATCH")
+            .arg(watch_keys)
+            .exec_async(&mut conn)
+            .await?;
+
+        let mut batch = match build(&mut conn).await {
+            Ok(batch) if batch.db_index == db_index => batch,
+            Ok(_) => {
+                redis::cmd("UNWATCH").exec_async(&mut conn).await?;
+                return Err(invalid_argument("batch not in transaction db"));
             }
-            _ => {
-                // 其他命令可以根据需要添加
+            Err(err) => {
+                redis::cmd("UNWATCH").exec_async(&mut conn).await?;
+                return Err(err);
             }
+        };
+        if let Some(err) = batch.error.take() {
+            redis::cmd("UNWATCH").exec_async(&mut conn).await?;
+            return Err(err);
+        }
+
+        // EXEC返回nil表示watch的key已被修改
+        let result: Option<T> = batch.pipe.atomic().query_async(&mut conn).await?;
+        if result.is_some() {
+            return Ok(result);
         }
     }
+    Ok(None)
+}
+
+// 批量执行redis命令，命令名称与参数原样发送
+pub async fn multi_exec(
+    key: impl Into<RedisKey>,
+    commands: &[(String, Vec<&str>)],
+) -> RedisResult<()> {
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let mut batch = Batch::for_key(key);
+    for (cmd_name, args) in commands {
+        let mut cmd = redis::cmd(cmd_name);
+        cmd.arg(args);
+        batch.cmd(cmd).ignore();
+    }
+    batch.exec().await
+}
 
-    let _: () = pipe.query(&mut conn)?;
-    Ok(())
+lazy_static! {
+    // 已使用的lua脚本，避免重复计算sha1
+    static ref SCRIPTS: RwLock<HashMap<String, Arc<Script>>> = RwLock::new(HashMap::new());
+}
+
+// 获取缓存的脚本
+fn script(source: &str) -> Arc<Script> {
+    if let Some(script) = SCRIPTS.read().unwrap().get(source) {
+        return script.clone();
+    }
+    SCRIPTS
+        .write()
+        .unwrap()
+        .entry(source.to_string())
+        .or_insert_with(|| Arc::new(Script::new(source)))
+        .clone()
 }
 
 // 执行lua脚本
-pub async fn eval(db_index: u8, script: &str, keys: &[&str], args: &[&str]) -> RedisResult<String> {
-    let key = if !keys.is_empty() {
-        keys[0]
-    } else {
-        ""
-    };
-
-    let mut conn = get_conn(key).await?;
-    // 使用通用命令执行eval
-    let result: String = redis::cmd("EVAL").arg(script).arg(keys.len()).arg(keys).arg(args).query(&mut conn)?;
-    Ok(result)
-}
\ No newline at end of file
+// 优先以EVALSHA执行，服务端没有缓存该脚本时自动加载后重试
+// 集群模式下keys需使用相同的hash tag
+#[allow(dead_code, reason = "事务与脚本接口，由业务代码调用")]
+pub async fn eval<T: FromRedisValue, A: ToRedisArgs>(
+    db_index: u8,
+    script_source: &str,
+    keys: &[&str],
+    args: &[A],
+) -> RedisResult<T> {
+    let script = script(script_source);
+    let mut invocation = script.prepare_invoke();
+    for key in keys {
+        invocation.key(*key);
+    }
+    for arg in args {
+        invocation.arg(arg);
+    }
+
+    let mut conn = get_conn0(db_index).await?;
+    invocation.invoke_async(&mut conn).await
+}
+
+// 加载lua脚本，返回脚本的sha1
+#[allow(dead_code, reason = "事务与脚本接口，由业务代码调用")]
+pub async fn script_load(db_index: u8, script_source: &str) -> RedisResult<String> {
+    let mut conn = get_conn0(db_index).await?;
+    script(script_source).load_async(&mut conn).await
+}
+
+// 以sha1执行已加载的lua脚本，脚本未加载时返回NOSCRIPT错误
+// 集群模式下keys需使用相同的hash tag
+#[allow(dead_code, reason = "事务与脚本接口，由业务代码调用")]
+pub async fn evalsha<T: FromRedisValue, A: ToRedisArgs>(
+    db_index: u8,
+    sha1: &str,
+    keys: &[&str],
+    args: &[A],
+) -> RedisResult<T> {
+    let mut conn = get_conn0(db_index).await?;
+    redis::cmd("EVALSHA")
+        .arg(sha1)
+        .arg(keys.len())
+        .arg(keys)
+        .arg(args)
+        .query_async(&mut conn)
+        .await
+}
+
+fn invalid_argument(desc: &'static str) -> RedisError {
+    RedisError::from((redis::ErrorKind::InvalidClientConfig, desc))
+}
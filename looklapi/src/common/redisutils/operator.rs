@@ -1,5 +1,6 @@
+use super::key::RedisKey;
 use super::redipool::{get_conn, get_conn0};
-use redis::{Commands, RedisResult};
+use redis::{AsyncCommands, RedisResult};
 use serde::{Serialize, de::DeserializeOwned};
 
 // 对象转json字符串
@@ -13,7 +14,8 @@ pub fn json_to_obj<T: DeserializeOwned>(json: &str) -> Result<T, serde_json::Err
 }
 
 // 设置键值对
-pub async fn set<T: Serialize>(key: &str, val: &T) -> RedisResult<()> {
+pub async fn set<T: Serialize>(key: impl Into<RedisKey>, val: &T) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -23,11 +25,12 @@ pub async fn set<T: Serialize>(key: &str, val: &T) -> RedisResult<()> {
 
     let val_json = obj_to_json(val)?;
     let mut conn = get_conn(key).await?;
-    conn.set(key, val_json)
+    conn.set(key, val_json).await
 }
 
 // 设置带过期时间的键值对
-pub async fn set_ex<T: Serialize>(key: &str, val: &T, secs: u64) -> RedisResult<()> {
+pub async fn set_ex<T: Serialize>(key: impl Into<RedisKey>, val: &T, secs: u64) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || secs == 0 {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -37,11 +40,12 @@ pub async fn set_ex<T: Serialize>(key: &str, val: &T, secs: u64) -> RedisResult<
 
     let val_json = obj_to_json(val)?;
     let mut conn = get_conn(key).await?;
-    conn.set_ex(key, val_json, secs)
+    conn.set_ex(key, val_json, secs).await
 }
 
 // 获取键值
-pub async fn get<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
+pub async fn get<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -50,12 +54,13 @@ pub async fn get<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
     }
 
     let mut conn = get_conn(key).await?;
-    let val_json: String = conn.get(key)?;
+    let val_json: String = conn.get(key).await?;
     json_to_obj(&val_json).map_err(|e| redis::RedisError::from(e))
 }
 
 // 增减值
-pub async fn incr(key: &str, incr_val: i64) -> RedisResult<i64> {
+pub async fn incr(key: impl Into<RedisKey>, incr_val: i64) -> RedisResult<i64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -64,7 +69,7 @@ pub async fn incr(key: &str, incr_val: i64) -> RedisResult<i64> {
     }
 
     let mut conn = get_conn(key).await?;
-    redis::cmd("INCRBY").arg(key).arg(incr_val).query(&mut conn)
+    redis::cmd("INCRBY").arg(key).arg(incr_val).query_async(&mut conn).await
 }
 
 // 模糊查询keys
@@ -84,6 +89,13 @@ pub async fn scan(db_index: u8, pattern: &str, limit: usize) -> RedisResult<Vec<
     }
 
     let mut conn = get_conn0(db_index).await?;
+    // 集群模式下SCAN只会扫描单个节点，结果不完整
+    if conn.is_cluster() {
+        return Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "scan is not supported in cluster mode",
+        )));
+    }
     let mut cursor = 0;
     let mut keys = Vec::new();
 
@@ -94,7 +106,7 @@ pub async fn scan(db_index: u8, pattern: &str, limit: usize) -> RedisResult<Vec<
             .arg(pattern)
             .arg("COUNT")
             .arg(100)
-            .query(&mut conn)?;
+            .query_async(&mut conn).await?;
         keys.extend(result);
 
         if new_cursor == 0 || (limit > 0 && keys.len() >= limit) {
@@ -112,7 +124,12 @@ pub async fn scan(db_index: u8, pattern: &str, limit: usize) -> RedisResult<Vec<
 }
 
 // 设置哈希字段
-pub async fn hash_set<T: Serialize>(key: &str, field: &str, val: &T) -> RedisResult<()> {
+pub async fn hash_set<T: Serialize>(
+    key: impl Into<RedisKey>,
+    field: &str,
+    val: &T,
+) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || field.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -122,11 +139,15 @@ pub async fn hash_set<T: Serialize>(key: &str, field: &str, val: &T) -> RedisRes
 
     let val_json = obj_to_json(val)?;
     let mut conn = get_conn(key).await?;
-    conn.hset(key, field, val_json)
+    conn.hset(key, field, val_json).await
 }
 
 // 获取哈希字段
-pub async fn hash_get<T: DeserializeOwned>(key: &str, field: &str) -> RedisResult<T> {
+pub async fn hash_get<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+    field: &str,
+) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || field.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -135,12 +156,13 @@ pub async fn hash_get<T: DeserializeOwned>(key: &str, field: &str) -> RedisResul
     }
 
     let mut conn = get_conn(key).await?;
-    let val_json: String = conn.hget(key, field)?;
+    let val_json: String = conn.hget(key, field).await?;
     json_to_obj(&val_json).map_err(|e| redis::RedisError::from(e))
 }
 
 // 获取哈希所有字段
-pub async fn hash_keys(key: &str) -> RedisResult<Vec<String>> {
+pub async fn hash_keys(key: impl Into<RedisKey>) -> RedisResult<Vec<String>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -149,11 +171,12 @@ pub async fn hash_keys(key: &str) -> RedisResult<Vec<String>> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.hkeys(key)
+    conn.hkeys(key).await
 }
 
 // 获取哈希所有值
-pub async fn hash_values<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>> {
+pub async fn hash_values<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -162,7 +185,7 @@ pub async fn hash_values<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>>
     }
 
     let mut conn = get_conn(key).await?;
-    let values: Vec<String> = conn.hvals(key)?;
+    let values: Vec<String> = conn.hvals(key).await?;
     values
         .into_iter()
         .map(|v| json_to_obj(&v).map_err(|e| redis::RedisError::from(e)))
@@ -170,7 +193,10 @@ pub async fn hash_values<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>>
 }
 
 // 获取哈希所有字段和值
-pub async fn hash_get_all<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<(String, T)>> {
+pub async fn hash_get_all<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+) -> RedisResult<Vec<(String, T)>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -179,7 +205,7 @@ pub async fn hash_get_all<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<(St
     }
 
     let mut conn = get_conn(key).await?;
-    let pairs: Vec<(String, String)> = conn.hgetall(key)?;
+    let pairs: Vec<(String, String)> = conn.hgetall(key).await?;
     pairs
         .into_iter()
         .map(|(k, v)| {
@@ -191,60 +217,66 @@ pub async fn hash_get_all<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<(St
 }
 
 // 判断键是否存在
-pub async fn exist(key: &str) -> RedisResult<bool> {
+pub async fn exist(key: impl Into<RedisKey>) -> RedisResult<bool> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Ok(false);
     }
 
     let mut conn = get_conn(key).await?;
-    conn.exists(key)
+    conn.exists(key).await
 }
 
 // 判断哈希字段是否存在
-pub async fn h_exist(key: &str, field: &str) -> RedisResult<bool> {
+pub async fn h_exist(key: impl Into<RedisKey>, field: &str) -> RedisResult<bool> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || field.is_empty() {
         return Ok(false);
     }
 
     let mut conn = get_conn(key).await?;
-    conn.hexists(key, field)
+    conn.hexists(key, field).await
 }
 
 // 删除键
-pub async fn del(key: &str) -> RedisResult<()> {
+pub async fn del(key: impl Into<RedisKey>) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Ok(());
     }
 
     let mut conn = get_conn(key).await?;
-    let _: usize = conn.del(key)?;
+    let _: usize = conn.del(key).await?;
     Ok(())
 }
 
 // 删除哈希字段
-pub async fn h_del(key: &str, field: &str) -> RedisResult<()> {
+pub async fn h_del(key: impl Into<RedisKey>, field: &str) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || field.is_empty() {
         return Ok(());
     }
 
     let mut conn = get_conn(key).await?;
-    let _: usize = conn.hdel(key, field)?;
+    let _: usize = conn.hdel(key, field).await?;
     Ok(())
 }
 
 // 批量删除哈希字段
-pub async fn h_del_multiple(key: &str, fields: &[&str]) -> RedisResult<()> {
+pub async fn h_del_multiple(key: impl Into<RedisKey>, fields: &[&str]) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || fields.is_empty() {
         return Ok(());
     }
 
     let mut conn = get_conn(key).await?;
-    let _: usize = conn.hdel(key, fields)?;
+    let _: usize = conn.hdel(key, fields).await?;
     Ok(())
 }
 
 // 增减哈希值
-pub async fn hash_incr(key: &str, field: &str, incr_val: i64) -> RedisResult<i64> {
+pub async fn hash_incr(key: impl Into<RedisKey>, field: &str, incr_val: i64) -> RedisResult<i64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || field.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -257,11 +289,12 @@ pub async fn hash_incr(key: &str, field: &str, incr_val: i64) -> RedisResult<i64
         .arg(key)
         .arg(field)
         .arg(incr_val)
-        .query(&mut conn)
+        .query_async(&mut conn).await
 }
 
 // 获取哈希长度
-pub async fn h_len(key: &str) -> RedisResult<usize> {
+pub async fn h_len(key: impl Into<RedisKey>) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -270,11 +303,12 @@ pub async fn h_len(key: &str) -> RedisResult<usize> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.hlen(key)
+    conn.hlen(key).await
 }
 
 // 设置键过期时间（秒）
-pub async fn set_key_exp_secs(key: &str, exp_secs: u64) -> RedisResult<()> {
+pub async fn set_key_exp_secs(key: impl Into<RedisKey>, exp_secs: u64) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || exp_secs == 0 {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -283,11 +317,15 @@ pub async fn set_key_exp_secs(key: &str, exp_secs: u64) -> RedisResult<()> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.expire(key, exp_secs as i64)
+    conn.expire(key, exp_secs as i64).await
 }
 
 // 设置键过期时间（毫秒）
-pub async fn set_key_exp_millisecs(key: &str, exp_millisecs: u64) -> RedisResult<()> {
+pub async fn set_key_exp_millisecs(
+    key: impl Into<RedisKey>,
+    exp_millisecs: u64,
+) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() || exp_millisecs == 0 {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -296,11 +334,12 @@ pub async fn set_key_exp_millisecs(key: &str, exp_millisecs: u64) -> RedisResult
     }
 
     let mut conn = get_conn(key).await?;
-    conn.pexpire(key, exp_millisecs as i64)
+    conn.pexpire(key, exp_millisecs as i64).await
 }
 
 // 设置键过期时间（时间戳，秒）
-pub async fn set_key_exp_unix_secs(key: &str, exp_time: u64) -> RedisResult<()> {
+pub async fn set_key_exp_unix_secs(key: impl Into<RedisKey>, exp_time: u64) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -312,11 +351,15 @@ pub async fn set_key_exp_unix_secs(key: &str, exp_time: u64) -> RedisResult<()>
     redis::cmd("EXPIREAT")
         .arg(key)
         .arg(exp_time as i64)
-        .query(&mut conn)
+        .query_async(&mut conn).await
 }
 
 // 设置键过期时间（时间戳，毫秒）
-pub async fn set_key_exp_unix_millisecs(key: &str, exp_time: u64) -> RedisResult<()> {
+pub async fn set_key_exp_unix_millisecs(
+    key: impl Into<RedisKey>,
+    exp_time: u64,
+) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -328,11 +371,12 @@ pub async fn set_key_exp_unix_millisecs(key: &str, exp_time: u64) -> RedisResult
     redis::cmd("PEXPIREAT")
         .arg(key)
         .arg(exp_time as i64)
-        .query(&mut conn)
+        .query_async(&mut conn).await
 }
 
 // 移除键过期时间
-pub async fn remove_key_exp(key: &str) -> RedisResult<()> {
+pub async fn remove_key_exp(key: impl Into<RedisKey>) -> RedisResult<()> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -341,11 +385,12 @@ pub async fn remove_key_exp(key: &str) -> RedisResult<()> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.persist(key)
+    conn.persist(key).await
 }
 
 // 获取键剩余存活时间（秒）
-pub async fn get_key_ttl_secs(key: &str) -> RedisResult<i64> {
+pub async fn get_key_ttl_secs(key: impl Into<RedisKey>) -> RedisResult<i64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -354,11 +399,12 @@ pub async fn get_key_ttl_secs(key: &str) -> RedisResult<i64> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.ttl(key)
+    conn.ttl(key).await
 }
 
 // 获取键剩余存活时间（毫秒）
-pub async fn get_key_ttl_millisecs(key: &str) -> RedisResult<i64> {
+pub async fn get_key_ttl_millisecs(key: impl Into<RedisKey>) -> RedisResult<i64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(redis::RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -367,5 +413,5 @@ pub async fn get_key_ttl_millisecs(key: &str) -> RedisResult<i64> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.pttl(key)
+    conn.pttl(key).await
 }
@@ -1,97 +1,549 @@
-use crate::app::app_config::AppConfig;
+use crate::app::app_config::{self, AppConfig, RedisMode};
 use crate::app::appcontext;
-use redis::{Client, Connection, ConnectionLike, RedisResult};
+use super::key::{MAX_DB_INDEX, RedisKey};
+use redis::aio::{
+    ConnectionLike, ConnectionManager, ConnectionManagerConfig, MultiplexedConnection,
+};
+use redis::cluster::ClusterClientBuilder;
+use redis::cluster_async::ClusterConnection;
+use redis::sentinel::{SentinelClient, SentinelClientBuilder, SentinelServerType};
+use redis::{
+    Client, ClientTlsConfig, Cmd, ConnectionAddr, ConnectionInfo, IntoConnectionInfo, Pipeline,
+    RedisConnectionInfo, RedisError, RedisFuture, RedisResult, ServerErrorKind, TlsCertificates,
+    TlsMode, Value,
+};
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, OnceCell, RwLock};
 
-const MAX_DB_INDEX: u8 = 15;
+/// redis异步连接
+/// 单机与哨兵模式下同一db共享一条多路复用的连接，集群模式下共享集群连接
+/// 集群模式下同一脚本或MULTI/EXEC中的多个key需位于同一slot，
+/// 应使用相同的hash tag，如{order}:schedule与{order}:payload
+/// 克隆开销很小，断线后自动重连
+#[derive(Clone)]
+pub struct RedisConn(ConnKind);
+
+#[derive(Clone)]
+enum ConnKind {
+    Standalone(ConnectionManager),
+    /// 哨兵模式下的主节点连接，附带db索引与创建序号
+    /// 主节点不可用或已切换为从节点时丢弃连接，下次获取时重新向哨兵查询主节点
+    Sentinel(u8, u64, ConnectionManager),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConn {
+    /// 是否为集群连接
+    #[allow(dead_code, reason = "供调用方按部署模式选择多key命令")]
+    pub fn is_cluster(&self) -> bool {
+        matches!(self.0, ConnKind::Cluster(_))
+    }
+}
+
+impl ConnectionLike for RedisConn {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match &mut self.0 {
+            ConnKind::Standalone(conn) => conn.req_packed_command(cmd),
+            ConnKind::Cluster(conn) => conn.req_packed_command(cmd),
+            ConnKind::Sentinel(db_index, seq, conn) => {
+                let (db_index, seq) = (*db_index, *seq);
+                Box::pin(async move {
+                    let result = conn.req_packed_command(cmd).await;
+                    check_master(db_index, seq, &result).await;
+                    result
+                })
+            }
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match &mut self.0 {
+            ConnKind::Standalone(conn) => conn.req_packed_commands(cmd, offset, count),
+            ConnKind::Cluster(conn) => conn.req_packed_commands(cmd, offset, count),
+            ConnKind::Sentinel(db_index, seq, conn) => {
+                let (db_index, seq) = (*db_index, *seq);
+                Box::pin(async move {
+                    let result = conn.req_packed_commands(cmd, offset, count).await;
+                    check_master(db_index, seq, &result).await;
+                    result
+                })
+            }
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match &self.0 {
+            ConnKind::Standalone(conn) | ConnKind::Sentinel(_, _, conn) => conn.get_db(),
+            ConnKind::Cluster(conn) => conn.get_db(),
+        }
+    }
+}
+
+// 哨兵模式连接的创建序号，用于丢弃连接时确认没有被其他任务替换
+static SENTINEL_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// 主节点不可用或已降级为从节点时丢弃该db的连接
+async fn check_master<T>(db_index: u8, seq: u64, result: &RedisResult<T>) {
+    let Err(err) = result else {
+        return;
+    };
+    let master_lost = err.is_io_error()
+        || err.is_connection_dropped()
+        || err.is_connection_refusal()
+        || matches!(
+            err.kind(),
+            redis::ErrorKind::Server(ServerErrorKind::ReadOnly)
+        );
+    if !master_lost {
+        return;
+    }
+
+    let mut pool = REDIS_POOL.write().await;
+    if let Some(RedisConn(ConnKind::Sentinel(_, current, _))) =
+        pool.conns.get(&db_index).and_then(|cell| cell.get())
+        && *current == seq
+    {
+        pool.conns.remove(&db_index);
+        tracing::warn!(
+            "redis主节点不可用，将重新查询主节点: db {} - {}",
+            db_index,
+            err
+        );
+    }
+}
+
+/// 连接配置，由AppConfig解析，证书文件在解析时读取
+pub(super) struct PoolSettings {
+    mode: RedisMode,
+    /// 单机模式的地址
+    addr: Option<(String, u16)>,
+    username: String,
+    password: String,
+    master_name: String,
+    sentinels: Vec<(String, u16)>,
+    sentinel_username: String,
+    sentinel_password: String,
+    nodes: Vec<(String, u16)>,
+    /// 为Some时使用TLS连接
+    tls: Option<TlsCertificates>,
+    connect_timeout: Option<Duration>,
+    response_timeout: Option<Duration>,
+}
+
+impl PoolSettings {
+    // 从配置加载，校验各模式必需的配置并读取证书文件
+    pub(super) fn from_config(config: &app_config::Redis) -> RedisResult<Self> {
+        let mut settings = Self {
+            mode: config.mode,
+            addr: None,
+            username: config.username.clone(),
+            password: config.password.clone(),
+            master_name: config.master_name.clone(),
+            sentinels: Vec::new(),
+            sentinel_username: config.sentinel_username.clone(),
+            sentinel_password: config.sentinel_password.clone(),
+            nodes: Vec::new(),
+            tls: None,
+            connect_timeout: millis(config.connect_timeout_ms()),
+            response_timeout: millis(config.command_timeout_ms()),
+        };
+
+        match config.mode {
+            RedisMode::Standalone => {
+                let port = u16::try_from(config.port)
+                    .map_err(|_| config_error("invalid redis port", config.port.to_string()))?;
+                if config.host.is_empty() {
+                    return Err(config_error("redis host is required", String::new()));
+                }
+                settings.addr = Some((config.host.clone(), port));
+            }
+            RedisMode::Sentinel => {
+                if config.master_name.is_empty() || config.sentinels.is_empty() {
+                    return Err(config_error(
+                        "master_name and sentinels are required in sentinel mode",
+                        String::new(),
+                    ));
+                }
+                settings.sentinels = parse_addrs(&config.sentinels)?;
+            }
+            RedisMode::Cluster => {
+                if config.nodes.is_empty() {
+                    return Err(config_error(
+                        "nodes are required in cluster mode",
+                        String::new(),
+                    ));
+                }
+                settings.nodes = parse_addrs(&config.nodes)?;
+            }
+        }
+
+        if let Some(ref tls) = config.tls {
+            let root_cert = match tls.ca_cert {
+                Some(ref ca_cert) => Some(std::fs::read(ca_cert)?),
+                None => None,
+            };
+            let client_tls = match (&tls.client_cert, &tls.client_key) {
+                (Some(cert), Some(key)) => Some(ClientTlsConfig {
+                    client_cert: std::fs::read(cert)?,
+                    client_key: std::fs::read(key)?,
+                }),
+                (None, None) => None,
+                _ => {
+                    return Err(config_error(
+                        "client_cert and client_key must be set together",
+                        String::new(),
+                    ));
+                }
+            };
+            settings.tls = Some(TlsCertificates {
+                client_tls,
+                root_cert,
+            });
+        }
+
+        Ok(settings)
+    }
+
+    // 节点地址
+    fn connection_addr(&self, host: &str, port: u16) -> ConnectionAddr {
+        if self.tls.is_some() {
+            ConnectionAddr::TcpTls {
+                host: host.to_string(),
+                port,
+                insecure: false,
+                tls_params: None,
+            }
+        } else {
+            ConnectionAddr::Tcp(host.to_string(), port)
+        }
+    }
+
+    // 连接数据节点时的认证与db信息
+    fn redis_info(&self, db_index: u8) -> RedisConnectionInfo {
+        let mut info = RedisConnectionInfo::default().set_db(db_index as i64);
+        if !self.username.is_empty() {
+            info = info.set_username(&self.username);
+        }
+        if !self.password.is_empty() {
+            info = info.set_password(&self.password);
+        }
+        info
+    }
+
+    // 单机模式的客户端
+    fn standalone_client(&self, db_index: u8) -> RedisResult<Client> {
+        let (host, port) = self
+            .addr
+            .as_ref()
+            .ok_or_else(|| config_error("redis host is required", String::new()))?;
+        let info: ConnectionInfo = self
+            .connection_addr(host, *port)
+            .into_connection_info()?
+            .set_redis_settings(self.redis_info(db_index));
+        match self.tls {
+            Some(ref certs) => Client::build_with_tls(info, certs.clone()),
+            None => Client::open(info),
+        }
+    }
+
+    // 哨兵模式的客户端，每个db一个
+    fn sentinel_client(&self, db_index: u8) -> RedisResult<SentinelClient> {
+        let sentinels = self
+            .sentinels
+            .iter()
+            .map(|(host, port)| self.connection_addr(host, *port));
+        let mut builder =
+            SentinelClientBuilder::new(sentinels, &self.master_name, SentinelServerType::Master)?
+                .set_client_to_redis_db(db_index as i64);
+        if !self.username.is_empty() {
+            builder = builder.set_client_to_redis_username(&self.username);
+        }
+        if !self.password.is_empty() {
+            builder = builder.set_client_to_redis_password(&self.password);
+        }
+        if !self.sentinel_username.is_empty() {
+            builder = builder.set_client_to_sentinel_username(&self.sentinel_username);
+        }
+        if !self.sentinel_password.is_empty() {
+            builder = builder.set_client_to_sentinel_password(&self.sentinel_password);
+        }
+        if let Some(ref certs) = self.tls {
+            builder = builder
+                .set_client_to_redis_tls_mode(TlsMode::Secure)
+                .set_client_to_redis_certificates(certs.clone())
+                .set_client_to_sentinel_tls_mode(TlsMode::Secure)
+                .set_client_to_sentinel_certificates(certs.clone());
+        }
+        builder.build()
+    }
+
+    // 集群连接
+    async fn cluster_conn(&self) -> RedisResult<ClusterConnection> {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|(host, port)| {
+                self.connection_addr(host, *port)
+                    .into_connection_info()
+                    .map(|info| info.set_redis_settings(self.redis_info(0)))
+            })
+            .collect::<RedisResult<Vec<_>>>()?;
+        let mut builder = ClusterClientBuilder::new(nodes);
+        if let Some(ref certs) = self.tls {
+            builder = builder.tls(TlsMode::Secure).certs(certs.clone());
+        }
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connection_timeout(timeout);
+        }
+        if let Some(timeout) = self.response_timeout {
+            builder = builder.response_timeout(timeout);
+        }
+        builder.build()?.get_async_connection().await
+    }
+
+    // 共享连接的配置
+    fn manager_config(&self) -> ConnectionManagerConfig {
+        ConnectionManagerConfig::new()
+            .set_connection_timeout(self.connect_timeout)
+            .set_response_timeout(self.response_timeout)
+    }
+}
 
 struct RedisPool {
+    settings: Option<Arc<PoolSettings>>,
+    /// 单机模式的客户端
     clients: HashMap<u8, Client>,
+    /// 哨兵模式的客户端，查询主节点需要独占访问
+    sentinels: HashMap<u8, Arc<Mutex<SentinelClient>>>,
+    /// 单机与哨兵模式下各db的共享连接，连接在锁外创建，同一db并发获取时只创建一次
+    conns: HashMap<u8, Arc<OnceCell<RedisConn>>>,
+    /// 集群模式的共享连接
+    cluster: Arc<OnceCell<RedisConn>>,
 }
 
 impl RedisPool {
     fn new() -> Self {
         Self {
+            settings: None,
             clients: HashMap::new(),
+            sentinels: HashMap::new(),
+            conns: HashMap::new(),
+            cluster: Arc::new(OnceCell::new()),
         }
     }
+}
 
-    async fn get_client(&mut self, db_index: u8) -> RedisResult<&Client> {
-        if db_index > MAX_DB_INDEX {
-            return Err(redis::RedisError::from((
-                redis::ErrorKind::InvalidClientConfig,
-                "db index out of range",
-            )));
-        }
-
-        if !self.clients.contains_key(&db_index) {
-            // 从AppConfig中获取redis配置
-            let rudi_context = appcontext::rudi_context::instance();
-            let context = rudi_context.read().await;
-            let app_config = context.get_ctx().get_single::<AppConfig>();
-            let redis_config = app_config.redis.as_ref().ok_or_else(|| {
-                redis::RedisError::from((
-                    redis::ErrorKind::InvalidClientConfig,
-                    "redis config not found",
-                ))
-            })?;
-
-            // 构建redis连接URL
-            let redis_url = if redis_config.password.is_empty() {
-                format!(
-                    "redis://{}:{}/{}",
-                    redis_config.host, redis_config.port, db_index
-                )
-            } else {
-                format!(
-                    "redis://:{}@{}:{}/{}",
-                    redis_config.password, redis_config.host, redis_config.port, db_index
-                )
+lazy_static::lazy_static! {
+    static ref REDIS_POOL: Arc<RwLock<RedisPool>> = Arc::new(RwLock::new(RedisPool::new()));
+}
+
+// 从AppConfig中读取redis配置
+async fn load_settings() -> RedisResult<PoolSettings> {
+    let rudi_context = appcontext::rudi_context::instance();
+    let context = rudi_context.read().await;
+    let app_config = context.get_ctx().get_single::<AppConfig>();
+    let redis_config = app_config.redis.as_ref().ok_or_else(|| {
+        redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "redis config not found",
+        ))
+    })?;
+    PoolSettings::from_config(redis_config)
+}
+
+// 获取连接配置，首次使用时从AppConfig中加载，加载时不持有连接池锁
+async fn pool_settings() -> RedisResult<Arc<PoolSettings>> {
+    if let Some(ref settings) = REDIS_POOL.read().await.settings {
+        return Ok(settings.clone());
+    }
+    let settings = Arc::new(load_settings().await?);
+    let mut pool = REDIS_POOL.write().await;
+    Ok(pool.settings.get_or_insert(settings).clone())
+}
+
+// 获取指定db的客户端，哨兵模式下每次都向哨兵查询当前的主节点
+// 客户端缓存在连接池中，连接池锁只用于读取与写入缓存，查询主节点时不持有
+async fn pool_client(settings: &PoolSettings, db_index: u8) -> RedisResult<Client> {
+    match settings.mode {
+        RedisMode::Standalone => {
+            if let Some(client) = REDIS_POOL.read().await.clients.get(&db_index) {
+                return Ok(client.clone());
+            }
+            let client = settings.standalone_client(db_index)?;
+            let mut pool = REDIS_POOL.write().await;
+            Ok(pool.clients.entry(db_index).or_insert(client).clone())
+        }
+        RedisMode::Sentinel => {
+            let cached = REDIS_POOL.read().await.sentinels.get(&db_index).cloned();
+            let sentinel = match cached {
+                Some(sentinel) => sentinel,
+                None => {
+                    let sentinel = Arc::new(Mutex::new(settings.sentinel_client(db_index)?));
+                    let mut pool = REDIS_POOL.write().await;
+                    pool.sentinels.entry(db_index).or_insert(sentinel).clone()
+                }
             };
+            let mut sentinel = sentinel.lock().await;
+            with_timeout(settings.connect_timeout, sentinel.async_get_client()).await
+        }
+        RedisMode::Cluster => Err(RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "dedicated connections are not supported in cluster mode",
+        ))),
+    }
+}
 
-            let client = Client::open(redis_url)?;
-            self.clients.insert(db_index, client);
+// 创建指定db的共享连接，集群模式除外
+async fn connect(settings: &PoolSettings, db_index: u8) -> RedisResult<RedisConn> {
+    let client = pool_client(settings, db_index).await?;
+    let manager = ConnectionManager::new_with_config(client, settings.manager_config()).await?;
+    Ok(match settings.mode {
+        RedisMode::Sentinel => {
+            let seq = SENTINEL_SEQ.fetch_add(1, Ordering::Relaxed);
+            RedisConn(ConnKind::Sentinel(db_index, seq, manager))
         }
+        _ => RedisConn(ConnKind::Standalone(manager)),
+    })
+}
 
-        Ok(self.clients.get(&db_index).unwrap())
-    }
+// 毫秒数转为超时时间，不大于0时不限制
+fn millis(ms: i32) -> Option<Duration> {
+    (ms > 0).then(|| Duration::from_millis(ms as u64))
+}
+
+// 解析host:port格式的地址
+fn parse_addrs(addrs: &[String]) -> RedisResult<Vec<(String, u16)>> {
+    addrs
+        .iter()
+        .map(|addr| {
+            addr.rsplit_once(':')
+                .and_then(|(host, port)| Some((host.to_string(), port.parse().ok()?)))
+                .filter(|(host, _)| !host.is_empty())
+                .ok_or_else(|| config_error("invalid redis address", addr.clone()))
+        })
+        .collect()
+}
 
-    async fn get_connection(&mut self, db_index: u8) -> RedisResult<Connection> {
-        let client = self.get_client(db_index).await?;
-        client.get_connection()
+fn config_error(desc: &'static str, detail: String) -> RedisError {
+    if detail.is_empty() {
+        RedisError::from((redis::ErrorKind::InvalidClientConfig, desc))
+    } else {
+        RedisError::from((redis::ErrorKind::InvalidClientConfig, desc, detail))
     }
 }
 
-lazy_static::lazy_static! {
-    static ref REDIS_POOL: Arc<RwLock<RedisPool>> = Arc::new(RwLock::new(RedisPool::new()));
+// 集群只有db 0，带db前缀的key无法使用
+fn check_cluster_db(db_index: u8, detail: impl Fn() -> String) -> RedisResult<()> {
+    if db_index == 0 {
+        return Ok(());
+    }
+    Err(config_error(
+        "db prefixed keys are not supported in cluster mode",
+        detail(),
+    ))
 }
 
-// 根据key获取对应的db索引
-fn get_db_index_from_key(key: &str) -> u8 {
-    // 这里实现与golang版本相同的逻辑，从key中提取db索引
-    // 例如：5_op_shop_stock_ 表示使用db 5
-    if let Some(first_char) = key.chars().next() {
-        if first_char.is_digit(10) {
-            let db_index = first_char.to_digit(10).unwrap() as u8;
-            if db_index <= MAX_DB_INDEX {
-                return db_index;
-            }
-        }
+// 为哨兵查询等没有内置超时的操作限制时间
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output = RedisResult<T>>,
+) -> RedisResult<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fut).await.map_err(|_| {
+            RedisError::from(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "redis connect timed out",
+            ))
+        })?,
+        None => fut.await,
     }
-    0 // 默认使用db 0
 }
 
-// 获取redis连接
-pub async fn get_conn(key: &str) -> RedisResult<Connection> {
-    let db_index = get_db_index_from_key(key);
-    let mut pool = REDIS_POOL.write().await;
-    pool.get_connection(db_index).await
+// 获取key所在db的redis连接
+pub async fn get_conn(key: &RedisKey) -> RedisResult<RedisConn> {
+    shared_conn(key.db(), || key.to_string()).await
 }
 
 // 获取指定db索引的redis连接
-pub async fn get_conn0(db_index: u8) -> RedisResult<Connection> {
-    let mut pool = REDIS_POOL.write().await;
-    pool.get_connection(db_index).await
+pub async fn get_conn0(db_index: u8) -> RedisResult<RedisConn> {
+    shared_conn(db_index, || format!("db {}", db_index)).await
+}
+
+// 获取共享连接，detail用于集群模式下使用非0 db时的错误信息
+async fn shared_conn(db_index: u8, detail: impl Fn() -> String) -> RedisResult<RedisConn> {
+    if db_index > MAX_DB_INDEX {
+        return Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "db index out of range",
+        )));
+    }
+
+    // 已创建的连接只需读锁
+    {
+        let pool = REDIS_POOL.read().await;
+        if let Some(conn) = pool.cluster.get() {
+            check_cluster_db(db_index, &detail)?;
+            return Ok(conn.clone());
+        }
+        if let Some(conn) = pool.conns.get(&db_index).and_then(|cell| cell.get()) {
+            return Ok(conn.clone());
+        }
+    }
+
+    // 首次使用时在锁外创建，写锁只用于登记该db的连接
+    let settings = pool_settings().await?;
+    if settings.mode == RedisMode::Cluster {
+        // 集群连接与db无关
+        check_cluster_db(db_index, &detail)?;
+        let cell = REDIS_POOL.read().await.cluster.clone();
+        let conn = cell
+            .get_or_try_init(|| async {
+                Ok::<_, RedisError>(RedisConn(ConnKind::Cluster(settings.cluster_conn().await?)))
+            })
+            .await?;
+        return Ok(conn.clone());
+    }
+
+    let cell = REDIS_POOL
+        .write()
+        .await
+        .conns
+        .entry(db_index)
+        .or_default()
+        .clone();
+    let conn = cell
+        .get_or_try_init(|| connect(&settings, db_index))
+        .await?;
+    Ok(conn.clone())
+}
+
+// 获取指定db索引的独占连接
+// 共享连接上的命令会交错执行，WATCH等依赖连接状态的命令需使用独占连接，集群模式下不支持
+#[allow(dead_code, reason = "供WATCH等需要独占连接的场景使用")]
+pub async fn get_dedicated_conn(db_index: u8) -> RedisResult<MultiplexedConnection> {
+    if db_index > MAX_DB_INDEX {
+        return Err(redis::RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "db index out of range",
+        )));
+    }
+
+    let settings = pool_settings().await?;
+    let client = pool_client(&settings, db_index).await?;
+
+    let config = redis::AsyncConnectionConfig::new()
+        .set_connection_timeout(settings.connect_timeout)
+        .set_response_timeout(settings.response_timeout);
+    client
+        .get_multiplexed_async_connection_with_config(&config)
+        .await
 }
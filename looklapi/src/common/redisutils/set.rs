@@ -1,10 +1,12 @@
+use super::key::RedisKey;
 use super::operator::{json_to_obj, obj_to_json};
 use super::redipool::get_conn;
-use redis::{Commands, RedisError, RedisResult};
+use redis::{AsyncCommands, RedisError, RedisResult};
 use serde::{Serialize, de::DeserializeOwned};
 
 // 向集合添加一个或多个成员
-pub async fn sadd<T: Serialize>(key: &str, members: &[T]) -> RedisResult<usize> {
+pub async fn sadd<T: Serialize>(key: impl Into<RedisKey>, members: &[T]) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -24,12 +26,13 @@ pub async fn sadd<T: Serialize>(key: &str, members: &[T]) -> RedisResult<usize>
         pipe.sadd(key, member_json);
     }
 
-    let results: Vec<usize> = pipe.query(&mut conn)?;
+    let results: Vec<usize> = pipe.query_async(&mut conn).await?;
     Ok(results.iter().sum())
 }
 
 // 移除集合中的一个或多个成员
-pub async fn srem(key: &str, members: &[&str]) -> RedisResult<usize> {
+pub async fn srem(key: impl Into<RedisKey>, members: &[&str]) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -42,11 +45,12 @@ pub async fn srem(key: &str, members: &[&str]) -> RedisResult<usize> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.srem(key, members)
+    conn.srem(key, members).await
 }
 
 // 判断成员是否在集合中
-pub async fn sismember<T: Serialize>(key: &str, member: &T) -> RedisResult<bool> {
+pub async fn sismember<T: Serialize>(key: impl Into<RedisKey>, member: &T) -> RedisResult<bool> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -56,11 +60,12 @@ pub async fn sismember<T: Serialize>(key: &str, member: &T) -> RedisResult<bool>
 
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
-    conn.sismember(key, member_json)
+    conn.sismember(key, member_json).await
 }
 
 // 获取集合中的所有成员
-pub async fn smembers<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>> {
+pub async fn smembers<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -69,7 +74,7 @@ pub async fn smembers<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>> {
     }
 
     let mut conn = get_conn(key).await?;
-    let members: Vec<String> = conn.smembers(key)?;
+    let members: Vec<String> = conn.smembers(key).await?;
     members
         .into_iter()
         .map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e)))
@@ -77,7 +82,8 @@ pub async fn smembers<T: DeserializeOwned>(key: &str) -> RedisResult<Vec<T>> {
 }
 
 // 获取集合的大小
-pub async fn scard(key: &str) -> RedisResult<usize> {
+pub async fn scard(key: impl Into<RedisKey>) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -86,11 +92,12 @@ pub async fn scard(key: &str) -> RedisResult<usize> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.scard(key)
+    conn.scard(key).await
 }
 
 // 从集合中随机移除并返回一个成员
-pub async fn spop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
+pub async fn spop<T: DeserializeOwned>(key: impl Into<RedisKey>) -> RedisResult<T> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -99,12 +106,16 @@ pub async fn spop<T: DeserializeOwned>(key: &str) -> RedisResult<T> {
     }
 
     let mut conn = get_conn(key).await?;
-    let member_json: String = conn.spop(key)?;
+    let member_json: String = conn.spop(key).await?;
     json_to_obj(&member_json).map_err(|e| RedisError::from(e))
 }
 
 // 从集合中随机返回指定数量的成员
-pub async fn srandmember<T: DeserializeOwned>(key: &str, count: usize) -> RedisResult<Vec<T>> {
+pub async fn srandmember<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+    count: usize,
+) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
@@ -115,7 +126,7 @@ pub async fn srandmember<T: DeserializeOwned>(key: &str, count: usize) -> RedisR
     let mut conn = get_conn(key).await?;
     let mut members = Vec::new();
     for _ in 0..count {
-        if let Ok(member_json) = conn.srandmember::<_, String>(key) {
+        if let Ok(member_json) = conn.srandmember::<_, String>(key).await {
             members.push(member_json);
         }
     }
@@ -125,51 +136,84 @@ pub async fn srandmember<T: DeserializeOwned>(key: &str, count: usize) -> RedisR
         .collect()
 }
 
-// 计算两个集合的差集
-pub async fn sdiff<T: DeserializeOwned>(key1: &str, key2: &str) -> RedisResult<Vec<T>> {
+// 计算两个集合的差集，两个集合需在同一db
+pub async fn sdiff<T: DeserializeOwned>(
+    key1: impl Into<RedisKey>,
+    key2: impl Into<RedisKey>,
+) -> RedisResult<Vec<T>> {
+    let key1: &RedisKey = &key1.into();
+    let key2: &RedisKey = &key2.into();
     if key1.is_empty() || key2.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
             "invalid key",
         )));
     }
+    if key1.db() != key2.db() {
+        return Err(RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "keys must be in the same db",
+        )));
+    }
 
     let mut conn = get_conn(key1).await?;
-    let members: Vec<String> = conn.sdiff(vec![key1, key2])?;
+    let members: Vec<String> = conn.sdiff(vec![key1, key2]).await?;
     members
         .into_iter()
         .map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e)))
         .collect()
 }
 
-// 计算两个集合的交集
-pub async fn sinter<T: DeserializeOwned>(key1: &str, key2: &str) -> RedisResult<Vec<T>> {
+// 计算两个集合的交集，两个集合需在同一db
+pub async fn sinter<T: DeserializeOwned>(
+    key1: impl Into<RedisKey>,
+    key2: impl Into<RedisKey>,
+) -> RedisResult<Vec<T>> {
+    let key1: &RedisKey = &key1.into();
+    let key2: &RedisKey = &key2.into();
     if key1.is_empty() || key2.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
             "invalid key",
         )));
     }
+    if key1.db() != key2.db() {
+        return Err(RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "keys must be in the same db",
+        )));
+    }
 
     let mut conn = get_conn(key1).await?;
-    let members: Vec<String> = conn.sinter(vec![key1, key2])?;
+    let members: Vec<String> = conn.sinter(vec![key1, key2]).await?;
     members
         .into_iter()
         .map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e)))
         .collect()
 }
 
-// 计算两个集合的并集
-pub async fn sunion<T: DeserializeOwned>(key1: &str, key2: &str) -> RedisResult<Vec<T>> {
+// 计算两个集合的并集，两个集合需在同一db
+pub async fn sunion<T: DeserializeOwned>(
+    key1: impl Into<RedisKey>,
+    key2: impl Into<RedisKey>,
+) -> RedisResult<Vec<T>> {
+    let key1: &RedisKey = &key1.into();
+    let key2: &RedisKey = &key2.into();
     if key1.is_empty() || key2.is_empty() {
         return Err(RedisError::from((
             redis::ErrorKind::InvalidClientConfig,
             "invalid key",
         )));
     }
+    if key1.db() != key2.db() {
+        return Err(RedisError::from((
+            redis::ErrorKind::InvalidClientConfig,
+            "keys must be in the same db",
+        )));
+    }
 
     let mut conn = get_conn(key1).await?;
-    let members: Vec<String> = conn.sunion(vec![key1, key2])?;
+    let members: Vec<String> = conn.sunion(vec![key1, key2]).await?;
     members
         .into_iter()
         .map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e)))
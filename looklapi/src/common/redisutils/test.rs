@@ -0,0 +1,414 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use redis::{FromRedisValue, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::app::app_config;
+use crate::common::redisutils::cache::{
+    self, CacheOptions, CacheStore, LocalCache, MemoryCacheStore,
+};
+use crate::common::redisutils::key::{self, RedisKey};
+use crate::common::redisutils::locker::{self, LockOptions, MemoryLockStore, RedisLock};
+use crate::common::redisutils::multi_cmds::{Batch, Json};
+use crate::common::redisutils::redipool::PoolSettings;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Order {
+    id: u32,
+    name: String,
+}
+
+#[test]
+fn test_json_value() {
+    let value = Value::BulkString(br#"{"id":1,"name":"order-1"}"#.to_vec());
+    let Json(order) = Json::<Order>::from_redis_value(value).unwrap();
+    assert_eq!(order.id, 1);
+    assert_eq!(order.name, "order-1");
+
+    // 值不存在
+    let missing = Option::<Json<Order>>::from_redis_value(Value::Nil).unwrap();
+    assert!(missing.is_none());
+
+    // 非json
+    assert!(Json::<Order>::from_redis_value(Value::BulkString(b"x".to_vec())).is_err());
+}
+
+#[tokio::test]
+async fn test_batch_rejects_other_db() {
+    let key = crate::redis_key!(db = 5, "order");
+    let mut batch = Batch::for_key(&key);
+    batch.set(&key, &1).expire(&key, 60).ignore();
+    assert_eq!(batch.len(), 2);
+
+    // 不属于批次db的key在执行前返回错误，不会连接redis
+    batch.get("order");
+    assert!(batch.exec::<()>().await.is_err());
+}
+
+#[test]
+fn test_local_cache_eviction() {
+    let mut local = LocalCache::new(2, Duration::from_secs(60));
+    let (a, b, c) = (
+        RedisKey::from("a"),
+        RedisKey::from("b"),
+        RedisKey::from("c"),
+    );
+    local.insert(&a, "1", Duration::from_secs(10));
+    local.insert(&b, "2", Duration::from_secs(20));
+
+    // 更新已有的key不淘汰其他项
+    local.insert(&b, "3", Duration::from_secs(30));
+    assert_eq!(local.get(&a).as_deref(), Some("1"));
+
+    // 已满时淘汰最早过期的一项
+    local.insert(&c, "4", Duration::from_secs(40));
+    assert_eq!(local.get(&a), None);
+    assert_eq!(local.get(&b).as_deref(), Some("3"));
+    assert_eq!(local.get(&c).as_deref(), Some("4"));
+
+    // 过期时间不超过本地缓存的最长过期时间
+    let mut local = LocalCache::new(2, Duration::from_millis(20));
+    local.insert(&a, "1", Duration::from_secs(10));
+    std::thread::sleep(Duration::from_millis(30));
+    assert_eq!(local.get(&a), None);
+}
+
+/// 生成唯一key，避免测试间共享内存存储时相互影响
+fn unique(prefix: &str) -> String {
+    format!("{}_{}", prefix, uuid::Uuid::new_v4().simple())
+}
+
+#[tokio::test]
+async fn test_cache_single_flight() {
+    MemoryCacheStore::install();
+    let key = unique("cache_order");
+    let loads = Arc::new(AtomicU32::new(0));
+
+    // 并发读取同一key只加载一次
+    let mut tasks = Vec::new();
+    for _ in 0..10 {
+        let key = key.clone();
+        let loads = loads.clone();
+        tasks.push(tokio::spawn(async move {
+            cache::get_or_load(&key, Duration::from_secs(60), || async move {
+                loads.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok::<_, String>(Some(Order {
+                    id: 1,
+                    name: "order-1".to_string(),
+                }))
+            })
+            .await
+        }));
+    }
+    for task in tasks {
+        let order = task.await.unwrap().unwrap().unwrap();
+        assert_eq!(order.id, 1);
+    }
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_cache_negative_and_local() {
+    let store = MemoryCacheStore::install();
+    cache::set_local_enabled(true);
+    let loads = Arc::new(AtomicU32::new(0));
+    let load = |loads: Arc<AtomicU32>, value: Option<u32>| async move {
+        loads.fetch_add(1, Ordering::SeqCst);
+        Ok::<_, String>(value)
+    };
+
+    // 空值同样缓存
+    let key = unique("cache_missing");
+    let options = CacheOptions::default();
+    for _ in 0..3 {
+        let value = cache::get_or_load_with_options(&key, &options, || load(loads.clone(), None))
+            .await
+            .unwrap();
+        assert!(value.is_none());
+    }
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    // 本地缓存在redis中的值被删除后仍然命中，清除本地缓存后重新加载
+    let key = unique("cache_local");
+    let options = CacheOptions::default().local(true);
+    loads.store(0, Ordering::SeqCst);
+    let value = cache::get_or_load_with_options(&key, &options, || load(loads.clone(), Some(7)))
+        .await
+        .unwrap();
+    assert_eq!(value, Some(7));
+    store.del(&RedisKey::from(&key)).await.unwrap();
+    let value = cache::get_or_load_with_options(&key, &options, || load(loads.clone(), Some(8)))
+        .await
+        .unwrap();
+    assert_eq!(value, Some(7));
+
+    cache::invalidate_local(&[key.as_str()]);
+    let value = cache::get_or_load_with_options(&key, &options, || load(loads.clone(), Some(8)))
+        .await
+        .unwrap();
+    assert_eq!(value, Some(8));
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+}
+
+#[looklapi_macro::cached(key = "macro_order:{id}", ttl = 60)]
+async fn cached_order(id: &str, calls: &AtomicU32) -> Order {
+    calls.fetch_add(1, Ordering::SeqCst);
+    Order {
+        id: calls.load(Ordering::SeqCst),
+        name: id.to_string(),
+    }
+}
+
+#[looklapi_macro::cached(key = "macro_order:{id}", db = 3, ttl = 60)]
+async fn cached_order_result(id: &str, fail: bool, calls: &AtomicU32) -> Result<Order, String> {
+    calls.fetch_add(1, Ordering::SeqCst);
+    if fail {
+        return Err("load failed".to_string());
+    }
+    Ok(Order {
+        id: calls.load(Ordering::SeqCst),
+        name: id.to_string(),
+    })
+}
+
+#[looklapi_macro::cache_evict(key = "macro_order:{id}")]
+async fn evict_order(id: String, calls: &AtomicU32) {
+    calls.fetch_add(1, Ordering::SeqCst);
+    drop(id);
+}
+
+#[looklapi_macro::cache_evict(key = "macro_order:{id}", db = 3)]
+async fn evict_order_result(id: &str, fail: bool) -> Result<(), String> {
+    if fail {
+        return Err("update failed".to_string());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cache_macro() {
+    let store = MemoryCacheStore::install();
+    let calls = AtomicU32::new(0);
+    let id = unique("order");
+    let key = RedisKey::from(format!("macro_order:{}", id));
+
+    // 命中缓存时不再执行原函数
+    let first = cached_order(&id, &calls).await;
+    assert_eq!(cached_order(&id, &calls).await, first);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert!(store.get(&key).await.unwrap().is_some());
+
+    // 执行成功后删除缓存，再次调用时重新执行
+    evict_order(id.clone(), &calls).await;
+    assert!(store.get(&key).await.unwrap().is_none());
+    assert_ne!(cached_order(&id, &calls).await, first);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_cache_macro_result_and_db() {
+    let store = MemoryCacheStore::install();
+    let calls = AtomicU32::new(0);
+    let id = unique("order");
+    let key = RedisKey::new(3, format!("macro_order:{}", id));
+
+    // Err不缓存
+    assert!(cached_order_result(&id, true, &calls).await.is_err());
+    assert!(store.get(&key).await.unwrap().is_none());
+
+    // Ok写入指定的db
+    let first = cached_order_result(&id, false, &calls).await.unwrap();
+    assert_eq!(cached_order_result(&id, true, &calls).await, Ok(first));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert!(store.get(&key).await.unwrap().is_some());
+    let db0 = RedisKey::new(0, key.key());
+    assert!(store.get(&db0).await.unwrap().is_none());
+
+    // 返回Err时不删除缓存
+    assert!(evict_order_result(&id, true).await.is_err());
+    assert!(store.get(&key).await.unwrap().is_some());
+    evict_order_result(&id, false).await.unwrap();
+    assert!(store.get(&key).await.unwrap().is_none());
+}
+
+#[test]
+fn test_pool_settings() {
+    let settings = |json: &str| {
+        let config: app_config::Redis = serde_json::from_str(json).unwrap();
+        PoolSettings::from_config(&config)
+    };
+
+    // 兼容原有的单机配置
+    assert!(settings(r#"{"host":"127.0.0.1","port":6379,"password":"","timeout":100}"#).is_ok());
+    assert!(settings(r#"{"host":"","port":6379}"#).is_err());
+
+    assert!(
+        settings(r#"{"mode":"sentinel","master_name":"mymaster","sentinels":["127.0.0.1:26379"]}"#)
+            .is_ok()
+    );
+    assert!(settings(r#"{"mode":"sentinel","sentinels":["127.0.0.1:26379"]}"#).is_err());
+
+    assert!(settings(r#"{"mode":"cluster","nodes":["127.0.0.1:7000","[::1]:7001"]}"#).is_ok());
+    assert!(settings(r#"{"mode":"cluster","nodes":["127.0.0.1"]}"#).is_err());
+    assert!(settings(r#"{"mode":"cluster","nodes":[]}"#).is_err());
+
+    // 客户端证书与私钥需同时配置
+    assert!(settings(r#"{"host":"127.0.0.1","port":6379,"tls":{"client_cert":"a.pem"}}"#).is_err());
+}
+
+#[test]
+fn test_redis_key() {
+    // 完整解析多位数字的db前缀
+    assert_eq!(key::parse_db_prefix("5_op_shop_stock"), Some(5));
+    assert_eq!(key::parse_db_prefix("12_foo"), Some(12));
+    assert_eq!(key::parse_db_prefix("16_foo"), None);
+    assert_eq!(key::parse_db_prefix("13800000000"), None);
+    assert_eq!(key::parse_db_prefix("138_0000"), None);
+    assert_eq!(key::parse_db_prefix("_foo"), None);
+
+    // 前缀保留在key中
+    let legacy = RedisKey::with_db_prefix("12_foo");
+    assert_eq!((legacy.db(), legacy.key()), (12, "12_foo"));
+    assert_eq!(RedisKey::with_db_prefix("13800000000").db(), 0);
+
+    let id = 7;
+    let key = crate::redis_key!(db = 3, "user:{}", id);
+    assert_eq!((key.db(), key.key()), (3, "user:7"));
+    assert_eq!(key.sibling("user:8").db(), 3);
+    assert_eq!(crate::redis_key!("user:{id}"), RedisKey::new(0, "user:7"));
+    assert_ne!(key, RedisKey::new(0, "user:7"));
+
+    // 未指定db的key在构造时确定db，之后切换开关不影响已构造的key
+    key::set_legacy_db_prefix(true);
+    let legacy = RedisKey::from("5_op_shop_stock");
+    key::set_legacy_db_prefix(false);
+    assert_eq!(legacy.db(), 5);
+    assert_eq!(legacy, RedisKey::new(5, "5_op_shop_stock"));
+    assert_eq!(RedisKey::from("5_op_shop_stock").db(), 0);
+}
+
+#[test]
+fn test_lock_backoff_delay() {
+    let options =
+        LockOptions::default().backoff(Duration::from_millis(10), Duration::from_millis(100));
+
+    // 首次失败后等待最小间隔
+    assert_eq!(options.backoff_delay(1), Duration::from_millis(10));
+
+    // 上限按次数指数增长，且不超过最大间隔
+    let mut delays = std::collections::HashSet::new();
+    for _ in 0..200 {
+        let delay = options.backoff_delay(3);
+        assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(40));
+        let delay = options.backoff_delay(64);
+        assert!(delay >= Duration::from_millis(10) && delay <= Duration::from_millis(100));
+        delays.insert(delay);
+    }
+    // 在范围内随机取值
+    assert!(delays.len() > 1);
+
+    // 最大间隔小于最小间隔时取最小间隔，最小间隔至少1ms
+    let options =
+        LockOptions::default().backoff(Duration::from_millis(50), Duration::from_millis(10));
+    assert_eq!(options.backoff_delay(5), Duration::from_millis(50));
+    let options = LockOptions::default().backoff(Duration::ZERO, Duration::ZERO);
+    assert_eq!(options.backoff_delay(5), Duration::from_millis(1));
+}
+
+#[tokio::test]
+async fn test_memory_lock() {
+    let store = MemoryLockStore::install();
+    let key = RedisKey::from(unique("lock"));
+
+    // 已被持有时只尝试一次的获取失败
+    let lock = locker::lock(key.clone(), Duration::from_secs(10))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(store.holder(&key).as_deref(), Some(lock.token()));
+    assert!(
+        locker::lock(key.clone(), Duration::from_secs(10))
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    // 手动释放后不会在drop时再次释放
+    let token = lock.token().to_string();
+    assert!(lock.unlock().await.unwrap());
+    assert_eq!(store.holder(&key), None);
+    store.steal(&key, &token);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(store.holder(&key), Some(token));
+
+    // 已被其他持有者获取时续期与释放均失败
+    let key = RedisKey::from(unique("lock"));
+    let lock = locker::lock(key.clone(), Duration::from_secs(10))
+        .await
+        .unwrap()
+        .unwrap();
+    store.steal(&key, "other");
+    assert!(!lock.extend().await.unwrap());
+    assert!(lock.is_lost());
+    assert!(!lock.unlock().await.unwrap());
+    assert_eq!(store.holder(&key).as_deref(), Some("other"));
+
+    // guard被丢弃时异步释放
+    let key = RedisKey::from(unique("lock"));
+    let lock = locker::lock(key.clone(), Duration::from_secs(10))
+        .await
+        .unwrap()
+        .unwrap();
+    drop(lock);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(store.holder(&key), None);
+}
+
+#[tokio::test]
+async fn test_memory_lock_wait_and_watchdog() {
+    let store = MemoryLockStore::install();
+    let key = RedisKey::from(unique("lock"));
+
+    // 等待期间持有者释放后获取成功，超过等待时间仍被持有时返回None
+    let lock = locker::lock(key.clone(), Duration::from_secs(10))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(
+        locker::try_lock(
+            key.clone(),
+            Duration::from_secs(10),
+            Duration::from_millis(30)
+        )
+        .await
+        .unwrap()
+        .is_none()
+    );
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        lock.unlock().await.unwrap();
+    });
+    let lock = locker::try_lock(key.clone(), Duration::from_secs(10), Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert!(lock.is_some());
+
+    // 看门狗在持有期间续期，发现锁被其他持有者获取后标记丢失
+    let key = RedisKey::from(unique("lock"));
+    let options = LockOptions::default()
+        .ttl(Duration::from_millis(60))
+        .watchdog(true);
+    let lock = RedisLock::acquire(key.clone(), &options)
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    assert_eq!(store.holder(&key).as_deref(), Some(lock.token()));
+    assert!(!lock.is_lost());
+    store.steal(&key, "other");
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(lock.is_lost());
+}
@@ -1,21 +1,28 @@
 use super::operator::{obj_to_json, json_to_obj};
+use super::key::RedisKey;
 use super::redipool::get_conn;
-use redis::{Commands, RedisError, RedisResult};
+use redis::{AsyncCommands, RedisError, RedisResult};
 use serde::{de::DeserializeOwned, Serialize};
 
 // 向有序集合添加一个或多个成员
-pub async fn zadd<T: Serialize>(key: &str, score: f64, member: &T) -> RedisResult<usize> {
+pub async fn zadd<T: Serialize>(
+    key: impl Into<RedisKey>,
+    score: f64,
+    member: &T,
+) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
-    conn.zadd(key, member_json, score)
+    conn.zadd(key, member_json, score).await
 }
 
 // 移除有序集合中的一个或多个成员
-pub async fn zrem(key: &str, members: &[&str]) -> RedisResult<usize> {
+pub async fn zrem(key: impl Into<RedisKey>, members: &[&str]) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
@@ -25,22 +32,28 @@ pub async fn zrem(key: &str, members: &[&str]) -> RedisResult<usize> {
     }
 
     let mut conn = get_conn(key).await?;
-    conn.zrem(key, members)
+    conn.zrem(key, members).await
 }
 
 // 获取有序集合中成员的分数
-pub async fn zscore<T: Serialize>(key: &str, member: &T) -> RedisResult<f64> {
+pub async fn zscore<T: Serialize>(key: impl Into<RedisKey>, member: &T) -> RedisResult<f64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
-    conn.zscore(key, member_json)
+    conn.zscore(key, member_json).await
 }
 
 // 增加有序集合中成员的分数
-pub async fn zincrby<T: Serialize>(key: &str, increment: f64, member: &T) -> RedisResult<f64> {
+pub async fn zincrby<T: Serialize>(
+    key: impl Into<RedisKey>,
+    increment: f64,
+    member: &T,
+) -> RedisResult<f64> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
@@ -48,93 +61,123 @@ pub async fn zincrby<T: Serialize>(key: &str, increment: f64, member: &T) -> Red
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
     // 使用通用命令执行zincrby
-    let result: (f64,) = redis::cmd("ZINCRBY").arg(key).arg(increment).arg(member_json).query(&mut conn)?;
+    let result: (f64,) = redis::cmd("ZINCRBY").arg(key).arg(increment).arg(member_json).query_async(&mut conn).await?;
     Ok(result.0)
 }
 
 // 获取有序集合的大小
-pub async fn zcard(key: &str) -> RedisResult<usize> {
+pub async fn zcard(key: impl Into<RedisKey>) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
-    conn.zcard(key)
+    conn.zcard(key).await
 }
 
 // 获取有序集合中指定分数范围的成员
-pub async fn zrangebyscore<T: DeserializeOwned>(key: &str, min: f64, max: f64) -> RedisResult<Vec<T>> {
+pub async fn zrangebyscore<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+    min: f64,
+    max: f64,
+) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
-    let members: Vec<String> = conn.zrangebyscore(key, min, max)?;
+    let members: Vec<String> = conn.zrangebyscore(key, min, max).await?;
     members.into_iter().map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e))).collect()
 }
 
 // 获取有序集合中指定排名范围的成员（从小到大）
-pub async fn zrange<T: DeserializeOwned>(key: &str, start: isize, stop: isize) -> RedisResult<Vec<T>> {
+pub async fn zrange<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+    start: isize,
+    stop: isize,
+) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
-    let members: Vec<String> = conn.zrange(key, start, stop)?;
+    let members: Vec<String> = conn.zrange(key, start, stop).await?;
     members.into_iter().map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e))).collect()
 }
 
 // 获取有序集合中指定排名范围的成员（从大到小）
-pub async fn zrevrange<T: DeserializeOwned>(key: &str, start: isize, stop: isize) -> RedisResult<Vec<T>> {
+pub async fn zrevrange<T: DeserializeOwned>(
+    key: impl Into<RedisKey>,
+    start: isize,
+    stop: isize,
+) -> RedisResult<Vec<T>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
-    let members: Vec<String> = conn.zrevrange(key, start, stop)?;
+    let members: Vec<String> = conn.zrevrange(key, start, stop).await?;
     members.into_iter().map(|m| json_to_obj(&m).map_err(|e| RedisError::from(e))).collect()
 }
 
 // 获取有序集合中成员的排名（从小到大，从0开始）
-pub async fn zrank<T: Serialize>(key: &str, member: &T) -> RedisResult<Option<usize>> {
+pub async fn zrank<T: Serialize>(
+    key: impl Into<RedisKey>,
+    member: &T,
+) -> RedisResult<Option<usize>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
-    conn.zrank(key, member_json)
+    conn.zrank(key, member_json).await
 }
 
 // 获取有序集合中成员的排名（从大到小，从0开始）
-pub async fn zrevrank<T: Serialize>(key: &str, member: &T) -> RedisResult<Option<usize>> {
+pub async fn zrevrank<T: Serialize>(
+    key: impl Into<RedisKey>,
+    member: &T,
+) -> RedisResult<Option<usize>> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let member_json = obj_to_json(member)?;
     let mut conn = get_conn(key).await?;
-    conn.zrevrank(key, member_json)
+    conn.zrevrank(key, member_json).await
 }
 
 // 移除有序集合中指定分数范围的成员
-pub async fn zremrangebyscore(key: &str, min: f64, max: f64) -> RedisResult<usize> {
+pub async fn zremrangebyscore(key: impl Into<RedisKey>, min: f64, max: f64) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
     // 使用通用命令执行zremrangebyscore
-    let result: (usize,) = redis::cmd("ZREMRANGEBYSCORE").arg(key).arg(min).arg(max).query(&mut conn)?;
+    let result: (usize,) = redis::cmd("ZREMRANGEBYSCORE").arg(key).arg(min).arg(max).query_async(&mut conn).await?;
     Ok(result.0)
 }
 
 // 移除有序集合中指定排名范围的成员
-pub async fn zremrangebyrank(key: &str, start: isize, stop: isize) -> RedisResult<usize> {
+pub async fn zremrangebyrank(
+    key: impl Into<RedisKey>,
+    start: isize,
+    stop: isize,
+) -> RedisResult<usize> {
+    let key: &RedisKey = &key.into();
     if key.is_empty() {
         return Err(RedisError::from((redis::ErrorKind::InvalidClientConfig, "invalid key")));
     }
 
     let mut conn = get_conn(key).await?;
-    conn.zremrangebyrank(key, start, stop)
+    conn.zremrangebyrank(key, start, stop).await
 }
\ No newline at end of file
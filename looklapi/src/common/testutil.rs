@@ -0,0 +1,22 @@
+use lazy_static::lazy_static;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    // 按类型保存的进程内共享实例
+    static ref SHARED: Mutex<HashMap<TypeId, Arc<dyn Any + Send + Sync>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// 获取进程内共享的实例，首次获取时由init创建，之后返回同一实例
+/// 测试并行执行且共用全局的传输层与存储，内存实现需共享同一实例
+pub fn shared<T: Send + Sync + 'static>(init: impl FnOnce() -> Arc<T>) -> Arc<T> {
+    let instance = SHARED
+        .lock()
+        .unwrap()
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| init())
+        .clone();
+    instance.downcast::<T>().unwrap()
+}
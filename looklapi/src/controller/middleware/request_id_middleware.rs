@@ -1,7 +1,8 @@
 use axum::{body::Body, http::Request, response::IntoResponse};
 use mongodb::bson::uuid;
+use tracing::Instrument;
 
-use crate::request_context::X_REQUEST_ID;
+use crate::request_context::{self, X_REQUEST_ID};
 
 pub async fn request_id_middleware(
     mut req: Request<Body>,
@@ -14,5 +15,7 @@ pub async fn request_id_middleware(
 
     // println!("请求ID: {}", req_id);
 
-    next.run(req).await
+    // 请求id写入任务上下文，供发布MQ消息时作为trace id传递
+    let span = tracing::info_span!("request", request_id = %req_id);
+    request_context::scope_request_id(req_id, next.run(req).instrument(span)).await
 }
@@ -1,6 +1,7 @@
 use axum::Router;
 
 pub mod middleware;
+mod mq_admin_controller;
 mod registry;
 mod test_controller;
 mod user_controller;
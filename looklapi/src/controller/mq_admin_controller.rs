@@ -0,0 +1,106 @@
+use axum::{
+    Json, Router,
+    extract::Query,
+    routing::{get, post},
+};
+use serde::Deserialize;
+
+use crate::{
+    app::{AppError, AppResponse},
+    common::mqutils::{
+        admin::{self, DeadLetter},
+        consumer::{self, ConsumerState},
+        models::PoolStats,
+        rabbitmq_pool::RabbitmqConnPool,
+    },
+    controller::{Controller, middleware::manager_validator_middleware},
+};
+
+/// MQ管理控制器，查看消费者、连接池状态与管理死信消息
+struct MqAdminController;
+
+impl Controller for MqAdminController {
+    fn routes() -> Router {
+        Router::new()
+            .route("/mq/admin/consumers", get(list_consumers))
+            .route("/mq/admin/pool", get(pool_stats))
+            .route("/mq/admin/deadletters", get(list_dead_letters))
+            .route("/mq/admin/deadletters/replay", post(replay_dead_letter))
+            .route("/mq/admin/deadletters/purge", post(purge_dead_letters))
+            // 仅管理员可访问
+            .layer(axum::middleware::from_fn(manager_validator_middleware))
+    }
+}
+
+crate::register_controller!(MqAdminController);
+
+/// 死信分页查询参数
+#[derive(Deserialize)]
+struct DeadLetterQuery {
+    /// 死信队列
+    queue: String,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// 死信重新发布参数
+#[derive(Deserialize)]
+struct ReplayRequest {
+    /// 死信队列
+    queue: String,
+    /// 消息id
+    guid: String,
+    /// 替换的消息内容，为空时按原消息发布
+    content: Option<serde_json::Value>,
+}
+
+/// 死信删除参数
+#[derive(Deserialize)]
+struct PurgeRequest {
+    /// 死信队列
+    queue: String,
+    /// 消息id，为空时清空死信队列
+    guid: Option<String>,
+}
+
+async fn list_consumers() -> Result<AppResponse<Vec<ConsumerState>>, AppError> {
+    Ok(AppResponse::new(consumer::consumer_states()))
+}
+
+async fn pool_stats() -> Result<AppResponse<PoolStats>, AppError> {
+    Ok(AppResponse::new(RabbitmqConnPool::get_instance().stats().await))
+}
+
+async fn list_dead_letters(
+    Query(query): Query<DeadLetterQuery>,
+) -> Result<AppResponse<Vec<DeadLetter>>, AppError> {
+    let dead_letters = admin::list_dead_letters(&query.queue, query.offset, query.limit)
+        .await
+        .map_err(admin_error)?;
+    Ok(AppResponse::new(dead_letters))
+}
+
+async fn replay_dead_letter(Json(req): Json<ReplayRequest>) -> Result<AppResponse<bool>, AppError> {
+    admin::replay_dead_letter(&req.queue, &req.guid, req.content)
+        .await
+        .map_err(admin_error)?;
+    Ok(AppResponse::new(true))
+}
+
+async fn purge_dead_letters(Json(req): Json<PurgeRequest>) -> Result<AppResponse<u32>, AppError> {
+    let count = admin::purge_dead_letters(&req.queue, req.guid.as_deref())
+        .await
+        .map_err(admin_error)?;
+    Ok(AppResponse::new(count))
+}
+
+// 转换为应用错误
+fn admin_error(err: admin::AdminError) -> AppError {
+    AppError::new(&err.to_string())
+}
@@ -1,4 +1,5 @@
 use axum::{Router, http::Method};
+use std::time::Duration;
 use tower_http::cors::{AllowHeaders, AllowOrigin, CorsLayer};
 use tracing::info;
 
@@ -24,6 +25,8 @@ async fn main() {
     let app_config = ctx.get_ctx().get_single::<app_config::AppConfig>();
 
     common::loggers::init_logger(app_config).await;
+    // 未指定db的redis key在构造时确定db，需在使用redis前加载
+    common::redisutils::init(app_config);
 
     // 订阅所有收集到的观察者
     app::appcontext::observer::register_app_observers();
@@ -37,7 +40,42 @@ async fn main() {
     let listener = tokio::net::TcpListener::bind(listen).await.unwrap();
     println!("listening on {}", listener.local_addr().unwrap());
     // info!("app start 完成");
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // 停止消费并关闭MQ连接
+    common::mqutils::rabbitmq_pool::RabbitmqConnPool::get_instance()
+        .shutdown(Duration::from_secs(10))
+        .await;
+    info!("app stopped");
+}
+
+// 等待ctrl+c或SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("shutdown signal received");
 }
 
 fn app() -> Router {
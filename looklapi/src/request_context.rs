@@ -17,3 +17,18 @@ pub enum LoginInfo {
     User(()),
     Admin(()),
 }
+
+tokio::task_local! {
+    /// 当前任务的请求id，由request_id_middleware设置，MQ消费时设置为消息的trace id
+    static REQUEST_ID: String;
+}
+
+/// 在指定请求id的上下文中执行
+pub async fn scope_request_id<F: std::future::Future>(req_id: String, f: F) -> F::Output {
+    REQUEST_ID.scope(req_id, f).await
+}
+
+/// 获取当前任务的请求id
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|req_id| req_id.clone()).ok()
+}